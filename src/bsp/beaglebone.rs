@@ -1,105 +1,130 @@
 //use armv7::VirtualAddress;
 //use armv7::PhysicalAddress;
 use crate::device::control_mod::*;
+use crate::device::pad::Pad;
 use crate::memory_map;
 use crate::device::gpio;
 use armv7::structures::paging;
 //use register::{mmio::*, register_bitfields, Field};
 
 
-fn get_pin_addr(gpio_pin: u8) -> Option<u32> {
-    // calculate the offset in the control module accoirding to P9Header
+fn get_pin_pad(gpio_pin: u8) -> Option<Pad> {
+    // the pad in the control module according to the P8 and P9 headers
     // omitted allocated pins
     match gpio_pin {
-        38 => Some(0x018),
-        39 => Some(0x01C),
-        34 => Some(0x008),
-        35 => Some(0x00c),
-        66 => Some(0x090),
-        67 => Some(0x094),
-        69 => Some(0x09c),
-        68 => Some(0x098),
-        45 => Some(0x034),
-        44 => Some(0x030),
-        23 => Some(0x024),
-        26 => Some(0x028),
-        47 => Some(0x03C),
-        46 => Some(0x038),
-        27 => Some(0x02C),
-        65 => Some(0x08C),
-        22 => Some(0x020),
-        63 => Some(0x084),
-        62 => Some(0x080),
-        37 => Some(0x014),
-        36 => Some(0x010),
-        33 => Some(0x004),
-        32 => Some(0x000),
-        61 => Some(0x07C),
-        86 => Some(0x0e0),
-        88 => Some(0x0e8),
-        87 => Some(0x0e4),
-        89 => Some(0x0eC),
-        10 => Some(0x0d8),
-        11 => Some(0x0dc),
-        9 => Some(0x0d4),
-        81 => Some(0x0cc),
-        8 => Some(0x0d0),
-        80 => Some(0x0c8),
-        78 => Some(0x0c0),
-        79 => Some(0x0c4),
-        76 => Some(0x0b8),
-        77 => Some(0x0bc),
-        74 => Some(0x0b0),
-        75 => Some(0x0b4),
-        72 => Some(0x0a8),
-        73 => Some(0x0ac),
-        70 => Some(0x0a0),
-        71 => Some(0x0a4),
+        38 => Some(Pad::GpmcAd6),
+        39 => Some(Pad::GpmcAd7),
+        34 => Some(Pad::GpmcAd2),
+        35 => Some(Pad::GpmcAd3),
+        66 => Some(Pad::GpmcAdvnAle),
+        67 => Some(Pad::GpmcOenRen),
+        69 => Some(Pad::GpmcBe0nCle),
+        68 => Some(Pad::GpmcWen),
+        45 => Some(Pad::GpmcAd13),
+        44 => Some(Pad::GpmcAd12),
+        23 => Some(Pad::GpmcAd9),
+        26 => Some(Pad::GpmcAd10),
+        47 => Some(Pad::GpmcAd15),
+        46 => Some(Pad::GpmcAd14),
+        27 => Some(Pad::GpmcAd11),
+        65 => Some(Pad::GpmcClk),
+        22 => Some(Pad::GpmcAd8),
+        63 => Some(Pad::GpmcCsn2),
+        62 => Some(Pad::GpmcCsn1),
+        37 => Some(Pad::GpmcAd5),
+        36 => Some(Pad::GpmcAd4),
+        33 => Some(Pad::GpmcAd1),
+        32 => Some(Pad::GpmcAd0),
+        61 => Some(Pad::GpmcCsn0),
+        86 => Some(Pad::LcdVsync),
+        88 => Some(Pad::LcdPclk),
+        87 => Some(Pad::LcdHsync),
+        89 => Some(Pad::LcdAcBiasEn),
+        10 => Some(Pad::LcdData14),
+        11 => Some(Pad::LcdData15),
+        9 => Some(Pad::LcdData13),
+        81 => Some(Pad::LcdData11),
+        8 => Some(Pad::LcdData12),
+        80 => Some(Pad::LcdData10),
+        78 => Some(Pad::LcdData8),
+        79 => Some(Pad::LcdData9),
+        76 => Some(Pad::LcdData6),
+        77 => Some(Pad::LcdData7),
+        74 => Some(Pad::LcdData4),
+        75 => Some(Pad::LcdData5),
+        72 => Some(Pad::LcdData2),
+        73 => Some(Pad::LcdData3),
+        70 => Some(Pad::LcdData0),
+        71 => Some(Pad::LcdData1),
 
-        30 => Some(0x070),
-        60 => Some(0x078),
-        31 => Some(0x074),
-        50 => Some(0x048),
-        48 => Some(0x040),
-        51 => Some(0x04c),
-        5 => Some(0x15c),
-        4 => Some(0x158),
-        3 => Some(0x154),
-        2 => Some(0x150),
-        49 => Some(0x044),
-        15 => Some(0x184),
-        14 => Some(0x180),
-        115 => Some(0x1a4),
-        20 => Some(0x1b4),
-        116 => Some(0x1a8),
-        7 => Some(0x164),
+        30 => Some(Pad::GpmcWait0),
+        60 => Some(Pad::GpmcBe1n),
+        31 => Some(Pad::GpmcWpn),
+        50 => Some(Pad::GpmcA2),
+        48 => Some(Pad::GpmcA0),
+        51 => Some(Pad::GpmcA3),
+        5 => Some(Pad::Spi0Cs0),
+        4 => Some(Pad::Spi0D1),
+        3 => Some(Pad::Spi0D0),
+        2 => Some(Pad::Spi0Sclk),
+        49 => Some(Pad::GpmcA1),
+        15 => Some(Pad::Uart1Txd),
+        14 => Some(Pad::Uart1Rxd),
+        115 => Some(Pad::Mcasp0Fsr),
+        20 => Some(Pad::XdmaEventIntr1),
+        116 => Some(Pad::Mcasp0Axr1),
+        7 => Some(Pad::Ecap0InPwm0Out),
         _ => None,
     }
 }
 
 // Initialize the GPIO pins as pins
 pub fn set_gpio_status(pin: u8, control_mod: &Control, state: u32) -> Option<()> {
-    let index = get_pin_addr(pin)?;
-    control_mod.set(index as usize, state);
+    let pad = get_pin_pad(pin)?;
+    control_mod.set(pad.index(), state);
     Some(())
 }
 pub fn get_gpio_status(pin: u8, control_mod: &Control) -> Option<u32> {
-    let index = get_pin_addr(pin)?;
-    control_mod.get(index as usize)
+    let pad = get_pin_pad(pin)?;
+    control_mod.get(pad.index())
+}
+/// Mux a header pin to its GPIO with the given pull and receiver settings
+pub fn configure_gpio(pin: u8, control_mod: &Control, pull: Pull, receiver: bool) -> Option<()> {
+    let pad = get_pin_pad(pin)?;
+    let config = match pull {
+        Pull::Up => PadConfig::new(MuxMode::Mode7).pull_up(),
+        Pull::Down => PadConfig::new(MuxMode::Mode7).pull_down(),
+        Pull::Disabled => PadConfig::new(MuxMode::Mode7).pull_disabled(),
+    };
+    let config = if receiver {
+        config.receiver_enable()
+    } else {
+        config
+    };
+    control_mod.configure(pad, config).ok()
 }
 // Initialize the Uart
 //
 
-pub fn get_gpio(device_mapper: &paging::DeviceVmemMapper, pin: u8) -> Option<gpio::Pin<gpio::Output>> {
-    let bank_phys_addr = match pin / 32 {
-        0 => memory_map::GPIO0,
-        1 => memory_map::GPIO1,
-        2 => memory_map::GPIO2,
-        3 => memory_map::GPIO3,
-        _ => return None,
-    };
-    let bank_addr = device_mapper.lookup(bank_phys_addr)?;
-    let mut gpio = unsafe { gpio::Gpio::new(bank_addr) };
-    gpio.get_pin_as_output(pin % 32)
+/// Get the controller for all four GPIO banks
+///
+/// # Safety
+/// Must only be called once, every pin is then handed out by the controller
+pub unsafe fn get_gpio_controller(
+    device_mapper: &paging::DeviceVmemMapper,
+) -> Option<gpio::GpioController> {
+    let banks = [
+        device_mapper.lookup(memory_map::GPIO0)?,
+        device_mapper.lookup(memory_map::GPIO1)?,
+        device_mapper.lookup(memory_map::GPIO2)?,
+        device_mapper.lookup(memory_map::GPIO3)?,
+    ];
+    Some(gpio::GpioController::new(banks))
 }
 
+pub fn get_gpio(
+    controller: &mut gpio::GpioController,
+    pin: u8,
+) -> Result<gpio::Pin<gpio::Output>, gpio::GpioError> {
+    controller.get_pin_as_output(pin)
+}
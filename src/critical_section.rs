@@ -0,0 +1,126 @@
+//! Critical sections and data shared between the main code and interrupt
+//! handlers
+//!
+//! A critical section masks IRQ and FIQ in the CPSR of the current core. The
+//! `Mutex` only hands out references inside of a critical section, so that a
+//! driver can be put into a static:
+//!
+//! ```ignore
+//! static UART: Mutex<RefCell<Option<Uart>>> = Mutex::new(RefCell::new(None));
+//!
+//! critical_section::free(|cs| UART.borrow(cs).replace(Some(uart)));
+//! UART.lock(|uart| writeln!(uart.as_mut().unwrap(), "Hello"));
+//! ```
+// Author: Moritz Doll
+// License: MIT
+
+#[cfg(target_arch = "arm")]
+use core::arch::asm;
+use core::cell::{RefCell, UnsafeCell};
+
+/// Token that is only available while IRQ and FIQ are masked
+pub struct CriticalSection {
+    _private: (),
+}
+
+/// Mask IRQ and FIQ and return the previous CPSR
+#[cfg(target_arch = "arm")]
+#[inline]
+fn disable_interrupts() -> u32 {
+    let cpsr: u32;
+    unsafe {
+        asm!("mrs {0}, cpsr", "cpsid if", out(reg) cpsr, options(nostack, preserves_flags));
+    }
+    cpsr
+}
+
+/// Unmask IRQ and return the previous CPSR
+///
+/// Waits for outstanding writes, e.g. to the interrupt controller, first.
+#[cfg(target_arch = "arm")]
+#[inline]
+pub(crate) fn enable_irq() -> u32 {
+    let cpsr: u32;
+    unsafe {
+        asm!("mrs {0}, cpsr", "dsb", "cpsie i", out(reg) cpsr, options(nostack, preserves_flags));
+    }
+    cpsr
+}
+
+/// Restore the interrupt masks of a previous CPSR
+#[cfg(target_arch = "arm")]
+#[inline]
+pub(crate) fn restore_interrupts(cpsr: u32) {
+    unsafe {
+        asm!("msr cpsr_c, {0}", in(reg) cpsr, options(nostack, preserves_flags));
+    }
+}
+
+/// There are no interrupts to mask on a host, e.g. in the tests
+#[cfg(not(target_arch = "arm"))]
+#[inline]
+fn disable_interrupts() -> u32 {
+    0
+}
+
+#[cfg(not(target_arch = "arm"))]
+#[inline]
+pub(crate) fn enable_irq() -> u32 {
+    0
+}
+
+#[cfg(not(target_arch = "arm"))]
+#[inline]
+pub(crate) fn restore_interrupts(_cpsr: u32) {}
+
+/// Execute the closure with IRQ and FIQ masked
+///
+/// Critical sections can be nested, the interrupt masks are restored to the
+/// state before the call.
+#[inline]
+pub fn free<F, R>(f: F) -> R
+where
+    F: FnOnce(&CriticalSection) -> R,
+{
+    let cpsr = disable_interrupts();
+    let result = f(&CriticalSection { _private: () });
+    restore_interrupts(cpsr);
+    result
+}
+
+/// Data that can only be accessed inside of a critical section
+pub struct Mutex<T> {
+    inner: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    pub const fn new(value: T) -> Self {
+        Mutex {
+            inner: UnsafeCell::new(value),
+        }
+    }
+    /// Borrow the data for the duration of the critical section
+    pub fn borrow<'cs>(&'cs self, _cs: &'cs CriticalSection) -> &'cs T {
+        unsafe { &*self.inner.get() }
+    }
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> Mutex<RefCell<T>> {
+    /// Mutably borrow the data inside of a new critical section
+    ///
+    /// # Panics
+    /// Panics if the data is already borrowed, i.e. if `lock` is nested for
+    /// the same mutex.
+    pub fn lock<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        free(|cs| f(&mut self.borrow(cs).borrow_mut()))
+    }
+}
+
+// The data is only accessed with interrupts masked on a single core
+unsafe impl<T: Send> Sync for Mutex<T> {}
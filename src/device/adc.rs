@@ -0,0 +1,768 @@
+//! The touchscreen and analog to digital converter subsystem (TSC_ADC_SS)
+//!
+//! The 12 bit ADC is driven by a sequencer of 16 steps. Every enabled step
+//! samples one of the inputs AIN0 to AIN7 and writes the result into one of
+//! the two FIFOs. The inputs are measured against the 1.8 V reference.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use core::hint;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u32,
+    IRQ [
+        HW_PEN_EVENT_ASYNC OFFSET(0) NUMBITS(1) [],
+        END_OF_SEQUENCE OFFSET(1) NUMBITS(1) [],
+        FIFO0_THRESHOLD OFFSET(2) NUMBITS(1) [],
+        FIFO0_OVERRUN OFFSET(3) NUMBITS(1) [],
+        FIFO0_UNDERFLOW OFFSET(4) NUMBITS(1) [],
+        FIFO1_THRESHOLD OFFSET(5) NUMBITS(1) [],
+        FIFO1_OVERRUN OFFSET(6) NUMBITS(1) [],
+        FIFO1_UNDERFLOW OFFSET(7) NUMBITS(1) [],
+        OUT_OF_RANGE OFFSET(8) NUMBITS(1) [],
+        PEN_UP_EVENT OFFSET(9) NUMBITS(1) [],
+        HW_PEN_EVENT_SYNC OFFSET(10) NUMBITS(1) []
+    ],
+    CTRL [
+        ENABLE OFFSET(0) NUMBITS(1) [],
+        STEP_ID_TAG OFFSET(1) NUMBITS(1) [],
+        STEPCONFIG_WRITEPROTECT_N OFFSET(2) NUMBITS(1) [Protected = 0, Writable = 1],
+        ADC_BIAS_SELECT OFFSET(3) NUMBITS(1) [],
+        POWER_DOWN OFFSET(4) NUMBITS(1) [],
+        AFE_PEN_CTRL OFFSET(5) NUMBITS(2) [
+            Disabled = 0,
+            FourWire = 1,
+            FiveWire = 2
+        ],
+        TOUCH_SCREEN_ENABLE OFFSET(7) NUMBITS(1) [],
+        HW_EVENT_MAPPING OFFSET(8) NUMBITS(1) [],
+        HW_PREEMPT OFFSET(9) NUMBITS(1) []
+    ],
+    ADCSTAT [
+        STEP_ID OFFSET(0) NUMBITS(5) [],
+        FSM_BUSY OFFSET(5) NUMBITS(1) [],
+        PEN_IRQ0 OFFSET(6) NUMBITS(1) [],
+        PEN_IRQ1 OFFSET(7) NUMBITS(1) []
+    ],
+    ADCRANGE [
+        LOW_RANGE_DATA OFFSET(0) NUMBITS(12) [],
+        HIGH_RANGE_DATA OFFSET(16) NUMBITS(12) []
+    ],
+    ADC_CLKDIV [
+        ADC_CLKDIV OFFSET(0) NUMBITS(16) []
+    ],
+    STEPCONFIG [
+        MODE OFFSET(0) NUMBITS(2) [
+            SwOneShot = 0,
+            SwContinuous = 1,
+            HwOneShot = 2,
+            HwContinuous = 3
+        ],
+        AVERAGING OFFSET(2) NUMBITS(3) [],
+        XPPSW_SWC OFFSET(5) NUMBITS(1) [],
+        XNNSW_SWC OFFSET(6) NUMBITS(1) [],
+        YPPSW_SWC OFFSET(7) NUMBITS(1) [],
+        YNNSW_SWC OFFSET(8) NUMBITS(1) [],
+        XNPSW_SWC OFFSET(9) NUMBITS(1) [],
+        YPNSW_SWC OFFSET(10) NUMBITS(1) [],
+        WPNSW_SWC OFFSET(11) NUMBITS(1) [],
+        SEL_RFP_SWC OFFSET(12) NUMBITS(3) [],
+        SEL_INM_SWC OFFSET(15) NUMBITS(4) [AdcRefM = 8],
+        SEL_INP_SWC OFFSET(19) NUMBITS(4) [AdcRefM = 8],
+        SEL_RFM_SWC OFFSET(23) NUMBITS(2) [],
+        DIFF_CNTRL OFFSET(25) NUMBITS(1) [],
+        FIFO_SELECT OFFSET(26) NUMBITS(1) [],
+        RANGE_CHECK OFFSET(27) NUMBITS(1) []
+    ],
+    STEPDELAY [
+        OPENDELAY OFFSET(0) NUMBITS(18) [],
+        SAMPLEDELAY OFFSET(24) NUMBITS(8) []
+    ],
+    FIFOCOUNT [
+        WORDS_IN_FIFO OFFSET(0) NUMBITS(7) []
+    ],
+    FIFOTHRESHOLD [
+        FIFO_THRESHOLD_LEVEL OFFSET(0) NUMBITS(6) []
+    ],
+    FIFODATA [
+        ADCDATA OFFSET(0) NUMBITS(12) [],
+        ADCCHNLID OFFSET(16) NUMBITS(4) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct StepRegisterBlock {
+    STEPCONFIG: ReadWrite<u32, STEPCONFIG::Register>, // 0x00
+    STEPDELAY: ReadWrite<u32, STEPDELAY::Register>,   // 0x04
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct FifoRegisterBlock {
+    FIFOCOUNT: ReadOnly<u32, FIFOCOUNT::Register>, // 0x00
+    FIFOTHRESHOLD: ReadWrite<u32, FIFOTHRESHOLD::Register>, // 0x04
+    _DMAREQ: ReadWrite<u32, ()>,                   // 0x08
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    _REVISION: ReadOnly<u32, ()>, // 0x00
+    __reserved_0: [u32; 3],
+    _SYSCONFIG: ReadWrite<u32, ()>, // 0x10
+    __reserved_1: [u32; 4],
+    IRQSTATUS_RAW: ReadOnly<u32, IRQ::Register>,  // 0x24
+    IRQSTATUS: ReadWrite<u32, IRQ::Register>,     // 0x28
+    IRQENABLE_SET: ReadWrite<u32, IRQ::Register>, // 0x2C
+    IRQENABLE_CLR: ReadWrite<u32, IRQ::Register>, // 0x30
+    _IRQWAKEUP: ReadWrite<u32, ()>,               // 0x34
+    _DMAENABLE_SET: ReadWrite<u32, ()>,           // 0x38
+    _DMAENABLE_CLR: ReadWrite<u32, ()>,           // 0x3C
+    CTRL: ReadWrite<u32, CTRL::Register>,         // 0x40
+    ADCSTAT: ReadOnly<u32, ADCSTAT::Register>,    // 0x44
+    ADCRANGE: ReadWrite<u32, ADCRANGE::Register>, // 0x48
+    ADC_CLKDIV: ReadWrite<u32, ADC_CLKDIV::Register>, // 0x4C
+    _ADC_MISC: ReadWrite<u32, ()>,                // 0x50
+    STEPENABLE: ReadWrite<u32, ()>,               // 0x54
+    IDLECONFIG: ReadWrite<u32, STEPCONFIG::Register>, // 0x58
+    TS_CHARGE_STEPCONFIG: ReadWrite<u32, STEPCONFIG::Register>, // 0x5C
+    TS_CHARGE_DELAY: ReadWrite<u32, STEPDELAY::Register>, // 0x60
+    STEP: [StepRegisterBlock; 16],                // 0x64
+    FIFO: [FifoRegisterBlock; 2],                 // 0xE4
+    __reserved_2: [u32; 1],
+    FIFO0DATA: ReadOnly<u32, FIFODATA::Register>, // 0x100
+    __reserved_3: [u32; 63],
+    FIFO1DATA: ReadOnly<u32, FIFODATA::Register>, // 0x200
+}
+
+/// Number of steps of the sequencer
+pub const STEP_NUM: u8 = 16;
+/// Number of analog inputs
+pub const CHANNEL_NUM: u8 = 8;
+/// Depth of each FIFO
+pub const FIFO_DEPTH: u8 = 64;
+/// Reference voltage in millivolts
+pub const REFERENCE_MV: u32 = 1800;
+/// Largest raw value
+pub const MAX_VALUE: u16 = 0xfff;
+/// Number of polls of the FIFO in `Adc::convert` before it gives up
+const CONVERT_RETRIES: u32 = 1_000_000;
+/// Interrupt of the subsystem
+pub const INTERRUPT: InterruptSource = InterruptSource::AdcTsc;
+
+/// Number of samples that are averaged per step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Averaging {
+    None = 0,
+    Two = 1,
+    Four = 2,
+    Eight = 3,
+    Sixteen = 4,
+}
+
+/// How a step is run by the sequencer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// The step is disabled after one conversion
+    OneShot,
+    /// The step is run again in every sequence
+    Continuous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fifo {
+    Fifo0,
+    Fifo1,
+}
+
+impl Fifo {
+    fn index(self) -> usize {
+        match self {
+            Fifo::Fifo0 => 0,
+            Fifo::Fifo1 => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    /// The sequencer finished the last enabled step
+    EndOfSequence,
+    /// The FIFO holds more samples than the threshold
+    FifoThreshold(Fifo),
+    /// A sample was lost because the FIFO was full
+    FifoOverrun(Fifo),
+    /// An empty FIFO was read
+    FifoUnderflow(Fifo),
+    /// A sample of a range checked step was outside of the range
+    OutOfRange,
+    /// The touchscreen was touched
+    PenDown,
+    /// The touchscreen was released
+    PenUp,
+}
+
+impl Interrupt {
+    fn mask(self) -> u32 {
+        match self {
+            Interrupt::EndOfSequence => 1 << 1,
+            Interrupt::FifoThreshold(Fifo::Fifo0) => 1 << 2,
+            Interrupt::FifoOverrun(Fifo::Fifo0) => 1 << 3,
+            Interrupt::FifoUnderflow(Fifo::Fifo0) => 1 << 4,
+            Interrupt::FifoThreshold(Fifo::Fifo1) => 1 << 5,
+            Interrupt::FifoOverrun(Fifo::Fifo1) => 1 << 6,
+            Interrupt::FifoUnderflow(Fifo::Fifo1) => 1 << 7,
+            Interrupt::OutOfRange => 1 << 8,
+            Interrupt::PenDown => 1 << 0,
+            Interrupt::PenUp => 1 << 9,
+        }
+    }
+}
+
+/// The configuration of a sequencer step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StepConfig {
+    channel: u8,
+    mode: Mode,
+    averaging: Averaging,
+    fifo: Fifo,
+    open_delay: u32,
+    sample_delay: u8,
+    range_check: bool,
+}
+
+impl StepConfig {
+    /// One-shot conversion of the input into FIFO0
+    ///
+    /// Returns None if the channel does not exist.
+    pub fn new(channel: u8) -> Option<Self> {
+        if channel >= CHANNEL_NUM {
+            return None;
+        }
+        Some(StepConfig {
+            channel,
+            mode: Mode::OneShot,
+            averaging: Averaging::None,
+            fifo: Fifo::Fifo0,
+            open_delay: 0,
+            sample_delay: 0,
+            range_check: false,
+        })
+    }
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn averaging(mut self, averaging: Averaging) -> Self {
+        self.averaging = averaging;
+        self
+    }
+    pub fn fifo(mut self, fifo: Fifo) -> Self {
+        self.fifo = fifo;
+        self
+    }
+    /// Clock cycles between the start of the step and the sampling
+    ///
+    /// Lets the input settle after switching, at most 0x3ffff.
+    pub fn open_delay(mut self, cycles: u32) -> Self {
+        self.open_delay = cycles.min(0x3ffff);
+        self
+    }
+    /// Clock cycles the input is sampled, minus one
+    pub fn sample_delay(mut self, cycles: u8) -> Self {
+        self.sample_delay = cycles;
+        self
+    }
+    /// Raise `Interrupt::OutOfRange` for samples outside of the range
+    pub fn range_check(mut self) -> Self {
+        self.range_check = true;
+        self
+    }
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+}
+
+/// A conversion result read from a FIFO
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// The step that produced the sample
+    pub step: u8,
+    /// The raw 12 bit value
+    pub raw: u16,
+}
+
+impl Sample {
+    pub fn millivolts(&self) -> u32 {
+        raw_to_millivolts(self.raw)
+    }
+}
+
+/// Converts a raw value into millivolts against the 1.8 V reference
+pub fn raw_to_millivolts(raw: u16) -> u32 {
+    (raw.min(MAX_VALUE) as u32 * REFERENCE_MV) / MAX_VALUE as u32
+}
+
+/// Converts millivolts into a raw value, for the range check
+pub fn millivolts_to_raw(millivolts: u32) -> u16 {
+    (millivolts.min(REFERENCE_MV) * MAX_VALUE as u32 / REFERENCE_MV) as u16
+}
+
+/// The wiring of a resistive touchscreen
+///
+/// The plates are connected to AIN0 (X+), AIN1 (X-), AIN2 (Y+) and AIN3 (Y-),
+/// the wiper of a 5-wire screen to AIN4.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wires {
+    Four,
+    Five,
+}
+
+/// The configuration of the touchscreen mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TouchConfig {
+    wires: Wires,
+    samples: u8,
+    charge_delay: u32,
+    open_delay: u32,
+    x_plate_ohms: u32,
+}
+
+impl TouchConfig {
+    /// Five samples per coordinate with the delays of the reference manual
+    pub fn new(wires: Wires) -> Self {
+        TouchConfig {
+            wires,
+            samples: 5,
+            charge_delay: 0x400,
+            open_delay: 0x98,
+            x_plate_ohms: 200,
+        }
+    }
+    /// Number of samples per coordinate that are averaged, at most 7
+    pub fn samples(mut self, samples: u8) -> Self {
+        self.samples = samples.clamp(1, MAX_TOUCH_SAMPLES);
+        self
+    }
+    /// Clock cycles the plates are charged before a sequence
+    ///
+    /// Keeps the pen-down detection from firing again on the charged plates.
+    pub fn charge_delay(mut self, cycles: u32) -> Self {
+        self.charge_delay = cycles.min(0x3ffff);
+        self
+    }
+    /// Clock cycles between switching the plates and sampling
+    pub fn open_delay(mut self, cycles: u32) -> Self {
+        self.open_delay = cycles.min(0x3ffff);
+        self
+    }
+    /// The resistance of the X plate, used for the pressure
+    pub fn x_plate_ohms(mut self, ohms: u32) -> Self {
+        self.x_plate_ohms = ohms;
+        self
+    }
+    /// Number of steps used by the touchscreen, starting with step 0
+    pub fn steps(&self) -> u8 {
+        2 * self.samples + 2
+    }
+}
+
+/// Largest number of samples per coordinate
+pub const MAX_TOUCH_SAMPLES: u8 = 7;
+
+/// A touch in raw coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Touch {
+    pub x: u16,
+    pub y: u16,
+    /// The touch resistance in ohms, smaller means harder
+    pub pressure: u32,
+}
+
+/// Transformation from raw touch coordinates to screen coordinates
+///
+/// Screen x is `(a * x + b * y + c) / divider` and screen y is
+/// `(d * x + e * y + f) / divider`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    coefficients: [i64; 6],
+    divider: i64,
+}
+
+impl Calibration {
+    /// The transformation that keeps the raw coordinates
+    pub fn identity() -> Self {
+        Calibration {
+            coefficients: [1, 0, 0, 0, 1, 0],
+            divider: 1,
+        }
+    }
+    /// Scale the full raw range to a screen of the given size
+    pub fn scale(width: u32, height: u32) -> Self {
+        let max = MAX_VALUE as i64;
+        Calibration {
+            coefficients: [width as i64, 0, 0, 0, height as i64, 0],
+            divider: max + 1,
+        }
+    }
+    /// Compute the transformation from three touched points
+    ///
+    /// Returns None if the points are on a line.
+    pub fn from_points(raw: [(i32, i32); 3], screen: [(i32, i32); 3]) -> Option<Self> {
+        let [(x0, y0), (x1, y1), (x2, y2)] = raw;
+        let [(u0, v0), (u1, v1), (u2, v2)] = screen;
+        let (x0, y0, x1, y1, x2, y2) = (
+            x0 as i64, y0 as i64, x1 as i64, y1 as i64, x2 as i64, y2 as i64,
+        );
+        let (u0, v0, u1, v1, u2, v2) = (
+            u0 as i64, v0 as i64, u1 as i64, v1 as i64, u2 as i64, v2 as i64,
+        );
+        let divider = (x0 - x2) * (y1 - y2) - (x1 - x2) * (y0 - y2);
+        if divider == 0 {
+            return None;
+        }
+        let a = (u0 - u2) * (y1 - y2) - (u1 - u2) * (y0 - y2);
+        let b = (x0 - x2) * (u1 - u2) - (u0 - u2) * (x1 - x2);
+        let c = (x2 * u1 - x1 * u2) * y0 + (x0 * u2 - x2 * u0) * y1 + (x1 * u0 - x0 * u1) * y2;
+        let d = (v0 - v2) * (y1 - y2) - (v1 - v2) * (y0 - y2);
+        let e = (x0 - x2) * (v1 - v2) - (v0 - v2) * (x1 - x2);
+        let f = (x2 * v1 - x1 * v2) * y0 + (x0 * v2 - x2 * v0) * y1 + (x1 * v0 - x0 * v1) * y2;
+        Some(Calibration {
+            coefficients: [a, b, c, d, e, f],
+            divider,
+        })
+    }
+    /// Returns the screen coordinates of a touch
+    pub fn apply(&self, touch: &Touch) -> (i32, i32) {
+        let [a, b, c, d, e, f] = self.coefficients;
+        let (x, y) = (touch.x as i64, touch.y as i64);
+        (
+            ((a * x + b * y + c) / self.divider) as i32,
+            ((d * x + e * y + f) / self.divider) as i32,
+        )
+    }
+}
+
+pub struct Adc {
+    memory: &'static RegisterBlock,
+    channels: [u8; STEP_NUM as usize],
+    touch: Option<TouchConfig>,
+}
+
+// The samples are only taken out of the FIFOs through the Adc, a step is never
+// configured behind its back
+unsafe impl Send for Adc {}
+
+impl Adc {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Adc {
+            memory,
+            channels: [0; STEP_NUM as usize],
+            touch: None,
+        }
+    }
+    /// Stop the subsystem and set the clock divider
+    ///
+    /// The ADC clock is the 24 MHz input clock divided by `clock_div + 1` and
+    /// must not exceed 3 MHz. The FIFOs are tagged with the step id and the
+    /// step configuration is made writable.
+    pub fn init(&mut self, clock_div: u16) {
+        self.disable();
+        self.memory.STEPENABLE.set(0);
+        self.memory.IRQENABLE_CLR.set(0x7ff);
+        self.memory.IRQSTATUS.set(0x7ff);
+        self.memory
+            .ADC_CLKDIV
+            .write(ADC_CLKDIV::ADC_CLKDIV.val(clock_div.max(7) as u32));
+        self.memory
+            .CTRL
+            .write(CTRL::STEP_ID_TAG::SET + CTRL::STEPCONFIG_WRITEPROTECT_N::Writable);
+        for fifo in [Fifo::Fifo0, Fifo::Fifo1].iter() {
+            self.flush(*fifo);
+        }
+    }
+    /// Start the sequencer
+    pub fn enable(&self) {
+        self.memory.CTRL.modify(CTRL::ENABLE::SET);
+    }
+    /// Stop the sequencer, the current step is finished
+    pub fn disable(&self) {
+        self.memory.CTRL.modify(CTRL::ENABLE::CLEAR);
+    }
+    pub fn is_busy(&self) -> bool {
+        self.memory.ADCSTAT.is_set(ADCSTAT::FSM_BUSY)
+    }
+    /// Configure a step, returns None if the step does not exist
+    pub fn configure_step(&mut self, step: u8, config: &StepConfig) -> Option<()> {
+        if step >= STEP_NUM {
+            return None;
+        }
+        let mode = match config.mode {
+            Mode::OneShot => STEPCONFIG::MODE::SwOneShot,
+            Mode::Continuous => STEPCONFIG::MODE::SwContinuous,
+        };
+        let reg = &self.memory.STEP[step as usize];
+        reg.STEPCONFIG.write(
+            mode + STEPCONFIG::AVERAGING.val(config.averaging as u32)
+                + STEPCONFIG::SEL_INM_SWC::AdcRefM
+                + STEPCONFIG::SEL_INP_SWC.val(config.channel as u32)
+                + STEPCONFIG::FIFO_SELECT.val(config.fifo.index() as u32)
+                + STEPCONFIG::RANGE_CHECK.val(config.range_check as u32),
+        );
+        reg.STEPDELAY.write(
+            STEPDELAY::OPENDELAY.val(config.open_delay)
+                + STEPDELAY::SAMPLEDELAY.val(config.sample_delay as u32),
+        );
+        self.channels[step as usize] = config.channel;
+        Some(())
+    }
+    /// Returns the channel sampled by a step
+    pub fn channel_of(&self, step: u8) -> Option<u8> {
+        self.channels.get(step as usize).copied()
+    }
+    /// Enable a step, one-shot steps are disabled after their conversion
+    pub fn enable_step(&self, step: u8) -> Option<()> {
+        if step >= STEP_NUM {
+            return None;
+        }
+        let reg = &self.memory.STEPENABLE;
+        reg.set(reg.get() | 1 << (step + 1));
+        Some(())
+    }
+    pub fn disable_step(&self, step: u8) -> Option<()> {
+        if step >= STEP_NUM {
+            return None;
+        }
+        let reg = &self.memory.STEPENABLE;
+        reg.set(reg.get() & !(1 << (step + 1)));
+        Some(())
+    }
+    /// Enable several steps at once, bit n enables step n
+    pub fn enable_steps(&self, steps: u16) {
+        let reg = &self.memory.STEPENABLE;
+        reg.set(reg.get() | (steps as u32) << 1);
+    }
+    /// Returns the number of samples in the FIFO
+    pub fn fifo_count(&self, fifo: Fifo) -> u8 {
+        self.memory.FIFO[fifo.index()]
+            .FIFOCOUNT
+            .read(FIFOCOUNT::WORDS_IN_FIFO) as u8
+    }
+    /// Raise `Interrupt::FifoThreshold` once the FIFO holds `level` samples
+    ///
+    /// Returns None if the level is 0 or larger than the FIFO.
+    pub fn set_fifo_threshold(&self, fifo: Fifo, level: u8) -> Option<()> {
+        if level == 0 || level > FIFO_DEPTH {
+            return None;
+        }
+        self.memory.FIFO[fifo.index()]
+            .FIFOTHRESHOLD
+            .write(FIFOTHRESHOLD::FIFO_THRESHOLD_LEVEL.val(level as u32 - 1));
+        Some(())
+    }
+    /// Read the oldest sample of the FIFO
+    pub fn read(&self, fifo: Fifo) -> Option<Sample> {
+        if self.fifo_count(fifo) == 0 {
+            return None;
+        }
+        let data = match fifo {
+            Fifo::Fifo0 => self.memory.FIFO0DATA.extract(),
+            Fifo::Fifo1 => self.memory.FIFO1DATA.extract(),
+        };
+        Some(Sample {
+            step: data.read(FIFODATA::ADCCHNLID) as u8,
+            raw: data.read(FIFODATA::ADCDATA) as u16,
+        })
+    }
+    /// Discard all samples of the FIFO
+    pub fn flush(&self, fifo: Fifo) {
+        while self.read(fifo).is_some() {}
+    }
+    /// Convert a channel once with the step and wait for the result
+    ///
+    /// No other step may use the FIFO. Returns None if the sequencer is not
+    /// enabled or no sample arrived in time, the step is disabled then.
+    pub fn convert(&mut self, step: u8, config: &StepConfig) -> Option<Sample> {
+        if !self.memory.CTRL.is_set(CTRL::ENABLE) {
+            return None;
+        }
+        let config = config.mode(Mode::OneShot);
+        self.configure_step(step, &config)?;
+        self.enable_step(step)?;
+        for _ in 0..CONVERT_RETRIES {
+            if let Some(sample) = self.read(config.fifo) {
+                return Some(sample);
+            }
+            hint::spin_loop();
+        }
+        self.disable_step(step);
+        None
+    }
+    /// Set the range for steps with range check, in raw values
+    pub fn set_range(&self, low: u16, high: u16) {
+        self.memory.ADCRANGE.write(
+            ADCRANGE::LOW_RANGE_DATA.val(low.min(MAX_VALUE) as u32)
+                + ADCRANGE::HIGH_RANGE_DATA.val(high.min(MAX_VALUE) as u32),
+        );
+    }
+    pub fn enable_interrupt(&self, interrupt: Interrupt) {
+        self.memory.IRQENABLE_SET.set(interrupt.mask());
+    }
+    pub fn disable_interrupt(&self, interrupt: Interrupt) {
+        self.memory.IRQENABLE_CLR.set(interrupt.mask());
+    }
+    /// Returns whether the interrupt is enabled and pending
+    pub fn is_pending(&self, interrupt: Interrupt) -> bool {
+        self.memory.IRQSTATUS.get() & interrupt.mask() != 0
+    }
+    pub fn clear_interrupt(&self, interrupt: Interrupt) {
+        self.memory.IRQSTATUS.set(interrupt.mask());
+    }
+
+    /// Use the first steps and FIFO0 for a resistive touchscreen
+    ///
+    /// A touch starts the charge step and a hardware triggered sequence of X,
+    /// Y and pressure steps. Has to be called while the sequencer is disabled.
+    /// Other conversions should use the steps after `TouchConfig::steps` and
+    /// FIFO1.
+    pub fn enable_touchscreen(&mut self, config: &TouchConfig) {
+        let n = config.samples;
+        let base = STEPCONFIG::MODE::HwOneShot
+            + STEPCONFIG::AVERAGING.val(Averaging::Sixteen as u32)
+            + STEPCONFIG::SEL_INM_SWC::AdcRefM;
+        let (x_step, y_step) = match config.wires {
+            // Measure the voltage divided by the touch on the other plate
+            Wires::Four => (
+                base + STEPCONFIG::XPPSW_SWC::SET
+                    + STEPCONFIG::XNNSW_SWC::SET
+                    + STEPCONFIG::SEL_INP_SWC.val(2),
+                base + STEPCONFIG::YPPSW_SWC::SET
+                    + STEPCONFIG::YNNSW_SWC::SET
+                    + STEPCONFIG::SEL_INP_SWC.val(0),
+            ),
+            // Measure at the wiper, with the corners switched per axis
+            Wires::Five => (
+                base + STEPCONFIG::XPPSW_SWC::SET
+                    + STEPCONFIG::XNNSW_SWC::SET
+                    + STEPCONFIG::YPPSW_SWC::SET
+                    + STEPCONFIG::YNNSW_SWC::SET
+                    + STEPCONFIG::SEL_INP_SWC.val(4),
+                base + STEPCONFIG::XPPSW_SWC::SET
+                    + STEPCONFIG::XNPSW_SWC::SET
+                    + STEPCONFIG::YPNSW_SWC::SET
+                    + STEPCONFIG::YNNSW_SWC::SET
+                    + STEPCONFIG::SEL_INP_SWC.val(4),
+            ),
+        };
+        // The touch resistance is measured between Y+ and X-
+        let z_step = base + STEPCONFIG::YPPSW_SWC::SET + STEPCONFIG::XNNSW_SWC::SET;
+        let delay = STEPDELAY::OPENDELAY.val(config.open_delay);
+        for i in 0..n {
+            let (x, y) = (
+                &self.memory.STEP[i as usize],
+                &self.memory.STEP[(n + i) as usize],
+            );
+            x.STEPCONFIG.write(x_step);
+            x.STEPDELAY.write(delay);
+            y.STEPCONFIG.write(y_step);
+            y.STEPDELAY.write(delay);
+        }
+        let (z1, z2) = (
+            &self.memory.STEP[2 * n as usize],
+            &self.memory.STEP[2 * n as usize + 1],
+        );
+        z1.STEPCONFIG.write(z_step + STEPCONFIG::SEL_INP_SWC.val(0));
+        z1.STEPDELAY.write(delay);
+        z2.STEPCONFIG.write(z_step + STEPCONFIG::SEL_INP_SWC.val(3));
+        z2.STEPDELAY.write(delay);
+        for step in 0..config.steps() {
+            self.channels[step as usize] = 0;
+        }
+        // Between sequences the pen-down detection pulls the Y plate up
+        let idle = STEPCONFIG::YNNSW_SWC::SET
+            + STEPCONFIG::YPNSW_SWC::SET
+            + STEPCONFIG::SEL_INM_SWC::AdcRefM
+            + STEPCONFIG::SEL_INP_SWC::AdcRefM;
+        self.memory.IDLECONFIG.write(idle);
+        self.memory.TS_CHARGE_STEPCONFIG.write(idle);
+        self.memory
+            .TS_CHARGE_DELAY
+            .write(STEPDELAY::OPENDELAY.val(config.charge_delay));
+        let afe = match config.wires {
+            Wires::Four => CTRL::AFE_PEN_CTRL::FourWire,
+            Wires::Five => CTRL::AFE_PEN_CTRL::FiveWire,
+        };
+        self.memory
+            .CTRL
+            .modify(afe + CTRL::TOUCH_SCREEN_ENABLE::SET + CTRL::HW_EVENT_MAPPING::CLEAR);
+        self.set_fifo_threshold(Fifo::Fifo0, config.steps());
+        self.touch = Some(*config);
+        self.arm_touchscreen();
+    }
+    /// Stop the touchscreen mode, the steps are disabled
+    pub fn disable_touchscreen(&mut self) {
+        if let Some(config) = self.touch.take() {
+            self.memory
+                .CTRL
+                .modify(CTRL::AFE_PEN_CTRL::Disabled + CTRL::TOUCH_SCREEN_ENABLE::CLEAR);
+            let mask = (1u32 << (config.steps() + 1)) - 1;
+            let reg = &self.memory.STEPENABLE;
+            reg.set(reg.get() & !mask);
+            self.disable_interrupt(Interrupt::PenDown);
+            self.disable_interrupt(Interrupt::PenUp);
+        }
+    }
+    /// Enable the charge step and the touchscreen steps for the next touch
+    fn arm_touchscreen(&self) {
+        if let Some(config) = self.touch {
+            let mask = (1u32 << (config.steps() + 1)) - 1;
+            let reg = &self.memory.STEPENABLE;
+            reg.set(reg.get() | mask);
+        }
+    }
+    /// Returns whether the touchscreen is touched
+    pub fn is_pen_down(&self) -> bool {
+        self.memory.ADCSTAT.is_set(ADCSTAT::PEN_IRQ0)
+    }
+    /// Read the result of a touchscreen sequence and arm the next one
+    ///
+    /// Returns None if the sequence is not complete or the touch was too
+    /// light to measure.
+    pub fn read_touch(&self) -> Option<Touch> {
+        let config = self.touch?;
+        if self.fifo_count(Fifo::Fifo0) < config.steps() {
+            return None;
+        }
+        let n = config.samples as u32;
+        let (mut x, mut y, mut z1, mut z2) = (0, 0, 0, 0);
+        for _ in 0..config.steps() {
+            let sample = self.read(Fifo::Fifo0)?;
+            let raw = sample.raw as u32;
+            match sample.step as u32 {
+                step if step < n => x += raw,
+                step if step < 2 * n => y += raw,
+                step if step == 2 * n => z1 = raw,
+                _ => z2 = raw,
+            }
+        }
+        self.arm_touchscreen();
+        let (x, y) = (x / n, y / n);
+        if z1 == 0 || z2 <= z1 {
+            return None;
+        }
+        // R_touch = R_x * x / 4096 * (z2 / z1 - 1)
+        let pressure = (config.x_plate_ohms as u64 * x as u64 * (z2 - z1) as u64
+            / (z1 as u64 * (MAX_VALUE as u64 + 1))) as u32;
+        Some(Touch {
+            x: x as u16,
+            y: y as u16,
+            pressure,
+        })
+    }
+}
@@ -0,0 +1,35 @@
+//! Storage devices that are accessed in blocks, e.g. memory cards
+
+// Author: Moritz Doll
+// License: MIT
+
+/// Size of a block in bytes
+pub const BLOCK_SIZE: usize = 512;
+
+/// A device of `BLOCK_SIZE` blocks, addressed by their index
+pub trait BlockDevice {
+    type Error;
+    /// Returns the number of blocks
+    fn block_count(&self) -> u64;
+    /// Read consecutive blocks starting at `lba` into the buffer
+    ///
+    /// The length of the buffer is a multiple of `BLOCK_SIZE`.
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), Self::Error>;
+    /// Write the buffer to consecutive blocks starting at `lba`
+    ///
+    /// The length of the buffer is a multiple of `BLOCK_SIZE`.
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl<D: BlockDevice> BlockDevice for &mut D {
+    type Error = D::Error;
+    fn block_count(&self) -> u64 {
+        (**self).block_count()
+    }
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), D::Error> {
+        (**self).read_blocks(lba, buffer)
+    }
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<(), D::Error> {
+        (**self).write_blocks(lba, buffer)
+    }
+}
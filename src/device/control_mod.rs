@@ -1,7 +1,8 @@
 //! Low level access to the control module functional group
 
+use crate::device::pad::Pad;
 use armv7::VirtualAddress;
-use register::{mmio::*, register_bitfields, Field};
+use register::{mmio::*, register_bitfields, FieldValue};
 
 register_bitfields! {
     u32,
@@ -11,6 +12,46 @@ register_bitfields! {
         PULL OFFSET(4) NUMBITS(1) [Up = 0b1, Down = 0b0],
         RX OFFSET(5) NUMBITS(1) [Enable = 1, Disable = 0],
         SLEW OFFSET(6) NUMBITS(1) [Fast = 0, Slow = 1]
+    ],
+    CONTROL_STATUS [
+        SYSBOOT0 OFFSET(0) NUMBITS(8) [],
+        DEVTYPE OFFSET(8) NUMBITS(3) [Test = 0, Emulation = 1, HighSecurity = 2, General = 3],
+        BW OFFSET(16) NUMBITS(1) [],
+        WAITEN OFFSET(17) NUMBITS(1) [],
+        ADMUX OFFSET(18) NUMBITS(2) [],
+        TESTMD OFFSET(20) NUMBITS(2) [],
+        SYSBOOT1 OFFSET(22) NUMBITS(2) [Osc19_2MHz = 0, Osc24MHz = 1, Osc25MHz = 2, Osc26MHz = 3]
+    ],
+    DEVICE_ID [
+        MFGR OFFSET(1) NUMBITS(11) [],
+        PARTNUM OFFSET(12) NUMBITS(16) [],
+        DEVREV OFFSET(28) NUMBITS(4) []
+    ],
+    DEV_FEATURE [
+        PRU_ICSS OFFSET(0) NUMBITS(1) [],
+        ETHERNET OFFSET(1) NUMBITS(1) [],
+        DCAN OFFSET(7) NUMBITS(1) [],
+        ICSS_FEA OFFSET(16) NUMBITS(2) [],
+        SGX OFFSET(29) NUMBITS(1) []
+    ],
+    MAC_ID_LO [
+        BYTE4 OFFSET(0) NUMBITS(8) [],
+        BYTE5 OFFSET(8) NUMBITS(8) []
+    ],
+    MAC_ID_HI [
+        BYTE0 OFFSET(0) NUMBITS(8) [],
+        BYTE1 OFFSET(8) NUMBITS(8) [],
+        BYTE2 OFFSET(16) NUMBITS(8) [],
+        BYTE3 OFFSET(24) NUMBITS(8) []
+    ],
+    EFUSE_SMA [
+        // A cleared bit means that the OPP is supported
+        OPP50_300 OFFSET(4) NUMBITS(1) [],
+        OPP50_300_ZCE OFFSET(5) NUMBITS(1) [],
+        OPP100_600 OFFSET(6) NUMBITS(1) [],
+        OPP120_720 OFFSET(7) NUMBITS(1) [],
+        TURBO_800 OFFSET(8) NUMBITS(1) [],
+        NITRO_1000 OFFSET(9) NUMBITS(1) []
     ]
 }
 
@@ -20,28 +61,425 @@ struct RegisterBlock {
     CONF_MOD: [ReadWrite<u32, CONF_MOD::Register>; CONF_NUM],
 }
 
-pub const CONF_NUM: usize = 141;
+#[allow(non_snake_case)]
+#[repr(C)]
+struct StatusBlock {
+    _REVISION: ReadOnly<u32, ()>, // 0x00
+    _HWINFO: ReadOnly<u32, ()>,   // 0x04
+    __reserved_0: [u32; 2],
+    _SYSCONFIG: ReadWrite<u32, ()>, // 0x10
+    __reserved_1: [u32; 11],
+    CONTROL_STATUS: ReadOnly<u32, CONTROL_STATUS::Register>, // 0x40
+    __reserved_2: [u32; 367],
+    DEVICE_ID: ReadOnly<u32, DEVICE_ID::Register>, // 0x600
+    DEV_FEATURE: ReadOnly<u32, DEV_FEATURE::Register>, // 0x604
+    __reserved_3: [u32; 10],
+    MAC_ID0_LO: ReadOnly<u32, MAC_ID_LO::Register>, // 0x630
+    MAC_ID0_HI: ReadOnly<u32, MAC_ID_HI::Register>, // 0x634
+    MAC_ID1_LO: ReadOnly<u32, MAC_ID_LO::Register>, // 0x638
+    MAC_ID1_HI: ReadOnly<u32, MAC_ID_HI::Register>, // 0x63C
+    __reserved_4: [u32; 9],
+    PWMSS_CTRL: ReadWrite<u32, ()>, // 0x664
+    __reserved_5: [u32; 101],
+    EFUSE_SMA: ReadOnly<u32, EFUSE_SMA::Register>, // 0x7FC
+}
+
+/// Number of pad conf registers, 0x800 to 0xA34
+pub const CONF_NUM: usize = 142;
+
+/// The mode of the pin multiplexer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MuxMode {
+    Mode0 = 0,
+    Mode1 = 1,
+    Mode2 = 2,
+    Mode3 = 3,
+    Mode4 = 4,
+    Mode5 = 5,
+    Mode6 = 6,
+    Mode7 = 7,
+}
+
+impl MuxMode {
+    pub const ALL: [MuxMode; 8] = [
+        MuxMode::Mode0,
+        MuxMode::Mode1,
+        MuxMode::Mode2,
+        MuxMode::Mode3,
+        MuxMode::Mode4,
+        MuxMode::Mode5,
+        MuxMode::Mode6,
+        MuxMode::Mode7,
+    ];
+    /// Get the mode from the lowest three bits
+    pub fn from_u32(bits: u32) -> MuxMode {
+        MuxMode::ALL[(bits & 0b111) as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    Up,
+    Down,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slew {
+    Fast,
+    Slow,
+}
+
+/// Configuration of a single pad
+///
+/// The default is pull down, receiver disabled and fast slew, which is the
+/// reset value of most pads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadConfig {
+    mode: MuxMode,
+    pull: Pull,
+    receiver: bool,
+    slew: Slew,
+}
+
+impl PadConfig {
+    pub fn new(mode: MuxMode) -> Self {
+        PadConfig {
+            mode,
+            pull: Pull::Down,
+            receiver: false,
+            slew: Slew::Fast,
+        }
+    }
+    /// Replace the mode, keeping the other settings
+    pub fn with_mode(mut self, mode: MuxMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn pull_up(mut self) -> Self {
+        self.pull = Pull::Up;
+        self
+    }
+    pub fn pull_down(mut self) -> Self {
+        self.pull = Pull::Down;
+        self
+    }
+    pub fn pull_disabled(mut self) -> Self {
+        self.pull = Pull::Disabled;
+        self
+    }
+    /// Enable the input buffer, required for every pad that is read
+    pub fn receiver_enable(mut self) -> Self {
+        self.receiver = true;
+        self
+    }
+    pub fn receiver_disable(mut self) -> Self {
+        self.receiver = false;
+        self
+    }
+    pub fn slew_fast(mut self) -> Self {
+        self.slew = Slew::Fast;
+        self
+    }
+    pub fn slew_slow(mut self) -> Self {
+        self.slew = Slew::Slow;
+        self
+    }
+    pub fn mode(&self) -> MuxMode {
+        self.mode
+    }
+    pub fn pull(&self) -> Pull {
+        self.pull
+    }
+    pub fn receiver(&self) -> bool {
+        self.receiver
+    }
+    pub fn slew(&self) -> Slew {
+        self.slew
+    }
+    fn field_value(&self) -> FieldValue<u32, CONF_MOD::Register> {
+        let pull = match self.pull {
+            Pull::Up => CONF_MOD::PULL_EN::Enable + CONF_MOD::PULL::Up,
+            Pull::Down => CONF_MOD::PULL_EN::Enable + CONF_MOD::PULL::Down,
+            Pull::Disabled => CONF_MOD::PULL_EN::Disable + CONF_MOD::PULL::Down,
+        };
+        let rx = if self.receiver {
+            CONF_MOD::RX::Enable
+        } else {
+            CONF_MOD::RX::Disable
+        };
+        let slew = match self.slew {
+            Slew::Fast => CONF_MOD::SLEW::Fast,
+            Slew::Slow => CONF_MOD::SLEW::Slow,
+        };
+        CONF_MOD::MODE.val(self.mode as u32) + pull + rx + slew
+    }
+    fn from_register(reg: &ReadWrite<u32, CONF_MOD::Register>) -> Self {
+        let pull = if reg.is_set(CONF_MOD::PULL_EN) {
+            Pull::Disabled
+        } else if reg.is_set(CONF_MOD::PULL) {
+            Pull::Up
+        } else {
+            Pull::Down
+        };
+        let slew = if reg.is_set(CONF_MOD::SLEW) {
+            Slew::Slow
+        } else {
+            Slew::Fast
+        };
+        PadConfig {
+            mode: MuxMode::from_u32(reg.read(CONF_MOD::MODE)),
+            pull,
+            receiver: reg.is_set(CONF_MOD::RX),
+            slew,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeviceType {
+    Test,
+    Emulation,
+    HighSecurity,
+    General,
+    Reserved,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SiliconRevision {
+    Pg1_0,
+    Pg2_0,
+    Pg2_1,
+    Unknown(u8),
+}
+
+/// A device the ROM code boots from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootDevice {
+    Mmc0,
+    Mmc1,
+    Spi0,
+    Uart0,
+    Usb0,
+}
+
+/// The order in which the ROM code tries the boot devices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootSequence {
+    /// The devices in the order they are tried
+    Devices([BootDevice; 4]),
+    /// A sequence that is not decoded, the raw SYSBOOT[4:0]
+    ///
+    /// The sequences are listed in the boot sequence table of the technical
+    /// reference manual.
+    Other(u8),
+}
+
+/// Optional modules of the AM335x variant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Features {
+    pub pru_icss: bool,
+    pub ethernet: bool,
+    pub dcan: bool,
+    pub sgx: bool,
+    /// Industrial protocol support of the PRU-ICSS
+    pub icss_features: u8,
+}
+
+/// Operating performance points of the MPU allowed by the eFuses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OppLimits {
+    /// OPP50 at 300 MHz
+    pub opp50: bool,
+    /// OPP100 at 600 MHz
+    pub opp100: bool,
+    /// OPP120 at 720 MHz
+    pub opp120: bool,
+    /// OPP Turbo at 800 MHz
+    pub turbo: bool,
+    /// OPP Nitro at 1 GHz
+    pub nitro: bool,
+}
+
+impl OppLimits {
+    /// Returns the highest allowed MPU frequency in MHz
+    pub fn max_mpu_mhz(&self) -> u32 {
+        if self.nitro {
+            1000
+        } else if self.turbo {
+            800
+        } else if self.opp120 {
+            720
+        } else if self.opp100 {
+            600
+        } else {
+            300
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PadError {
+    /// The mode does not select a signal on the pad
+    IllegalMode,
+}
 
 pub struct Control {
     memory: &'static RegisterBlock,
+    status: &'static StatusBlock,
 }
 
+// The PinManager takes the Control by value instead of mapping the pads again
+unsafe impl Send for Control {}
+
 impl Control {
     pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
         // We have to add 0x800 to the memory address
         let memory = &*((0x800 + memory_addr.as_u32()) as *mut RegisterBlock);
-        Self { memory }
+        let status = &*(memory_addr.as_u32() as *mut StatusBlock);
+        Self { memory, status }
+    }
+    /// Returns the SYSBOOT[15:0] pins latched at power on reset
+    pub fn sysboot(&self) -> u16 {
+        let status = &self.status.CONTROL_STATUS;
+        (status.read(CONTROL_STATUS::SYSBOOT0)
+            | status.read(CONTROL_STATUS::BW) << 8
+            | status.read(CONTROL_STATUS::WAITEN) << 9
+            | status.read(CONTROL_STATUS::ADMUX) << 10
+            | status.read(CONTROL_STATUS::TESTMD) << 12
+            | status.read(CONTROL_STATUS::SYSBOOT1) << 14) as u16
+    }
+    /// Returns the boot sequence selected by SYSBOOT[4:0]
+    pub fn boot_sequence(&self) -> BootSequence {
+        use BootDevice::*;
+        match self.status.CONTROL_STATUS.read(CONTROL_STATUS::SYSBOOT0) & 0x1F {
+            0x17 => BootSequence::Devices([Mmc0, Spi0, Uart0, Usb0]),
+            0x18 => BootSequence::Devices([Spi0, Mmc0, Usb0, Uart0]),
+            0x1c => BootSequence::Devices([Mmc1, Mmc0, Uart0, Usb0]),
+            bits => BootSequence::Other(bits as u8),
+        }
+    }
+    /// Returns the frequency of the main oscillator in Hz
+    pub fn oscillator_frequency(&self) -> u32 {
+        match self.status.CONTROL_STATUS.read(CONTROL_STATUS::SYSBOOT1) {
+            0 => 19_200_000,
+            1 => 24_000_000,
+            2 => 25_000_000,
+            _ => 26_000_000,
+        }
+    }
+    pub fn device_type(&self) -> DeviceType {
+        match self.status.CONTROL_STATUS.read(CONTROL_STATUS::DEVTYPE) {
+            0 => DeviceType::Test,
+            1 => DeviceType::Emulation,
+            2 => DeviceType::HighSecurity,
+            3 => DeviceType::General,
+            _ => DeviceType::Reserved,
+        }
+    }
+    pub fn silicon_revision(&self) -> SiliconRevision {
+        match self.status.DEVICE_ID.read(DEVICE_ID::DEVREV) {
+            0 => SiliconRevision::Pg1_0,
+            1 => SiliconRevision::Pg2_0,
+            2 => SiliconRevision::Pg2_1,
+            rev => SiliconRevision::Unknown(rev as u8),
+        }
+    }
+    /// Returns the part number, 0xB944 for the AM335x
+    pub fn part_number(&self) -> u16 {
+        self.status.DEVICE_ID.read(DEVICE_ID::PARTNUM) as u16
+    }
+    /// Returns the manufacturer, 0x017 for Texas Instruments
+    pub fn manufacturer(&self) -> u16 {
+        self.status.DEVICE_ID.read(DEVICE_ID::MFGR) as u16
+    }
+    pub fn features(&self) -> Features {
+        let feature = &self.status.DEV_FEATURE;
+        Features {
+            pru_icss: feature.is_set(DEV_FEATURE::PRU_ICSS),
+            ethernet: feature.is_set(DEV_FEATURE::ETHERNET),
+            dcan: feature.is_set(DEV_FEATURE::DCAN),
+            sgx: feature.is_set(DEV_FEATURE::SGX),
+            icss_features: feature.read(DEV_FEATURE::ICSS_FEA) as u8,
+        }
+    }
+    /// Returns the MAC address of the CPSW port (0 or 1) burnt into the eFuses
+    pub fn mac_address(&self, port: usize) -> Option<[u8; 6]> {
+        let (lo, hi) = match port {
+            0 => (&self.status.MAC_ID0_LO, &self.status.MAC_ID0_HI),
+            1 => (&self.status.MAC_ID1_LO, &self.status.MAC_ID1_HI),
+            _ => return None,
+        };
+        Some([
+            hi.read(MAC_ID_HI::BYTE0) as u8,
+            hi.read(MAC_ID_HI::BYTE1) as u8,
+            hi.read(MAC_ID_HI::BYTE2) as u8,
+            hi.read(MAC_ID_HI::BYTE3) as u8,
+            lo.read(MAC_ID_LO::BYTE4) as u8,
+            lo.read(MAC_ID_LO::BYTE5) as u8,
+        ])
+    }
+    /// Returns the OPPs the MPU may run at
+    ///
+    /// PG1.0 silicon has no eFuses and supports up to OPP120 at 720 MHz.
+    pub fn opp_limits(&self) -> OppLimits {
+        if self.silicon_revision() == SiliconRevision::Pg1_0 {
+            return OppLimits {
+                opp50: true,
+                opp100: true,
+                opp120: true,
+                turbo: false,
+                nitro: false,
+            };
+        }
+        let efuse = &self.status.EFUSE_SMA;
+        OppLimits {
+            opp50: !efuse.is_set(EFUSE_SMA::OPP50_300) || !efuse.is_set(EFUSE_SMA::OPP50_300_ZCE),
+            opp100: !efuse.is_set(EFUSE_SMA::OPP100_600),
+            opp120: !efuse.is_set(EFUSE_SMA::OPP120_720),
+            turbo: !efuse.is_set(EFUSE_SMA::TURBO_800),
+            nitro: !efuse.is_set(EFUSE_SMA::NITRO_1000),
+        }
+    }
+    /// Gate the time-base clock of the ePWM of a PWM subsystem
+    ///
+    /// Returns None if the subsystem does not exist.
+    pub fn set_pwm_timebase_clock(&self, pwmss: usize, enable: bool) -> Option<()> {
+        if pwmss >= 3 {
+            return None;
+        }
+        let reg = &self.status.PWMSS_CTRL;
+        if enable {
+            reg.set(reg.get() | 1 << pwmss);
+        } else {
+            reg.set(reg.get() & !(1 << pwmss));
+        }
+        Some(())
     }
     pub fn set(&self, index: usize, value: u32) {
-        if index > CONF_NUM {
+        if Pad::from_index(index).is_none() {
             return;
         }
         self.memory.CONF_MOD[index].set(value);
     }
     pub fn get(&self, index: usize) -> Option<u32> {
-        if index > CONF_NUM {
-            return None;
+        let pad = Pad::from_index(index)?;
+        Some(self.memory.CONF_MOD[pad.index()].get())
+    }
+    /// Configure a pad, the mode has to select a signal on the pad
+    pub fn configure(&self, pad: Pad, config: PadConfig) -> Result<(), PadError> {
+        if !pad.is_legal(config.mode) {
+            return Err(PadError::IllegalMode);
         }
-        Some(self.memory.CONF_MOD[index].get())
+        self.memory.CONF_MOD[pad.index()].write(config.field_value());
+        Ok(())
+    }
+    /// Read the current configuration of a pad
+    pub fn config(&self, pad: Pad) -> PadConfig {
+        PadConfig::from_register(&self.memory.CONF_MOD[pad.index()])
+    }
+    /// Returns the signal currently selected on the pad
+    pub fn signal(&self, pad: Pad) -> Option<&'static str> {
+        pad.signal(self.config(pad).mode)
     }
 }
@@ -0,0 +1,619 @@
+//! CPPI buffer descriptors and the receive and transmit rings of the CPDMA
+//!
+//! The CPDMA of the Ethernet switch walks linked lists of buffer descriptors
+//! in memory. The rings below keep one buffer per descriptor and reach the
+//! hardware only through the `Queue` trait, i.e. the head descriptor and the
+//! completion pointer of one channel. Any other implementation of `Queue`
+//! that plays the part of the DMA on the descriptors works the same, which
+//! allows running the rings against a simulated DMA.
+//!
+//! The descriptors have to be in memory that is not cached, e.g. the CPPI RAM.
+//! Cache maintenance is only done for the buffers.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::dma::DmaMemory;
+use armv7::VirtualAddress;
+use core::ptr;
+use core::sync::atomic::{self, Ordering};
+
+/// Start of packet
+pub const SOP: u32 = 1 << 31;
+/// End of packet
+pub const EOP: u32 = 1 << 30;
+/// The descriptor belongs to the DMA
+pub const OWNER: u32 = 1 << 29;
+/// The DMA reached the end of the list at this descriptor
+pub const EOQ: u32 = 1 << 28;
+/// The channel was torn down
+pub const TEARDOWN: u32 = 1 << 27;
+/// The received frame contains the CRC
+pub const PASS_CRC: u32 = 1 << 26;
+/// Any receive error: too long, too short, control frame, overrun, CRC
+pub const RX_ERRORS: u32 = 0b1111 << 22 | 0b11 << 20;
+/// Mask of the packet length in the flags
+pub const PACKET_LEN: u32 = 0x7ff;
+
+/// Size of a buffer, enough for a VLAN tagged frame
+pub const BUFFER_SIZE: usize = 1536;
+/// Shortest frame without CRC, shorter frames are padded
+pub const MIN_FRAME: usize = 60;
+/// Number of descriptors that fit into the 8 KiB CPPI RAM
+pub const CPPI_RAM_DESCRIPTORS: usize = 512;
+
+pub type Buffer = [u8; BUFFER_SIZE];
+
+/// A CPPI buffer descriptor
+///
+/// All accesses are volatile, since the DMA changes the descriptor.
+#[repr(C)]
+pub struct Descriptor {
+    next: u32,
+    buffer: u32,
+    offset_len: u32,
+    flags: u32,
+}
+
+impl Descriptor {
+    pub const EMPTY: Descriptor = Descriptor {
+        next: 0,
+        buffer: 0,
+        offset_len: 0,
+        flags: 0,
+    };
+    /// Returns the physical address of the next descriptor, 0 ends the list
+    pub fn next(&self) -> u32 {
+        unsafe { ptr::read_volatile(&self.next) }
+    }
+    fn set_next(&mut self, next: u32) {
+        unsafe { ptr::write_volatile(&mut self.next, next) }
+    }
+    /// Returns the physical address of the buffer
+    pub fn buffer(&self) -> u32 {
+        unsafe { ptr::read_volatile(&self.buffer) }
+    }
+    fn set_buffer(&mut self, buffer: u32) {
+        unsafe { ptr::write_volatile(&mut self.buffer, buffer) }
+    }
+    /// Returns the number of valid bytes in the buffer
+    pub fn buffer_len(&self) -> usize {
+        (unsafe { ptr::read_volatile(&self.offset_len) } & 0xffff) as usize
+    }
+    pub fn set_buffer_len(&mut self, len: usize) {
+        unsafe { ptr::write_volatile(&mut self.offset_len, len as u32 & 0xffff) }
+    }
+    pub fn flags(&self) -> u32 {
+        unsafe { ptr::read_volatile(&self.flags) }
+    }
+    pub fn set_flags(&mut self, flags: u32) {
+        unsafe { ptr::write_volatile(&mut self.flags, flags) }
+    }
+    /// Returns the length of the packet, valid in the start of packet
+    pub fn packet_len(&self) -> usize {
+        (self.flags() & PACKET_LEN) as usize
+    }
+}
+
+/// Returns descriptors at a virtual address, e.g. of the CPPI RAM
+///
+/// # Safety
+/// The memory has to hold `count` descriptors, must not be cached and must
+/// not be used otherwise.
+pub unsafe fn descriptors_at(
+    memory_addr: VirtualAddress,
+    count: usize,
+) -> &'static mut [Descriptor] {
+    let descriptors =
+        core::slice::from_raw_parts_mut(memory_addr.as_u32() as *mut Descriptor, count);
+    for descriptor in descriptors.iter_mut() {
+        descriptor.set_next(0);
+        descriptor.set_flags(0);
+    }
+    descriptors
+}
+
+/// One channel of the DMA
+pub trait Queue {
+    /// Write the head descriptor pointer, the DMA starts at the descriptor
+    fn set_head(&self, descriptor: u32);
+    /// Write the completion pointer, acknowledges the processed descriptor
+    fn acknowledge(&self, descriptor: u32);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RingError {
+    /// The number of descriptors and buffers differ or is too small
+    SizeMismatch,
+    /// The frame does not fit into a buffer
+    TooLong,
+    /// All descriptors are in use
+    Full,
+}
+
+struct Ring {
+    descriptors: &'static mut [Descriptor],
+    buffers: &'static mut [Buffer],
+    /// Physical address of the first descriptor
+    base: u32,
+}
+
+impl Ring {
+    fn new<M: DmaMemory>(
+        descriptors: &'static mut [Descriptor],
+        buffers: &'static mut [Buffer],
+        memory: &M,
+    ) -> Result<Self, RingError> {
+        if descriptors.is_empty() || descriptors.len() != buffers.len() {
+            return Err(RingError::SizeMismatch);
+        }
+        let base = memory.physical(descriptors.as_ptr() as *const u8);
+        Ok(Ring {
+            descriptors,
+            buffers,
+            base,
+        })
+    }
+    fn len(&self) -> usize {
+        self.descriptors.len()
+    }
+    fn address(&self, index: usize) -> u32 {
+        self.base + (index * core::mem::size_of::<Descriptor>()) as u32
+    }
+    fn buffer_address<M: DmaMemory>(&self, index: usize, memory: &M) -> u32 {
+        memory.physical(self.buffers[index].as_ptr())
+    }
+}
+
+/// The receive ring, all free descriptors belong to the DMA
+pub struct RxRing {
+    ring: Ring,
+    /// The oldest descriptor, the next to be filled
+    head: usize,
+    /// The last descriptor of the list of the DMA
+    tail: usize,
+}
+
+impl RxRing {
+    pub fn new<M: DmaMemory>(
+        descriptors: &'static mut [Descriptor],
+        buffers: &'static mut [Buffer],
+        memory: &M,
+    ) -> Result<Self, RingError> {
+        let ring = Ring::new(descriptors, buffers, memory)?;
+        // A single descriptor would be linked to itself
+        if ring.len() < 2 {
+            return Err(RingError::SizeMismatch);
+        }
+        let tail = ring.len() - 1;
+        Ok(RxRing {
+            ring,
+            head: 0,
+            tail,
+        })
+    }
+    /// Give all descriptors to the DMA and start the channel
+    pub fn start<Q: Queue, M: DmaMemory>(&mut self, queue: &Q, memory: &M) {
+        let len = self.ring.len();
+        for index in 0..len {
+            self.reset(index, memory);
+            if index + 1 < len {
+                let next = self.ring.address(index + 1);
+                self.ring.descriptors[index].set_next(next);
+            }
+        }
+        self.head = 0;
+        self.tail = len - 1;
+        atomic::fence(Ordering::SeqCst);
+        queue.set_head(self.ring.address(0));
+    }
+    /// Prepare a descriptor for the DMA, at the end of a list
+    fn reset<M: DmaMemory>(&mut self, index: usize, memory: &M) {
+        let buffer = self.ring.buffer_address(index, memory);
+        memory.invalidate(self.ring.buffers[index].as_ptr(), BUFFER_SIZE);
+        let descriptor = &mut self.ring.descriptors[index];
+        descriptor.set_next(0);
+        descriptor.set_buffer(buffer);
+        descriptor.set_buffer_len(BUFFER_SIZE);
+        descriptor.set_flags(OWNER);
+    }
+    /// Returns whether a received frame is waiting
+    pub fn is_ready(&self) -> bool {
+        self.ring.descriptors[self.head].flags() & OWNER == 0
+    }
+    /// Drop the frames with errors, returns whether a frame is waiting
+    pub fn poll<Q: Queue, M: DmaMemory>(&mut self, queue: &Q, memory: &M) -> bool {
+        loop {
+            let index = self.head;
+            let flags = self.ring.descriptors[index].flags();
+            if flags & OWNER != 0 {
+                return false;
+            }
+            if flags & (SOP | EOP) == SOP | EOP && flags & RX_ERRORS == 0 {
+                return true;
+            }
+            queue.acknowledge(self.ring.address(index));
+            self.recycle(index, queue, memory);
+        }
+    }
+    /// Hand the next received frame to the closure
+    ///
+    /// Frames with errors are dropped. The descriptor is given back to the
+    /// DMA afterwards. Returns None if no frame was received.
+    pub fn receive<Q: Queue, M: DmaMemory, F: FnOnce(&[u8]) -> R, R>(
+        &mut self,
+        queue: &Q,
+        memory: &M,
+        f: F,
+    ) -> Option<R> {
+        if !self.poll(queue, memory) {
+            return None;
+        }
+        let index = self.head;
+        atomic::fence(Ordering::SeqCst);
+        let len = self.ring.descriptors[index].buffer_len().min(BUFFER_SIZE);
+        let buffer = &self.ring.buffers[index];
+        memory.invalidate(buffer.as_ptr(), len);
+        let result = f(&buffer[..len]);
+        queue.acknowledge(self.ring.address(index));
+        self.recycle(index, queue, memory);
+        Some(result)
+    }
+    /// Append a processed descriptor to the list of the DMA
+    fn recycle<Q: Queue, M: DmaMemory>(&mut self, index: usize, queue: &Q, memory: &M) {
+        self.reset(index, memory);
+        atomic::fence(Ordering::SeqCst);
+        let address = self.ring.address(index);
+        let tail = &mut self.ring.descriptors[self.tail];
+        tail.set_next(address);
+        atomic::fence(Ordering::SeqCst);
+        // The DMA stopped at the old tail before it saw the new descriptor
+        if tail.flags() & (OWNER | EOQ) == EOQ {
+            queue.set_head(address);
+        }
+        self.tail = index;
+        self.head = (index + 1) % self.ring.len();
+    }
+}
+
+/// The transmit ring, only descriptors with frames belong to the DMA
+pub struct TxRing {
+    ring: Ring,
+    /// The next free descriptor
+    next: usize,
+    /// The oldest descriptor that was not reclaimed
+    pending: usize,
+    /// Number of descriptors that were not reclaimed
+    in_flight: usize,
+}
+
+impl TxRing {
+    pub fn new<M: DmaMemory>(
+        descriptors: &'static mut [Descriptor],
+        buffers: &'static mut [Buffer],
+        memory: &M,
+    ) -> Result<Self, RingError> {
+        let ring = Ring::new(descriptors, buffers, memory)?;
+        Ok(TxRing {
+            ring,
+            next: 0,
+            pending: 0,
+            in_flight: 0,
+        })
+    }
+    /// Forget all frames, the channel has to be stopped
+    pub fn start(&mut self) {
+        for descriptor in self.ring.descriptors.iter_mut() {
+            descriptor.set_next(0);
+            descriptor.set_flags(0);
+        }
+        self.next = 0;
+        self.pending = 0;
+        self.in_flight = 0;
+    }
+    /// Returns the number of free descriptors
+    pub fn free(&self) -> usize {
+        self.ring.len() - self.in_flight
+    }
+    /// Copy the frame into a buffer and queue it
+    ///
+    /// The frame is without CRC, short frames are padded. Completed frames
+    /// are reclaimed first if the ring is full.
+    pub fn send<Q: Queue, M: DmaMemory>(
+        &mut self,
+        queue: &Q,
+        memory: &M,
+        frame: &[u8],
+    ) -> Result<(), RingError> {
+        self.send_with(queue, memory, frame.len(), |buffer| {
+            buffer.copy_from_slice(frame)
+        })
+    }
+    /// Let the closure write a frame of `len` bytes into a buffer and queue
+    /// it, like `send`
+    pub fn send_with<Q: Queue, M: DmaMemory, F: FnOnce(&mut [u8]) -> R, R>(
+        &mut self,
+        queue: &Q,
+        memory: &M,
+        len: usize,
+        f: F,
+    ) -> Result<R, RingError> {
+        if len > BUFFER_SIZE {
+            return Err(RingError::TooLong);
+        }
+        if self.free() == 0 && self.reclaim(queue) == 0 {
+            return Err(RingError::Full);
+        }
+        let index = self.next;
+        let buffer = &mut self.ring.buffers[index];
+        let result = f(&mut buffer[..len]);
+        let padded = len.max(MIN_FRAME);
+        for byte in buffer[len..padded].iter_mut() {
+            *byte = 0;
+        }
+        let len = padded;
+        memory.clean(buffer.as_ptr(), len);
+        let buffer = self.ring.buffer_address(index, memory);
+        let address = self.ring.address(index);
+        let descriptor = &mut self.ring.descriptors[index];
+        descriptor.set_next(0);
+        descriptor.set_buffer(buffer);
+        descriptor.set_buffer_len(len);
+        descriptor.set_flags(SOP | EOP | OWNER | len as u32);
+        atomic::fence(Ordering::SeqCst);
+        if self.in_flight == 0 {
+            queue.set_head(address);
+        } else {
+            let previous = (index + self.ring.len() - 1) % self.ring.len();
+            let previous = &mut self.ring.descriptors[previous];
+            previous.set_next(address);
+            atomic::fence(Ordering::SeqCst);
+            // The DMA stopped at the previous frame before it saw this one
+            if previous.flags() & (OWNER | EOQ) == EOQ {
+                queue.set_head(address);
+            }
+        }
+        self.in_flight += 1;
+        self.next = (index + 1) % self.ring.len();
+        Ok(result)
+    }
+    /// Acknowledge the sent frames, returns how many were reclaimed
+    pub fn reclaim<Q: Queue>(&mut self, queue: &Q) -> usize {
+        let mut count = 0;
+        while self.in_flight > 0 {
+            let index = self.pending;
+            if self.ring.descriptors[index].flags() & OWNER != 0 {
+                break;
+            }
+            queue.acknowledge(self.ring.address(index));
+            self.pending = (index + 1) % self.ring.len();
+            self.in_flight -= 1;
+            count += 1;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Physical addresses of the descriptors and buffers of the simulated DMA
+    const DESCRIPTORS: u32 = 0x4a10_2000;
+    const BUFFERS: u32 = 0x8000_0000;
+
+    /// Maps the descriptors and buffers of one ring to physical addresses
+    struct Memory {
+        descriptors: *const Descriptor,
+        buffers: *const Buffer,
+        count: usize,
+    }
+
+    impl DmaMemory for Memory {
+        fn physical(&self, addr: *const u8) -> u32 {
+            let addr = addr as usize;
+            let descriptors = self.descriptors as usize;
+            let buffers = self.buffers as usize;
+            let descriptors_len = self.count * core::mem::size_of::<Descriptor>();
+            if (descriptors..descriptors + descriptors_len).contains(&addr) {
+                DESCRIPTORS + (addr - descriptors) as u32
+            } else if (buffers..buffers + self.count * BUFFER_SIZE).contains(&addr) {
+                BUFFERS + (addr - buffers) as u32
+            } else {
+                panic!("address outside of the ring");
+            }
+        }
+        fn clean(&self, _addr: *const u8, _len: usize) {}
+        fn invalidate(&self, _addr: *const u8, _len: usize) {}
+    }
+
+    /// One channel that walks the descriptors like the CPDMA
+    struct Dma {
+        descriptors: *mut Descriptor,
+        buffers: *mut Buffer,
+        /// The descriptor the channel works on, None while it is stopped
+        head: Cell<Option<u32>>,
+        /// Number of writes of the head descriptor pointer
+        starts: Cell<usize>,
+        /// The last value of the completion pointer
+        completed: Cell<u32>,
+    }
+
+    impl Queue for Dma {
+        fn set_head(&self, descriptor: u32) {
+            assert_eq!(self.head.get(), None, "head written while running");
+            self.head.set(Some(descriptor));
+            self.starts.set(self.starts.get() + 1);
+        }
+        fn acknowledge(&self, descriptor: u32) {
+            self.completed.set(descriptor);
+        }
+    }
+
+    impl Dma {
+        fn descriptor(&self, address: u32) -> *mut Descriptor {
+            let index = (address - DESCRIPTORS) as usize / core::mem::size_of::<Descriptor>();
+            unsafe { self.descriptors.add(index) }
+        }
+        fn buffer(&self, address: u32) -> *mut Buffer {
+            let index = (address - BUFFERS) as usize / BUFFER_SIZE;
+            unsafe { self.buffers.add(index) }
+        }
+        /// Move on to the next descriptor, stop at the end of the list
+        fn advance(&self, descriptor: &mut Descriptor, flags: u32) {
+            let next = descriptor.next();
+            if next == 0 {
+                descriptor.set_flags(flags | EOQ);
+                self.head.set(None);
+            } else {
+                descriptor.set_flags(flags);
+                self.head.set(Some(next));
+            }
+        }
+        /// Receive a frame, returns false if the channel is stopped
+        fn receive(&self, frame: &[u8], errors: u32) -> bool {
+            let address = match self.head.get() {
+                Some(address) => address,
+                None => return false,
+            };
+            let descriptor = unsafe { &mut *self.descriptor(address) };
+            assert_ne!(descriptor.flags() & OWNER, 0);
+            let buffer = unsafe { &mut *self.buffer(descriptor.buffer()) };
+            buffer[..frame.len()].copy_from_slice(frame);
+            descriptor.set_buffer_len(frame.len());
+            self.advance(descriptor, SOP | EOP | errors | frame.len() as u32);
+            true
+        }
+        /// Send the next frame, None if the channel is stopped
+        fn transmit(&self) -> Option<Vec<u8>> {
+            let descriptor = unsafe { &mut *self.descriptor(self.head.get()?) };
+            let flags = descriptor.flags();
+            assert_eq!(flags & (SOP | EOP | OWNER), SOP | EOP | OWNER);
+            let len = descriptor.buffer_len();
+            assert_eq!(descriptor.packet_len(), len);
+            let buffer = unsafe { &*self.buffer(descriptor.buffer()) };
+            let frame = buffer[..len].to_vec();
+            self.advance(descriptor, flags & !OWNER);
+            Some(frame)
+        }
+    }
+
+    fn ring(
+        count: usize,
+    ) -> (
+        &'static mut [Descriptor],
+        &'static mut [Buffer],
+        Memory,
+        Dma,
+    ) {
+        let descriptors: Box<[Descriptor]> = (0..count).map(|_| Descriptor::EMPTY).collect();
+        let descriptors = Box::leak(descriptors).as_mut_ptr();
+        let buffers = Box::leak(vec![[0; BUFFER_SIZE]; count].into_boxed_slice()).as_mut_ptr();
+        let memory = Memory {
+            descriptors,
+            buffers,
+            count,
+        };
+        let dma = Dma {
+            descriptors,
+            buffers,
+            head: Cell::new(None),
+            starts: Cell::new(0),
+            completed: Cell::new(0),
+        };
+        unsafe {
+            (
+                core::slice::from_raw_parts_mut(descriptors, count),
+                core::slice::from_raw_parts_mut(buffers, count),
+                memory,
+                dma,
+            )
+        }
+    }
+
+    fn address(index: usize) -> u32 {
+        DESCRIPTORS + (index * core::mem::size_of::<Descriptor>()) as u32
+    }
+
+    #[test]
+    fn rx_drops_frames_with_errors() {
+        let (descriptors, buffers, memory, dma) = ring(4);
+        let mut rx = RxRing::new(descriptors, buffers, &memory).unwrap();
+        rx.start(&dma, &memory);
+        assert!(dma.receive(&[1; 64], 1 << 23));
+        assert!(dma.receive(&[2; 64], 0));
+        assert_eq!(
+            rx.receive(&dma, &memory, |frame| frame.to_vec()),
+            Some(vec![2; 64])
+        );
+        assert_eq!(dma.completed.get(), address(1));
+        assert_eq!(rx.receive(&dma, &memory, |frame| frame.to_vec()), None);
+    }
+
+    #[test]
+    fn rx_restarts_after_end_of_queue() {
+        let (descriptors, buffers, memory, dma) = ring(2);
+        let mut rx = RxRing::new(descriptors, buffers, &memory).unwrap();
+        rx.start(&dma, &memory);
+        assert!(dma.receive(&[1; 64], 0));
+        assert!(dma.receive(&[2; 64], 0));
+        assert!(!dma.receive(&[3; 64], 0));
+        // Appending a descriptor to the stopped list restarts the channel
+        assert_eq!(rx.receive(&dma, &memory, |frame| frame[0]), Some(1));
+        assert_eq!(dma.starts.get(), 2);
+        assert!(dma.receive(&[3; 64], 0));
+        assert_eq!(rx.receive(&dma, &memory, |frame| frame[0]), Some(2));
+        assert_eq!(dma.starts.get(), 3);
+        // The channel is running, the descriptor is only linked
+        assert_eq!(rx.receive(&dma, &memory, |frame| frame[0]), Some(3));
+        assert_eq!(dma.starts.get(), 3);
+        assert!(dma.receive(&[4; 64], 0));
+        assert_eq!(rx.receive(&dma, &memory, |frame| frame[0]), Some(4));
+    }
+
+    #[test]
+    fn tx_send_and_reclaim_wrap_around() {
+        let (descriptors, buffers, memory, dma) = ring(3);
+        let mut tx = TxRing::new(descriptors, buffers, &memory).unwrap();
+        tx.start();
+        for i in 0..10 {
+            let frame = [i as u8; 100];
+            tx.send(&dma, &memory, &frame).unwrap();
+            if i % 2 == 1 {
+                assert_eq!(dma.transmit(), Some(vec![i as u8 - 1; 100]));
+                assert_eq!(dma.transmit(), Some(frame.to_vec()));
+            }
+        }
+        assert_eq!(tx.reclaim(&dma), 2);
+        assert_eq!(dma.completed.get(), address(9 % 3));
+        assert_eq!(tx.free(), 3);
+    }
+
+    #[test]
+    fn tx_full_if_nothing_was_sent() {
+        let (descriptors, buffers, memory, dma) = ring(2);
+        let mut tx = TxRing::new(descriptors, buffers, &memory).unwrap();
+        tx.start();
+        tx.send(&dma, &memory, &[1; 64]).unwrap();
+        tx.send(&dma, &memory, &[2; 64]).unwrap();
+        assert_eq!(tx.send(&dma, &memory, &[3; 64]), Err(RingError::Full));
+        assert_eq!(dma.transmit(), Some(vec![1; 64]));
+        tx.send(&dma, &memory, &[3; 64]).unwrap();
+        assert_eq!(dma.transmit(), Some(vec![2; 64]));
+        assert_eq!(dma.transmit(), Some(vec![3; 64]));
+    }
+
+    #[test]
+    fn tx_pads_short_frames() {
+        let (descriptors, buffers, memory, dma) = ring(1);
+        let mut tx = TxRing::new(descriptors, buffers, &memory).unwrap();
+        tx.start();
+        tx.send(&dma, &memory, &[0xff; 100]).unwrap();
+        assert_eq!(dma.transmit().map(|frame| frame.len()), Some(100));
+        tx.send(&dma, &memory, &[1; 10]).unwrap();
+        let mut padded = vec![1; 10];
+        padded.resize(MIN_FRAME, 0);
+        assert_eq!(dma.transmit(), Some(padded));
+    }
+}
@@ -0,0 +1,577 @@
+//! The Ethernet switch (CPSW)
+//!
+//! The switch has the host port 0, which is connected to the CPDMA, and the
+//! two MAC ports 1 and 2. The address lookup engine (ALE) decides to which
+//! ports a frame is forwarded. Only channel 0 of the CPDMA is used in both
+//! directions.
+//!
+//! The clocks of the switch have to be enabled in the PRCM, the pins muxed
+//! and the interface (MII by default) selected in the control module. The
+//! MAC address of the device can be read from the control module.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::cpdma::{self, RingError, RxRing, TxRing};
+use crate::device::dma::DmaMemory;
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use core::hint;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u32,
+    SOFT_RESET [
+        SOFT_RESET OFFSET(0) NUMBITS(1) []
+    ],
+    CONTROL [
+        ENABLE OFFSET(0) NUMBITS(1) []
+    ],
+    ALE_CONTROL [
+        BYPASS OFFSET(4) NUMBITS(1) [],
+        CLEAR_TABLE OFFSET(30) NUMBITS(1) [],
+        ENABLE_ALE OFFSET(31) NUMBITS(1) []
+    ],
+    ALE_TBLCTL [
+        ENTRY_POINTER OFFSET(0) NUMBITS(10) [],
+        WRITE_RDZ OFFSET(31) NUMBITS(1) []
+    ],
+    ALE_PORTCTL [
+        PORT_STATE OFFSET(0) NUMBITS(2) [
+            Disabled = 0,
+            Blocked = 1,
+            Learning = 2,
+            Forwarding = 3
+        ]
+    ],
+    MACCONTROL [
+        FULLDUPLEX OFFSET(0) NUMBITS(1) [],
+        GMII_EN OFFSET(5) NUMBITS(1) [],
+        GIG OFFSET(7) NUMBITS(1) [],
+        IFCTL_A OFFSET(15) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SsRegisterBlock {
+    _ID_VER: ReadOnly<u32, ()>,                       // 0x00
+    _CONTROL: ReadWrite<u32, ()>,                     // 0x04
+    SOFT_RESET: ReadWrite<u32, SOFT_RESET::Register>, // 0x08
+    STAT_PORT_EN: ReadWrite<u32, ()>,                 // 0x0C
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct PortRegisterBlock {
+    _CONTROL: ReadWrite<u32, ()>, // 0x00
+    __reserved_0: [u32; 7],
+    SA_LO: ReadWrite<u32, ()>, // 0x20
+    SA_HI: ReadWrite<u32, ()>, // 0x24
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct CpdmaRegisterBlock {
+    _TX_IDVER: ReadOnly<u32, ()>,                  // 0x00
+    TX_CONTROL: ReadWrite<u32, CONTROL::Register>, // 0x04
+    _TX_TEARDOWN: ReadWrite<u32, ()>,              // 0x08
+    __reserved_0: u32,
+    _RX_IDVER: ReadOnly<u32, ()>,                     // 0x10
+    RX_CONTROL: ReadWrite<u32, CONTROL::Register>,    // 0x14
+    _RX_TEARDOWN: ReadWrite<u32, ()>,                 // 0x18
+    SOFT_RESET: ReadWrite<u32, SOFT_RESET::Register>, // 0x1C
+    _DMACONTROL: ReadWrite<u32, ()>,                  // 0x20
+    _DMASTATUS: ReadOnly<u32, ()>,                    // 0x24
+    RX_BUFFER_OFFSET: ReadWrite<u32, ()>,             // 0x28
+    _EMCONTROL: ReadWrite<u32, ()>,                   // 0x2C
+    __reserved_1: [u32; 20],
+    _TX_INTSTAT_RAW: ReadOnly<u32, ()>,    // 0x80
+    _TX_INTSTAT_MASKED: ReadOnly<u32, ()>, // 0x84
+    TX_INTMASK_SET: WriteOnly<u32, ()>,    // 0x88
+    TX_INTMASK_CLEAR: WriteOnly<u32, ()>,  // 0x8C
+    _IN_VECTOR: ReadOnly<u32, ()>,         // 0x90
+    EOI_VECTOR: WriteOnly<u32, ()>,        // 0x94
+    __reserved_2: [u32; 2],
+    _RX_INTSTAT_RAW: ReadOnly<u32, ()>,   // 0xA0
+    RX_INTSTAT_MASKED: ReadOnly<u32, ()>, // 0xA4
+    RX_INTMASK_SET: WriteOnly<u32, ()>,   // 0xA8
+    RX_INTMASK_CLEAR: WriteOnly<u32, ()>, // 0xAC
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct StateRamRegisterBlock {
+    TX_HDP: [ReadWrite<u32, ()>; 8], // 0x00
+    RX_HDP: [ReadWrite<u32, ()>; 8], // 0x20
+    TX_CP: [ReadWrite<u32, ()>; 8],  // 0x40
+    RX_CP: [ReadWrite<u32, ()>; 8],  // 0x60
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct AleRegisterBlock {
+    _IDVER: ReadOnly<u32, ()>, // 0x00
+    __reserved_0: u32,
+    CONTROL: ReadWrite<u32, ALE_CONTROL::Register>, // 0x08
+    __reserved_1: [u32; 5],
+    TBLCTL: ReadWrite<u32, ALE_TBLCTL::Register>, // 0x20
+    __reserved_2: [u32; 4],
+    TBLW2: ReadWrite<u32, ()>,                           // 0x34
+    TBLW1: ReadWrite<u32, ()>,                           // 0x38
+    TBLW0: ReadWrite<u32, ()>,                           // 0x3C
+    PORTCTL: [ReadWrite<u32, ALE_PORTCTL::Register>; 6], // 0x40
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct SliverRegisterBlock {
+    _IDVER: ReadOnly<u32, ()>,                        // 0x00
+    MACCONTROL: ReadWrite<u32, MACCONTROL::Register>, // 0x04
+    _MACSTATUS: ReadOnly<u32, ()>,                    // 0x08
+    SOFT_RESET: ReadWrite<u32, SOFT_RESET::Register>, // 0x0C
+    RX_MAXLEN: ReadWrite<u32, ()>,                    // 0x10
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct WrRegisterBlock {
+    _IDVER: ReadOnly<u32, ()>,                        // 0x00
+    SOFT_RESET: ReadWrite<u32, SOFT_RESET::Register>, // 0x04
+    _CONTROL: ReadWrite<u32, ()>,                     // 0x08
+    _INT_CONTROL: ReadWrite<u32, ()>,                 // 0x0C
+    _C0_RX_THRESH_EN: ReadWrite<u32, ()>,             // 0x10
+    C0_RX_EN: ReadWrite<u32, ()>,                     // 0x14
+    C0_TX_EN: ReadWrite<u32, ()>,                     // 0x18
+    C0_MISC_EN: ReadWrite<u32, ()>,                   // 0x1C
+}
+
+const PORT_OFFSET: [u32; 2] = [0x200, 0x300];
+const CPDMA_OFFSET: u32 = 0x800;
+const STATERAM_OFFSET: u32 = 0xA00;
+const ALE_OFFSET: u32 = 0xD00;
+const SLIVER_OFFSET: [u32; 2] = [0xD80, 0xDC0];
+const WR_OFFSET: u32 = 0x1200;
+
+/// Number of entries of the ALE table
+pub const ALE_ENTRIES: u32 = 1024;
+/// Longest frame that is received, including CRC
+pub const MAX_FRAME: u32 = 1522;
+/// Broadcast address
+pub const BROADCAST: [u8; 6] = [0xff; 6];
+
+/// Interrupt of a received frame
+pub const RX_INTERRUPT: InterruptSource = InterruptSource::CpswRx0;
+/// Interrupt of a sent frame
+pub const TX_INTERRUPT: InterruptSource = InterruptSource::CpswTx0;
+pub const RX_THRESHOLD_INTERRUPT: InterruptSource = InterruptSource::CpswRxThr0;
+pub const MISC_INTERRUPT: InterruptSource = InterruptSource::CpswMisc0;
+
+const EOI_RX: u32 = 1;
+const EOI_TX: u32 = 2;
+const EOI_MISC: u32 = 3;
+/// Link interrupt of the MDIO in the misc interrupt
+const MISC_MDIO_LINK: u32 = 1 << 1;
+
+// Fields of an ALE entry, in the words 1 and 2 of the table
+const ENTRY_TYPE_SHIFT: u32 = 28;
+const ENTRY_TYPE_MASK: u32 = 0b11 << ENTRY_TYPE_SHIFT;
+const ENTRY_TYPE_FREE: u32 = 0;
+const ENTRY_TYPE_ADDRESS: u32 = 1;
+const UCAST_PERSISTENT: u32 = 0;
+const MCAST_FORWARD: u32 = 3;
+const STATE_SHIFT: u32 = 30;
+const PORT_SHIFT: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Port {
+    Host = 0,
+    Mac1 = 1,
+    Mac2 = 2,
+}
+
+impl Port {
+    /// Returns the index of a MAC port
+    fn mac(self) -> Option<usize> {
+        match self {
+            Port::Host => None,
+            Port::Mac1 => Some(0),
+            Port::Mac2 => Some(1),
+        }
+    }
+}
+
+/// The state of a port in the ALE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortState {
+    Disabled,
+    Blocked,
+    Learning,
+    Forwarding,
+}
+
+/// Speed of the link
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Speed {
+    Mbit10,
+    Mbit100,
+    Mbit1000,
+}
+
+struct RxQueue(&'static StateRamRegisterBlock);
+
+impl cpdma::Queue for RxQueue {
+    fn set_head(&self, descriptor: u32) {
+        self.0.RX_HDP[0].set(descriptor);
+    }
+    fn acknowledge(&self, descriptor: u32) {
+        self.0.RX_CP[0].set(descriptor);
+    }
+}
+
+struct TxQueue(&'static StateRamRegisterBlock);
+
+impl cpdma::Queue for TxQueue {
+    fn set_head(&self, descriptor: u32) {
+        self.0.TX_HDP[0].set(descriptor);
+    }
+    fn acknowledge(&self, descriptor: u32) {
+        self.0.TX_CP[0].set(descriptor);
+    }
+}
+
+/// The receive side of the switch, see `Cpsw::split`
+pub struct Receiver<'a> {
+    ring: &'a mut RxRing,
+    queue: RxQueue,
+}
+
+impl<'a> Receiver<'a> {
+    /// Drop the frames with errors, returns whether a frame is waiting
+    pub fn poll<M: DmaMemory>(&mut self, memory: &M) -> bool {
+        self.ring.poll(&self.queue, memory)
+    }
+    /// Hand the next received frame to the closure, None if there is none
+    pub fn receive<M: DmaMemory, F: FnOnce(&[u8]) -> R, R>(
+        &mut self,
+        memory: &M,
+        f: F,
+    ) -> Option<R> {
+        self.ring.receive(&self.queue, memory, f)
+    }
+}
+
+/// The transmit side of the switch, see `Cpsw::split`
+pub struct Sender<'a> {
+    ring: &'a mut TxRing,
+    queue: TxQueue,
+}
+
+impl<'a> Sender<'a> {
+    /// Reclaim the sent frames, returns the number of frames that can be
+    /// queued
+    pub fn free(&mut self) -> usize {
+        self.ring.reclaim(&self.queue);
+        self.ring.free()
+    }
+    /// Let the closure write a frame of `len` bytes without CRC and queue it
+    pub fn send_with<M: DmaMemory, F: FnOnce(&mut [u8]) -> R, R>(
+        &mut self,
+        len: usize,
+        memory: &M,
+        f: F,
+    ) -> Result<R, RingError> {
+        self.ring.send_with(&self.queue, memory, len, f)
+    }
+}
+
+pub struct Cpsw {
+    ss: &'static SsRegisterBlock,
+    ports: [&'static PortRegisterBlock; 2],
+    cpdma: &'static CpdmaRegisterBlock,
+    state: &'static StateRamRegisterBlock,
+    ale: &'static AleRegisterBlock,
+    slivers: [&'static SliverRegisterBlock; 2],
+    wr: &'static WrRegisterBlock,
+    rx: RxRing,
+    tx: TxRing,
+}
+
+// The descriptor rings belong to the Cpsw, the Receiver and the Sender only
+// borrow them
+unsafe impl Send for Cpsw {}
+
+/// Reset a module and wait until it is done
+fn soft_reset(reg: &ReadWrite<u32, SOFT_RESET::Register>) {
+    reg.write(SOFT_RESET::SOFT_RESET::SET);
+    while reg.is_set(SOFT_RESET::SOFT_RESET) {
+        hint::spin_loop();
+    }
+}
+
+impl Cpsw {
+    /// Create the driver at the base address of the switch subsystem
+    pub unsafe fn new(memory_addr: VirtualAddress, rx: RxRing, tx: TxRing) -> Self {
+        let base = memory_addr.as_u32();
+        Cpsw {
+            ss: &*(base as *mut SsRegisterBlock),
+            ports: [
+                &*((base + PORT_OFFSET[0]) as *mut PortRegisterBlock),
+                &*((base + PORT_OFFSET[1]) as *mut PortRegisterBlock),
+            ],
+            cpdma: &*((base + CPDMA_OFFSET) as *mut CpdmaRegisterBlock),
+            state: &*((base + STATERAM_OFFSET) as *mut StateRamRegisterBlock),
+            ale: &*((base + ALE_OFFSET) as *mut AleRegisterBlock),
+            slivers: [
+                &*((base + SLIVER_OFFSET[0]) as *mut SliverRegisterBlock),
+                &*((base + SLIVER_OFFSET[1]) as *mut SliverRegisterBlock),
+            ],
+            wr: &*((base + WR_OFFSET) as *mut WrRegisterBlock),
+            rx,
+            tx,
+        }
+    }
+    /// Reset the switch and start receiving frames for the MAC address
+    ///
+    /// All ports are forwarding, the links run at 100 Mbit full duplex until
+    /// changed with `set_link`.
+    pub fn init<M: DmaMemory>(&mut self, mac: [u8; 6], memory: &M) {
+        soft_reset(&self.wr.SOFT_RESET);
+        soft_reset(&self.ss.SOFT_RESET);
+        for sliver in self.slivers.iter() {
+            soft_reset(&sliver.SOFT_RESET);
+        }
+        soft_reset(&self.cpdma.SOFT_RESET);
+        for channel in 0..8 {
+            self.state.TX_HDP[channel].set(0);
+            self.state.RX_HDP[channel].set(0);
+            self.state.TX_CP[channel].set(0);
+            self.state.RX_CP[channel].set(0);
+        }
+        self.ale
+            .CONTROL
+            .write(ALE_CONTROL::ENABLE_ALE::SET + ALE_CONTROL::CLEAR_TABLE::SET);
+        for port in [Port::Host, Port::Mac1, Port::Mac2].iter() {
+            self.set_port_state(*port, PortState::Forwarding);
+        }
+        self.add_unicast(mac, Port::Host);
+        self.add_multicast(BROADCAST, 0b111);
+        let sa_hi = u32::from_le_bytes([mac[0], mac[1], mac[2], mac[3]]);
+        let sa_lo = u32::from(mac[4]) | u32::from(mac[5]) << 8;
+        for (index, port) in self.ports.iter().enumerate() {
+            port.SA_HI.set(sa_hi);
+            port.SA_LO.set(sa_lo);
+            self.slivers[index].RX_MAXLEN.set(MAX_FRAME);
+        }
+        self.set_link(Port::Mac1, Speed::Mbit100, true);
+        self.set_link(Port::Mac2, Speed::Mbit100, true);
+        self.ss.STAT_PORT_EN.set(0b111);
+        self.cpdma.RX_BUFFER_OFFSET.set(0);
+        self.tx.start();
+        self.cpdma.TX_CONTROL.write(CONTROL::ENABLE::SET);
+        self.cpdma.RX_CONTROL.write(CONTROL::ENABLE::SET);
+        self.rx.start(&RxQueue(self.state), memory);
+    }
+    /// Set speed and duplex of a MAC port, e.g. after the PHY negotiated
+    ///
+    /// Returns None for the host port.
+    pub fn set_link(&self, port: Port, speed: Speed, full_duplex: bool) -> Option<()> {
+        let sliver = self.slivers[port.mac()?];
+        let duplex = if full_duplex {
+            MACCONTROL::FULLDUPLEX::SET
+        } else {
+            MACCONTROL::FULLDUPLEX::CLEAR
+        };
+        let speed = match speed {
+            Speed::Mbit10 => MACCONTROL::GIG::CLEAR + MACCONTROL::IFCTL_A::CLEAR,
+            Speed::Mbit100 => MACCONTROL::GIG::CLEAR + MACCONTROL::IFCTL_A::SET,
+            Speed::Mbit1000 => MACCONTROL::GIG::SET + MACCONTROL::IFCTL_A::CLEAR,
+        };
+        sliver
+            .MACCONTROL
+            .write(MACCONTROL::GMII_EN::SET + duplex + speed);
+        Some(())
+    }
+    pub fn set_port_state(&self, port: Port, state: PortState) {
+        let state = match state {
+            PortState::Disabled => ALE_PORTCTL::PORT_STATE::Disabled,
+            PortState::Blocked => ALE_PORTCTL::PORT_STATE::Blocked,
+            PortState::Learning => ALE_PORTCTL::PORT_STATE::Learning,
+            PortState::Forwarding => ALE_PORTCTL::PORT_STATE::Forwarding,
+        };
+        self.ale.PORTCTL[port as usize].modify(state);
+    }
+    /// Forward all frames to the host port, regardless of the ALE table
+    pub fn set_ale_bypass(&self, bypass: bool) {
+        if bypass {
+            self.ale.CONTROL.modify(ALE_CONTROL::BYPASS::SET);
+        } else {
+            self.ale.CONTROL.modify(ALE_CONTROL::BYPASS::CLEAR);
+        }
+    }
+    fn read_entry(&self, index: u32) -> [u32; 3] {
+        self.ale.TBLCTL.write(ALE_TBLCTL::ENTRY_POINTER.val(index));
+        [
+            self.ale.TBLW0.get(),
+            self.ale.TBLW1.get(),
+            self.ale.TBLW2.get(),
+        ]
+    }
+    fn write_entry(&self, index: u32, entry: [u32; 3]) {
+        self.ale.TBLW0.set(entry[0]);
+        self.ale.TBLW1.set(entry[1]);
+        self.ale.TBLW2.set(entry[2]);
+        self.ale
+            .TBLCTL
+            .write(ALE_TBLCTL::ENTRY_POINTER.val(index) + ALE_TBLCTL::WRITE_RDZ::SET);
+    }
+    /// Returns the index of the entry of the address or else of a free entry
+    fn find_entry(&self, mac: [u8; 6]) -> (Option<u32>, Option<u32>) {
+        let words = address_words(mac);
+        let mut free = None;
+        for index in 0..ALE_ENTRIES {
+            let entry = self.read_entry(index);
+            let entry_type = (entry[1] & ENTRY_TYPE_MASK) >> ENTRY_TYPE_SHIFT;
+            if entry_type == ENTRY_TYPE_FREE {
+                free = free.or(Some(index));
+            } else if entry_type == ENTRY_TYPE_ADDRESS
+                && entry[0] == words[0]
+                && entry[1] & 0xffff == words[1]
+            {
+                return (Some(index), free);
+            }
+        }
+        (None, free)
+    }
+    /// Write the entry of an address, returns None if the table is full
+    fn set_address(&self, mac: [u8; 6], word1: u32, word2: u32) -> Option<()> {
+        let index = match self.find_entry(mac) {
+            (Some(index), _) => index,
+            (None, free) => free?,
+        };
+        let words = address_words(mac);
+        let word1 = words[1] | ENTRY_TYPE_ADDRESS << ENTRY_TYPE_SHIFT | word1;
+        self.write_entry(index, [words[0], word1, word2]);
+        Some(())
+    }
+    /// Forward frames to the unicast address to a port
+    ///
+    /// Returns None if the table is full.
+    pub fn add_unicast(&self, mac: [u8; 6], port: Port) -> Option<()> {
+        self.set_address(
+            mac,
+            UCAST_PERSISTENT << STATE_SHIFT,
+            (port as u32) << PORT_SHIFT,
+        )
+    }
+    /// Forward frames to the multicast address to a mask of ports
+    ///
+    /// Bit 0 of the mask is the host port. Returns None if the table is full.
+    pub fn add_multicast(&self, mac: [u8; 6], port_mask: u8) -> Option<()> {
+        self.set_address(
+            mac,
+            MCAST_FORWARD << STATE_SHIFT,
+            u32::from(port_mask & 0b111) << PORT_SHIFT,
+        )
+    }
+    /// Remove the entry of an address, returns None if there is none
+    pub fn remove_address(&self, mac: [u8; 6]) -> Option<()> {
+        let index = self.find_entry(mac).0?;
+        self.write_entry(index, [0; 3]);
+        Some(())
+    }
+    /// Queue a frame without CRC for sending
+    pub fn send<M: DmaMemory>(&mut self, frame: &[u8], memory: &M) -> Result<(), RingError> {
+        self.tx.send(&TxQueue(self.state), memory, frame)
+    }
+    /// Let the closure write a frame of `len` bytes without CRC and queue it
+    pub fn send_with<M: DmaMemory, F: FnOnce(&mut [u8]) -> R, R>(
+        &mut self,
+        len: usize,
+        memory: &M,
+        f: F,
+    ) -> Result<R, RingError> {
+        self.tx.send_with(&TxQueue(self.state), memory, len, f)
+    }
+    /// Returns the number of frames that can be queued
+    pub fn tx_free(&self) -> usize {
+        self.tx.free()
+    }
+    /// Hand the next received frame to the closure, None if there is none
+    pub fn receive<M: DmaMemory, F: FnOnce(&[u8]) -> R, R>(
+        &mut self,
+        memory: &M,
+        f: F,
+    ) -> Option<R> {
+        self.rx.receive(&RxQueue(self.state), memory, f)
+    }
+    /// Returns whether a received frame is waiting
+    pub fn is_rx_ready(&self) -> bool {
+        self.rx.is_ready()
+    }
+    /// Split into the receive and the transmit side, to use both at once
+    pub fn split(&mut self) -> (Receiver<'_>, Sender<'_>) {
+        (
+            Receiver {
+                ring: &mut self.rx,
+                queue: RxQueue(self.state),
+            },
+            Sender {
+                ring: &mut self.tx,
+                queue: TxQueue(self.state),
+            },
+        )
+    }
+    /// Enable the receive and transmit interrupt of channel 0
+    pub fn enable_interrupts(&self) {
+        self.cpdma.RX_INTMASK_SET.set(1);
+        self.cpdma.TX_INTMASK_SET.set(1);
+        self.wr.C0_RX_EN.set(1);
+        self.wr.C0_TX_EN.set(1);
+    }
+    pub fn disable_interrupts(&self) {
+        self.wr.C0_RX_EN.set(0);
+        self.wr.C0_TX_EN.set(0);
+        self.cpdma.RX_INTMASK_CLEAR.set(1);
+        self.cpdma.TX_INTMASK_CLEAR.set(1);
+    }
+    /// Returns whether the receive interrupt of channel 0 is pending
+    pub fn is_rx_pending(&self) -> bool {
+        self.cpdma.RX_INTSTAT_MASKED.get() & 1 != 0
+    }
+    /// Reclaim the sent frames and end the transmit interrupt
+    ///
+    /// Returns the number of reclaimed frames.
+    pub fn handle_tx_interrupt(&mut self) -> usize {
+        let count = self.tx.reclaim(&TxQueue(self.state));
+        self.cpdma.EOI_VECTOR.set(EOI_TX);
+        count
+    }
+    /// End the receive interrupt
+    ///
+    /// The interrupt is raised again as long as frames are not received.
+    pub fn end_rx_interrupt(&self) {
+        self.cpdma.EOI_VECTOR.set(EOI_RX);
+    }
+    /// Route the link interrupt of the MDIO to the misc interrupt
+    pub fn enable_link_interrupt(&self) {
+        let reg = &self.wr.C0_MISC_EN;
+        reg.set(reg.get() | MISC_MDIO_LINK);
+    }
+    pub fn disable_link_interrupt(&self) {
+        let reg = &self.wr.C0_MISC_EN;
+        reg.set(reg.get() & !MISC_MDIO_LINK);
+    }
+    /// End the misc interrupt, after the source was cleared
+    pub fn end_misc_interrupt(&self) {
+        self.cpdma.EOI_VECTOR.set(EOI_MISC);
+    }
+}
+
+/// Returns the address as words 0 and 1 of an ALE entry
+fn address_words(mac: [u8; 6]) -> [u32; 2] {
+    [
+        u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]]),
+        u32::from(mac[0]) << 8 | u32::from(mac[1]),
+    ]
+}
@@ -0,0 +1,393 @@
+//! Safe DMA transfers on top of the EDMA3
+//!
+//! A buffer is moved into a `Transfer` together with the channel and is only
+//! given back once the transfer has completed or was aborted. Buffers have to
+//! be `'static`, so that dropping an ongoing transfer can not free memory that
+//! the DMA still writes to.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::edma::{Channel, Edma, Param, SyncMode, Trigger};
+use core::hint;
+use core::sync::atomic::{self, Ordering};
+
+/// Largest number of bytes of a single transfer
+pub const MAX_LEN: usize = 0xffff;
+
+/// Address translation and cache maintenance for DMA buffers
+///
+/// The DMA works on physical addresses and bypasses the data cache.
+pub trait DmaMemory {
+    /// Returns the physical address of a buffer
+    fn physical(&self, addr: *const u8) -> u32;
+    /// Write the cached data of the range back to memory
+    ///
+    /// Called before the DMA reads the range. Has to end with a barrier.
+    fn clean(&self, addr: *const u8, len: usize);
+    /// Discard the cached data of the range
+    ///
+    /// Called before and after the DMA writes the range.
+    fn invalidate(&self, addr: *const u8, len: usize);
+}
+
+/// Identity mapped memory that is not cached
+pub struct Uncached;
+
+impl DmaMemory for Uncached {
+    fn physical(&self, addr: *const u8) -> u32 {
+        addr as u32
+    }
+    fn clean(&self, _addr: *const u8, _len: usize) {}
+    fn invalidate(&self, _addr: *const u8, _len: usize) {}
+}
+
+/// A buffer the DMA reads from
+///
+/// # Safety
+/// The returned range has to stay valid and must not move as long as the
+/// buffer is alive, even if the buffer itself is moved.
+pub unsafe trait ReadBuffer {
+    fn dma_read_buffer(&self) -> (*const u8, usize);
+}
+
+/// A buffer the DMA writes to
+///
+/// # Safety
+/// The returned range has to stay valid and must not move as long as the
+/// buffer is alive, even if the buffer itself is moved.
+pub unsafe trait WriteBuffer {
+    fn dma_write_buffer(&mut self) -> (*mut u8, usize);
+}
+
+unsafe impl ReadBuffer for &'static [u8] {
+    fn dma_read_buffer(&self) -> (*const u8, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl ReadBuffer for &'static mut [u8] {
+    fn dma_read_buffer(&self) -> (*const u8, usize) {
+        (self.as_ptr(), self.len())
+    }
+}
+
+unsafe impl<const N: usize> ReadBuffer for &'static [u8; N] {
+    fn dma_read_buffer(&self) -> (*const u8, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl<const N: usize> ReadBuffer for &'static mut [u8; N] {
+    fn dma_read_buffer(&self) -> (*const u8, usize) {
+        (self.as_ptr(), N)
+    }
+}
+
+unsafe impl WriteBuffer for &'static mut [u8] {
+    fn dma_write_buffer(&mut self) -> (*mut u8, usize) {
+        (self.as_mut_ptr(), self.len())
+    }
+}
+
+unsafe impl<const N: usize> WriteBuffer for &'static mut [u8; N] {
+    fn dma_write_buffer(&mut self) -> (*mut u8, usize) {
+        (self.as_mut_ptr(), N)
+    }
+}
+
+/// An ongoing transfer that owns its buffer and channel
+///
+/// Dropping the transfer leaves the DMA running and leaks the channel.
+#[must_use]
+pub struct Transfer<B> {
+    channel: Channel,
+    buffer: B,
+    /// The range the DMA writes to, invalidated after the transfer
+    written: Option<(usize, usize)>,
+}
+
+impl<B> Transfer<B> {
+    fn start(
+        edma: &Edma,
+        channel: Channel,
+        buffer: B,
+        param: Option<Param>,
+        trigger: Trigger,
+        written: Option<(usize, usize)>,
+    ) -> Result<Self, (B, Channel)> {
+        let param = match param {
+            Some(param) => param,
+            None => return Err((buffer, channel)),
+        };
+        edma.clear_completion(channel.number());
+        edma.write_param(channel.param(), &param);
+        // The buffer has to be in memory before the DMA sees it
+        atomic::fence(Ordering::SeqCst);
+        edma.start(&channel, trigger);
+        Ok(Transfer {
+            channel,
+            buffer,
+            written,
+        })
+    }
+    /// Returns whether the transfer has completed
+    pub fn is_done(&self, edma: &Edma) -> bool {
+        edma.is_complete(self.channel.number())
+    }
+    /// Busy-waits for the transfer and returns the buffer and the channel
+    pub fn wait<M: DmaMemory>(self, edma: &Edma, memory: &M) -> (B, Channel) {
+        while !self.is_done(edma) {
+            hint::spin_loop();
+        }
+        self.finish(edma, memory)
+    }
+    /// Returns the buffer and the channel if the transfer has completed
+    pub fn try_wait<M: DmaMemory>(self, edma: &Edma, memory: &M) -> Result<(B, Channel), Self> {
+        if self.is_done(edma) {
+            Ok(self.finish(edma, memory))
+        } else {
+            Err(self)
+        }
+    }
+    /// Stops the transfer
+    ///
+    /// The content of the buffer is undefined afterwards.
+    pub fn abort<M: DmaMemory>(self, edma: &Edma, memory: &M) -> (B, Channel) {
+        self.finish(edma, memory)
+    }
+    fn finish<M: DmaMemory>(self, edma: &Edma, memory: &M) -> (B, Channel) {
+        // The peripheral keeps raising events, e.g. for every free FIFO entry,
+        // they must not be latched for the next user of the channel
+        edma.stop(&self.channel);
+        edma.clear_completion(self.channel.number());
+        atomic::fence(Ordering::SeqCst);
+        if let Some((addr, len)) = self.written {
+            memory.invalidate(addr as *const u8, len);
+        }
+        (self.buffer, self.channel)
+    }
+}
+
+impl<B: ReadBuffer> Transfer<B> {
+    /// Writes the buffer byte by byte into a peripheral register
+    ///
+    /// Every DMA event of the channel moves one byte. Gives the buffer back if
+    /// it is empty or longer than `MAX_LEN`.
+    pub fn memory_to_peripheral<M: DmaMemory>(
+        edma: &Edma,
+        channel: Channel,
+        buffer: B,
+        register: u32,
+        memory: &M,
+    ) -> Result<Self, (B, Channel)> {
+        let (addr, len) = buffer.dma_read_buffer();
+        if len == 0 || len > MAX_LEN {
+            return Err((buffer, channel));
+        }
+        memory.clean(addr, len);
+        let param = byte_param(memory.physical(addr), register, len, true, channel.number());
+        Self::start(edma, channel, buffer, param, Trigger::Event, None)
+    }
+    /// Writes the buffer in blocks of 32 bit words into a FIFO register
+    ///
+    /// Every DMA event of the channel moves one block, after the last block
+    /// the set is linked to the null set. Gives the buffer back if it is
+    /// empty, its length is no multiple of the block or the block no multiple
+    /// of 4 bytes.
+    pub fn memory_to_fifo<M: DmaMemory>(
+        edma: &Edma,
+        channel: Channel,
+        buffer: B,
+        register: u32,
+        block: usize,
+        memory: &M,
+    ) -> Result<Self, (B, Channel)> {
+        let (addr, len) = buffer.dma_read_buffer();
+        let blocks = match block_count(len, block) {
+            Some(blocks) => blocks,
+            None => return Err((buffer, channel)),
+        };
+        memory.clean(addr, len);
+        let param = Param::new(
+            memory.physical(addr),
+            register,
+            4,
+            (block / 4) as u16,
+            blocks,
+        )
+        .map(|param| {
+            param
+                .sync(SyncMode::AB)
+                .src_index(4, block as i16)
+                .dst_index(0, 0)
+                .completion_code(channel.number())
+                .interrupt_on_completion()
+        });
+        Self::start(edma, channel, buffer, param, Trigger::Event, None)
+    }
+}
+
+impl<B: WriteBuffer> Transfer<B> {
+    /// Reads a peripheral register byte by byte into the buffer
+    ///
+    /// Every DMA event of the channel moves one byte. Gives the buffer back if
+    /// it is empty or longer than `MAX_LEN`.
+    pub fn peripheral_to_memory<M: DmaMemory>(
+        edma: &Edma,
+        channel: Channel,
+        mut buffer: B,
+        register: u32,
+        memory: &M,
+    ) -> Result<Self, (B, Channel)> {
+        let (addr, len) = buffer.dma_write_buffer();
+        if len == 0 || len > MAX_LEN {
+            return Err((buffer, channel));
+        }
+        // Dirty lines must not be evicted over the data of the DMA
+        memory.invalidate(addr, len);
+        let param = byte_param(
+            memory.physical(addr),
+            register,
+            len,
+            false,
+            channel.number(),
+        );
+        Self::start(
+            edma,
+            channel,
+            buffer,
+            param,
+            Trigger::Event,
+            Some((addr as usize, len)),
+        )
+    }
+    /// Reads a FIFO register in blocks of 32 bit words into the buffer
+    ///
+    /// Every DMA event of the channel moves one block, after the last block
+    /// the set is linked to the null set. Gives the buffer back if it is
+    /// empty, its length is no multiple of the block or the block no multiple
+    /// of 4 bytes.
+    pub fn fifo_to_memory<M: DmaMemory>(
+        edma: &Edma,
+        channel: Channel,
+        mut buffer: B,
+        register: u32,
+        block: usize,
+        memory: &M,
+    ) -> Result<Self, (B, Channel)> {
+        let (addr, len) = buffer.dma_write_buffer();
+        let blocks = match block_count(len, block) {
+            Some(blocks) => blocks,
+            None => return Err((buffer, channel)),
+        };
+        memory.invalidate(addr, len);
+        let param = Param::new(
+            register,
+            memory.physical(addr),
+            4,
+            (block / 4) as u16,
+            blocks,
+        )
+        .map(|param| {
+            param
+                .sync(SyncMode::AB)
+                .src_index(0, 0)
+                .dst_index(4, block as i16)
+                .completion_code(channel.number())
+                .interrupt_on_completion()
+        });
+        Self::start(
+            edma,
+            channel,
+            buffer,
+            param,
+            Trigger::Event,
+            Some((addr as usize, len)),
+        )
+    }
+}
+
+impl<S: ReadBuffer, D: WriteBuffer> Transfer<(S, D)> {
+    /// Copies the source into the destination
+    ///
+    /// Copies as many bytes as the shorter buffer holds. Gives the buffers
+    /// back if one is empty or longer than `MAX_LEN`.
+    pub fn memory_to_memory<M: DmaMemory>(
+        edma: &Edma,
+        channel: Channel,
+        src: S,
+        mut dst: D,
+        memory: &M,
+    ) -> Result<Self, ((S, D), Channel)> {
+        let (src_addr, src_len) = src.dma_read_buffer();
+        let (dst_addr, dst_len) = dst.dma_write_buffer();
+        let len = src_len.min(dst_len);
+        if len == 0 || src_len > MAX_LEN || dst_len > MAX_LEN {
+            return Err(((src, dst), channel));
+        }
+        memory.clean(src_addr, len);
+        memory.invalidate(dst_addr, len);
+        let param = Param::new(
+            memory.physical(src_addr),
+            memory.physical(dst_addr),
+            len as u16,
+            1,
+            1,
+        )
+        .map(|param| {
+            param
+                .sync(SyncMode::AB)
+                .completion_code(channel.number())
+                .interrupt_on_completion()
+                .static_set()
+        });
+        Self::start(
+            edma,
+            channel,
+            (src, dst),
+            param,
+            Trigger::Manual,
+            Some((dst_addr as usize, len)),
+        )
+    }
+}
+
+/// A set that moves one byte per event between memory and a register
+///
+/// The set is not static, after the last byte it is linked to the null set
+/// and further events of the peripheral do not transfer anything.
+fn byte_param(memory: u32, register: u32, len: usize, to_register: bool, tcc: u8) -> Option<Param> {
+    let param = if to_register {
+        Param::new(memory, register, 1, len as u16, 1).map(|param| param.dst_index(0, 0))
+    } else {
+        Param::new(register, memory, 1, len as u16, 1).map(|param| param.src_index(0, 0))
+    };
+    param.map(|param| param.completion_code(tcc).interrupt_on_completion())
+}
+
+/// Returns the number of blocks of a buffer for a block transfer
+fn block_count(len: usize, block: usize) -> Option<u16> {
+    if block == 0 || block % 4 != 0 || block > i16::MAX as usize {
+        return None;
+    }
+    if len == 0 || len % block != 0 || len / block > MAX_LEN {
+        return None;
+    }
+    Some((len / block) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_transfers_complete_after_len_events() {
+        for &len in [1, 2, 64, MAX_LEN].iter() {
+            for &to_register in [true, false].iter() {
+                let param = byte_param(0x8000_0000, 0x44e0_9000, len, to_register, 26).unwrap();
+                assert_eq!(param.requests(), len as u32);
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! The enhanced capture modules (eCAP)
+//!
+//! In capture mode the module timestamps up to four edges of its input with
+//! a 32 bit counter running at SYSCLK. In APWM mode the capture registers hold
+//! period and compare value and the pin is a single PWM output.
+//!
+//! The clock of the module has to be enabled in the PWM subsystem.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u16,
+    ECCTL1 [
+        CAP1POL OFFSET(0) NUMBITS(1) [],
+        CTRRST1 OFFSET(1) NUMBITS(1) [],
+        CAP2POL OFFSET(2) NUMBITS(1) [],
+        CTRRST2 OFFSET(3) NUMBITS(1) [],
+        CAP3POL OFFSET(4) NUMBITS(1) [],
+        CTRRST3 OFFSET(5) NUMBITS(1) [],
+        CAP4POL OFFSET(6) NUMBITS(1) [],
+        CTRRST4 OFFSET(7) NUMBITS(1) [],
+        CAPLDEN OFFSET(8) NUMBITS(1) [],
+        PRESCALE OFFSET(9) NUMBITS(5) [],
+        FREE_SOFT OFFSET(14) NUMBITS(2) []
+    ],
+    ECCTL2 [
+        CONT_ONESHT OFFSET(0) NUMBITS(1) [Continuous = 0, OneShot = 1],
+        STOP_WRAP OFFSET(1) NUMBITS(2) [],
+        REARM OFFSET(3) NUMBITS(1) [],
+        TSCTRSTOP OFFSET(4) NUMBITS(1) [Stopped = 0, Running = 1],
+        SYNCI_EN OFFSET(5) NUMBITS(1) [],
+        SYNCO_SEL OFFSET(6) NUMBITS(2) [],
+        SWSYNC OFFSET(8) NUMBITS(1) [],
+        CAP_APWM OFFSET(9) NUMBITS(1) [Capture = 0, Apwm = 1],
+        APWMPOL OFFSET(10) NUMBITS(1) [ActiveHigh = 0, ActiveLow = 1]
+    ],
+    ECFLG [
+        INT OFFSET(0) NUMBITS(1) [],
+        CEVT1 OFFSET(1) NUMBITS(1) [],
+        CEVT2 OFFSET(2) NUMBITS(1) [],
+        CEVT3 OFFSET(3) NUMBITS(1) [],
+        CEVT4 OFFSET(4) NUMBITS(1) [],
+        CTROVF OFFSET(5) NUMBITS(1) [],
+        CTR_PRD OFFSET(6) NUMBITS(1) [],
+        CTR_CMP OFFSET(7) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    TSCTR: ReadWrite<u32, ()>,    // 0x00
+    CTRPHS: ReadWrite<u32, ()>,   // 0x04
+    CAP: [ReadWrite<u32, ()>; 4], // 0x08
+    __reserved_0: [u32; 4],
+    ECCTL1: ReadWrite<u16, ECCTL1::Register>, // 0x28
+    ECCTL2: ReadWrite<u16, ECCTL2::Register>, // 0x2A
+    ECEINT: ReadWrite<u16, ECFLG::Register>,  // 0x2C
+    ECFLG: ReadOnly<u16, ECFLG::Register>,    // 0x2E
+    ECCLR: WriteOnly<u16, ECFLG::Register>,   // 0x30
+    ECFRC: WriteOnly<u16, ECFLG::Register>,   // 0x32
+}
+
+/// Interrupts of ECAP0 to ECAP2
+pub const INTERRUPTS: [InterruptSource; 3] = [
+    InterruptSource::Ecap0,
+    InterruptSource::Ecap1,
+    InterruptSource::Ecap2,
+];
+
+/// Frequency of the counter
+pub const SYSCLK_HZ: u32 = 100_000_000;
+/// Number of capture events
+pub const EVENT_NUM: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// The configuration of the capture mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptureConfig {
+    edges: [Edge; 4],
+    reset: [bool; 4],
+    prescale: u8,
+    one_shot: bool,
+    events: u8,
+}
+
+impl CaptureConfig {
+    /// Capture four rising edges continuously
+    pub fn new() -> Self {
+        CaptureConfig {
+            edges: [Edge::Rising; 4],
+            reset: [false; 4],
+            prescale: 1,
+            one_shot: false,
+            events: EVENT_NUM,
+        }
+    }
+    /// Set the edge of a capture event and whether the counter is reset
+    /// after it, for delta timestamps
+    ///
+    /// Events other than 0 to 3 are ignored.
+    pub fn edge(mut self, event: u8, edge: Edge, reset_counter: bool) -> Self {
+        if event < EVENT_NUM {
+            self.edges[event as usize] = edge;
+            self.reset[event as usize] = reset_counter;
+        }
+        self
+    }
+    /// Divide the input by 1 or an even number up to 62
+    pub fn prescale(mut self, prescale: u8) -> Self {
+        self.prescale = match prescale {
+            0 | 1 => 1,
+            _ => prescale.min(62) & !1,
+        };
+        self
+    }
+    /// Capture the events 0 to `events - 1` in a loop
+    pub fn continuous(mut self, events: u8) -> Self {
+        self.one_shot = false;
+        self.events = events.clamp(1, EVENT_NUM);
+        self
+    }
+    /// Capture the events 0 to `events - 1` once, until rearmed
+    pub fn one_shot(mut self, events: u8) -> Self {
+        self.one_shot = true;
+        self.events = events.clamp(1, EVENT_NUM);
+        self
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    /// A capture event 0 to 3 happened
+    Capture(u8),
+    /// The counter wrapped around
+    Overflow,
+    /// The counter reached the APWM period
+    Period,
+    /// The counter reached the APWM compare value
+    Compare,
+}
+
+impl Interrupt {
+    /// Returns None for a capture event that does not exist
+    fn mask(self) -> Option<u16> {
+        match self {
+            Interrupt::Capture(event) if event < EVENT_NUM => Some(1 << (event + 1)),
+            Interrupt::Capture(_) => None,
+            Interrupt::Overflow => Some(1 << 5),
+            Interrupt::Period => Some(1 << 6),
+            Interrupt::Compare => Some(1 << 7),
+        }
+    }
+}
+
+pub struct Ecap {
+    memory: &'static RegisterBlock,
+}
+
+// The captured counter values are only read and the capture sequence is only
+// rearmed through the Ecap
+unsafe impl Send for Ecap {}
+
+impl Ecap {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Ecap { memory }
+    }
+    /// Stop the counter and switch to capture mode
+    pub fn configure_capture(&self, config: &CaptureConfig) {
+        self.stop();
+        let mut ctl1 = 0;
+        for event in 0..EVENT_NUM as usize {
+            if config.edges[event] == Edge::Falling {
+                ctl1 |= 1 << (2 * event);
+            }
+            if config.reset[event] {
+                ctl1 |= 1 << (2 * event + 1);
+            }
+        }
+        self.memory.ECCTL1.set(ctl1);
+        self.memory.ECCTL1.modify(
+            ECCTL1::CAPLDEN::SET
+                + ECCTL1::PRESCALE.val(config.prescale as u16 / 2)
+                + ECCTL1::FREE_SOFT.val(0b10),
+        );
+        let mode = if config.one_shot {
+            ECCTL2::CONT_ONESHT::OneShot
+        } else {
+            ECCTL2::CONT_ONESHT::Continuous
+        };
+        self.memory.ECCTL2.write(
+            mode + ECCTL2::STOP_WRAP.val(config.events as u16 - 1) + ECCTL2::CAP_APWM::Capture,
+        );
+        self.memory.TSCTR.set(0);
+        if config.one_shot {
+            self.rearm();
+        }
+    }
+    /// Stop the counter and switch to APWM mode
+    ///
+    /// Period and compare are in SYSCLK cycles, the output is active from
+    /// zero until the compare value.
+    pub fn configure_apwm(&self, period: u32, compare: u32, active_high: bool) {
+        self.stop();
+        self.memory.ECCTL1.modify(ECCTL1::CAPLDEN::CLEAR);
+        let polarity = if active_high {
+            ECCTL2::APWMPOL::ActiveHigh
+        } else {
+            ECCTL2::APWMPOL::ActiveLow
+        };
+        self.memory.ECCTL2.write(ECCTL2::CAP_APWM::Apwm + polarity);
+        // CAP1 and CAP2 are the active period and compare registers
+        self.memory.CAP[0].set(period);
+        self.memory.CAP[1].set(compare);
+        self.memory.TSCTR.set(0);
+    }
+    /// Change the APWM period at the next period match
+    pub fn set_apwm_period(&self, period: u32) {
+        self.memory.CAP[2].set(period);
+    }
+    /// Change the APWM compare value at the next period match
+    pub fn set_apwm_compare(&self, compare: u32) {
+        self.memory.CAP[3].set(compare);
+    }
+    /// Start the counter
+    pub fn start(&self) {
+        self.memory.ECCTL2.modify(ECCTL2::TSCTRSTOP::Running);
+    }
+    pub fn stop(&self) {
+        self.memory.ECCTL2.modify(ECCTL2::TSCTRSTOP::Stopped);
+    }
+    /// Restart a one-shot capture at event 0
+    pub fn rearm(&self) {
+        self.memory.ECCTL2.modify(ECCTL2::REARM::SET);
+    }
+    pub fn counter(&self) -> u32 {
+        self.memory.TSCTR.get()
+    }
+    pub fn set_counter(&self, value: u32) {
+        self.memory.TSCTR.set(value);
+    }
+    /// Returns the timestamp of a capture event, None if it does not exist
+    pub fn capture(&self, event: u8) -> Option<u32> {
+        if event >= EVENT_NUM {
+            return None;
+        }
+        Some(self.memory.CAP[event as usize].get())
+    }
+    /// Returns whether the capture event happened since it was cleared
+    pub fn is_captured(&self, event: u8) -> bool {
+        self.is_pending(Interrupt::Capture(event))
+    }
+    /// Enable the interrupt, returns None if there is no such capture event
+    pub fn enable_interrupt(&self, interrupt: Interrupt) -> Option<()> {
+        let mask = interrupt.mask()?;
+        let reg = &self.memory.ECEINT;
+        reg.set(reg.get() | mask);
+        Some(())
+    }
+    /// Disable the interrupt, returns None if there is no such capture event
+    pub fn disable_interrupt(&self, interrupt: Interrupt) -> Option<()> {
+        let mask = interrupt.mask()?;
+        let reg = &self.memory.ECEINT;
+        reg.set(reg.get() & !mask);
+        Some(())
+    }
+    /// Returns whether the flag is set, false if there is no such capture event
+    pub fn is_pending(&self, interrupt: Interrupt) -> bool {
+        interrupt
+            .mask()
+            .map_or(false, |mask| self.memory.ECFLG.get() & mask != 0)
+    }
+    /// Clear the flag and the global interrupt, so the next one is raised
+    ///
+    /// Returns None if there is no such capture event.
+    pub fn clear_interrupt(&self, interrupt: Interrupt) -> Option<()> {
+        self.memory.ECCLR.set(interrupt.mask()? | 1);
+        Some(())
+    }
+    /// Set the flag by software, returns None if there is no such capture event
+    pub fn force_interrupt(&self, interrupt: Interrupt) -> Option<()> {
+        self.memory.ECFRC.set(interrupt.mask()?);
+        Some(())
+    }
+}
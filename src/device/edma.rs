@@ -0,0 +1,680 @@
+//! The enhanced direct memory access controller (EDMA3)
+//!
+//! The EDMA3 consists of the channel controller (TPCC), which holds the
+//! parameter sets (PaRAM) and schedules the transfers, and three transfer
+//! controllers (TPTC0 to TPTC2), which move the data. Every DMA event of a
+//! peripheral is hardwired to the channel with the same number. All addresses
+//! in a parameter set are physical addresses.
+//!
+//! The clocks of the TPCC and the TPTCs have to be enabled in the PRCM before
+//! the driver is used.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use core::convert::TryFrom;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u32,
+    CCCFG [
+        NUM_DMACH OFFSET(0) NUMBITS(3) [],
+        NUM_QDMACH OFFSET(4) NUMBITS(3) [],
+        NUM_INTCH OFFSET(8) NUMBITS(3) [],
+        NUM_PAENTRY OFFSET(12) NUMBITS(3) [],
+        NUM_EVQUE OFFSET(16) NUMBITS(3) [],
+        CHMAP_EXIST OFFSET(24) NUMBITS(1) [],
+        MP_EXIST OFFSET(25) NUMBITS(1) []
+    ],
+    DCHMAP [
+        PAENTRY OFFSET(5) NUMBITS(9) []
+    ],
+    CCERR [
+        QTHRXCD0 OFFSET(0) NUMBITS(1) [],
+        QTHRXCD1 OFFSET(1) NUMBITS(1) [],
+        QTHRXCD2 OFFSET(2) NUMBITS(1) [],
+        TCCERR OFFSET(16) NUMBITS(1) []
+    ],
+    OPT [
+        SAM OFFSET(0) NUMBITS(1) [Increment = 0, Constant = 1],
+        DAM OFFSET(1) NUMBITS(1) [Increment = 0, Constant = 1],
+        SYNCDIM OFFSET(2) NUMBITS(1) [ASync = 0, ABSync = 1],
+        STATIC OFFSET(3) NUMBITS(1) [],
+        FWID OFFSET(8) NUMBITS(3) [],
+        TCCMODE OFFSET(11) NUMBITS(1) [Normal = 0, Early = 1],
+        TCC OFFSET(12) NUMBITS(6) [],
+        TCINTEN OFFSET(20) NUMBITS(1) [],
+        ITCINTEN OFFSET(21) NUMBITS(1) [],
+        TCCHEN OFFSET(22) NUMBITS(1) [],
+        ITCCHEN OFFSET(23) NUMBITS(1) []
+    ],
+    A_B_CNT [
+        ACNT OFFSET(0) NUMBITS(16) [],
+        BCNT OFFSET(16) NUMBITS(16) []
+    ],
+    SRC_DST_IDX [
+        SRC OFFSET(0) NUMBITS(16) [],
+        DST OFFSET(16) NUMBITS(16) []
+    ],
+    LINK_BCNTRLD [
+        LINK OFFSET(0) NUMBITS(16) [Null = 0xFFFF],
+        BCNTRLD OFFSET(16) NUMBITS(16) []
+    ],
+    CCNT [
+        CCNT OFFSET(0) NUMBITS(16) []
+    ],
+    ERRSTAT [
+        BUSERR OFFSET(0) NUMBITS(1) [],
+        TRERR OFFSET(2) NUMBITS(1) [],
+        MMRAERR OFFSET(3) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct CcRegisterBlock {
+    _PID: ReadOnly<u32, ()>,               // 0x000
+    CCCFG: ReadOnly<u32, CCCFG::Register>, // 0x004
+    __reserved_0: [u32; 2],
+    _SYSCONFIG: ReadWrite<u32, ()>, // 0x010
+    __reserved_1: [u32; 59],
+    DCHMAP: [ReadWrite<u32, DCHMAP::Register>; 64], // 0x100
+    _QCHMAP: [ReadWrite<u32, ()>; 8],               // 0x200
+    __reserved_2: [u32; 8],
+    DMAQNUM: [ReadWrite<u32, ()>; 8], // 0x240
+    _QDMAQNUM: ReadWrite<u32, ()>,    // 0x260
+    __reserved_3: [u32; 8],
+    _QUEPRI: ReadWrite<u32, ()>, // 0x284
+    __reserved_4: [u32; 30],
+    EMR: [ReadOnly<u32, ()>; 2],           // 0x300
+    EMCR: [WriteOnly<u32, ()>; 2],         // 0x308
+    QEMR: ReadOnly<u32, ()>,               // 0x310
+    QEMCR: WriteOnly<u32, ()>,             // 0x314
+    CCERR: ReadOnly<u32, CCERR::Register>, // 0x318
+    CCERRCLR: WriteOnly<u32, ()>,          // 0x31C
+    EEVAL: WriteOnly<u32, ()>,             // 0x320
+    __reserved_5: [u32; 7],
+    DRAE0: [ReadWrite<u32, ()>; 2], // 0x340
+}
+
+/// The channel registers of the global region at 0x1000
+#[allow(non_snake_case)]
+#[repr(C)]
+struct ChannelRegisterBlock {
+    ER: [ReadOnly<u32, ()>; 2],    // 0x00
+    ECR: [WriteOnly<u32, ()>; 2],  // 0x08
+    ESR: [WriteOnly<u32, ()>; 2],  // 0x10
+    _CER: [ReadOnly<u32, ()>; 2],  // 0x18
+    EER: [ReadOnly<u32, ()>; 2],   // 0x20
+    EECR: [WriteOnly<u32, ()>; 2], // 0x28
+    EESR: [WriteOnly<u32, ()>; 2], // 0x30
+    _SER: [ReadOnly<u32, ()>; 2],  // 0x38
+    SECR: [WriteOnly<u32, ()>; 2], // 0x40
+    __reserved_0: [u32; 2],
+    IER: [ReadOnly<u32, ()>; 2],   // 0x50
+    IECR: [WriteOnly<u32, ()>; 2], // 0x58
+    IESR: [WriteOnly<u32, ()>; 2], // 0x60
+    IPR: [ReadOnly<u32, ()>; 2],   // 0x68
+    ICR: [WriteOnly<u32, ()>; 2],  // 0x70
+    IEVAL: WriteOnly<u32, ()>,     // 0x78
+}
+
+/// A parameter set at 0x4000 + 0x20 * n
+#[allow(non_snake_case)]
+#[repr(C)]
+struct ParamRegisterBlock {
+    OPT: ReadWrite<u32, OPT::Register>,                   // 0x00
+    SRC: ReadWrite<u32, ()>,                              // 0x04
+    A_B_CNT: ReadWrite<u32, A_B_CNT::Register>,           // 0x08
+    DST: ReadWrite<u32, ()>,                              // 0x0C
+    SRC_DST_BIDX: ReadWrite<u32, SRC_DST_IDX::Register>,  // 0x10
+    LINK_BCNTRLD: ReadWrite<u32, LINK_BCNTRLD::Register>, // 0x14
+    SRC_DST_CIDX: ReadWrite<u32, SRC_DST_IDX::Register>,  // 0x18
+    CCNT: ReadWrite<u32, CCNT::Register>,                 // 0x1C
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct TcRegisterBlock {
+    _PID: ReadOnly<u32, ()>,   // 0x000
+    _TCCFG: ReadOnly<u32, ()>, // 0x004
+    __reserved_0: [u32; 2],
+    _SYSCONFIG: ReadWrite<u32, ()>, // 0x010
+    __reserved_1: [u32; 59],
+    _TCSTAT: ReadOnly<u32, ()>, // 0x100
+    __reserved_2: [u32; 7],
+    ERRSTAT: ReadOnly<u32, ERRSTAT::Register>, // 0x120
+    ERREN: ReadWrite<u32, ERRSTAT::Register>,  // 0x124
+    ERRCLR: WriteOnly<u32, ERRSTAT::Register>, // 0x128
+    _ERRDET: ReadOnly<u32, ()>,                // 0x12C
+}
+
+/// Number of DMA channels
+pub const CHANNEL_NUM: usize = 64;
+/// Number of parameter sets
+pub const PARAM_NUM: usize = 256;
+/// Number of transfer controllers and event queues
+pub const TC_NUM: usize = 3;
+
+/// Interrupt raised when a transfer with completion interrupt is done
+pub const COMPLETION_INTERRUPT: InterruptSource = InterruptSource::Edma3CcInt0;
+/// Interrupt raised on missed events and queue threshold errors
+pub const ERROR_INTERRUPT: InterruptSource = InterruptSource::Edma3CcErr;
+/// Interrupt raised on memory protection errors
+pub const MEMORY_PROTECTION_INTERRUPT: InterruptSource = InterruptSource::Edma3CcMpErr;
+/// Error interrupts of TPTC0 to TPTC2
+pub const TC_ERROR_INTERRUPTS: [InterruptSource; TC_NUM] = [
+    InterruptSource::Edma3TcErr0,
+    InterruptSource::Edma3TcErr1,
+    InterruptSource::Edma3TcErr2,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdmaError {
+    /// The channel or parameter set does not exist
+    OutOfRange,
+    /// The channel was already allocated
+    ChannelTaken,
+    /// All channels are allocated
+    NoFreeChannel,
+    /// All parameter sets for linking are allocated
+    NoFreeParam,
+}
+
+/// The event queue, queue n is served by TPTCn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Queue {
+    Q0,
+    Q1,
+    Q2,
+}
+
+/// How a channel is started
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trigger {
+    /// The transfer is started by software
+    Manual,
+    /// The transfer is started by the DMA events of the peripheral
+    Event,
+}
+
+/// Transfer synchronization, i.e. how much is moved per event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    /// Every event transfers one array of ACNT bytes
+    A,
+    /// Every event transfers one frame of BCNT arrays
+    AB,
+}
+
+/// An allocated DMA channel
+#[derive(Debug, PartialEq)]
+pub struct Channel {
+    number: u8,
+}
+
+impl Channel {
+    pub fn number(&self) -> u8 {
+        self.number
+    }
+    /// Returns the parameter set that is started by the channel
+    pub fn param(&self) -> ParamIndex {
+        ParamIndex(self.number as u16)
+    }
+}
+
+/// The index of a parameter set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamIndex(u16);
+
+impl ParamIndex {
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
+    /// Returns the value of the LINK field that points to this set
+    fn link_address(&self) -> u16 {
+        0x4000 + self.0 * 0x20
+    }
+}
+
+/// The content of a parameter set
+///
+/// A transfer moves CCNT frames of BCNT arrays of ACNT bytes each. Between
+/// arrays the addresses advance by the B index, between frames by the C index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Param {
+    src: u32,
+    dst: u32,
+    acnt: u16,
+    bcnt: u16,
+    ccnt: u16,
+    src_bidx: i16,
+    dst_bidx: i16,
+    src_cidx: i16,
+    dst_cidx: i16,
+    bcnt_reload: u16,
+    link: Option<ParamIndex>,
+    sync: SyncMode,
+    static_set: bool,
+    tcc: u8,
+    interrupt: bool,
+    intermediate_interrupt: bool,
+    chain: bool,
+    intermediate_chain: bool,
+}
+
+impl Param {
+    /// A transfer of contiguous arrays
+    ///
+    /// The completion code is 0 and no interrupt is raised. Returns None if
+    /// there are several arrays that are too long for the B index to step
+    /// over, i.e. longer than 32767 bytes.
+    pub fn new(src: u32, dst: u32, acnt: u16, bcnt: u16, ccnt: u16) -> Option<Self> {
+        let bidx = if bcnt > 1 {
+            i16::try_from(acnt).ok()?
+        } else {
+            0
+        };
+        Some(Param {
+            src,
+            dst,
+            acnt,
+            bcnt,
+            ccnt,
+            src_bidx: bidx,
+            dst_bidx: bidx,
+            src_cidx: 0,
+            dst_cidx: 0,
+            bcnt_reload: 0,
+            link: None,
+            sync: SyncMode::A,
+            static_set: false,
+            tcc: 0,
+            interrupt: false,
+            intermediate_interrupt: false,
+            chain: false,
+            intermediate_chain: false,
+        })
+    }
+    /// Returns the number of synchronization events until the set is done
+    pub fn requests(&self) -> u32 {
+        match self.sync {
+            SyncMode::A if self.ccnt > 0 => {
+                self.bcnt as u32 + (self.ccnt as u32 - 1) * self.bcnt_reload as u32
+            }
+            SyncMode::A => 0,
+            SyncMode::AB => self.ccnt as u32,
+        }
+    }
+    pub fn sync(mut self, sync: SyncMode) -> Self {
+        self.sync = sync;
+        self
+    }
+    /// Set the address steps of the source between arrays and frames
+    ///
+    /// A step of 0 between arrays reads a peripheral FIFO.
+    pub fn src_index(mut self, bidx: i16, cidx: i16) -> Self {
+        self.src_bidx = bidx;
+        self.src_cidx = cidx;
+        self
+    }
+    /// Set the address steps of the destination between arrays and frames
+    ///
+    /// A step of 0 between arrays writes a peripheral FIFO.
+    pub fn dst_index(mut self, bidx: i16, cidx: i16) -> Self {
+        self.dst_bidx = bidx;
+        self.dst_cidx = cidx;
+        self
+    }
+    /// Reload value of BCNT in A synchronized transfers
+    pub fn bcnt_reload(mut self, bcnt_reload: u16) -> Self {
+        self.bcnt_reload = bcnt_reload;
+        self
+    }
+    /// Load the linked set into the channel when the transfer is done
+    pub fn link(mut self, next: ParamIndex) -> Self {
+        self.link = Some(next);
+        self
+    }
+    /// Keep the set unchanged after the transfer request
+    ///
+    /// A static set is neither updated nor linked after a request, so it is
+    /// only written for sets that are done with a single request, e.g. an AB
+    /// synchronized frame or a QDMA transfer. Other sets ignore it, they are
+    /// ended with a link instead.
+    pub fn static_set(mut self) -> Self {
+        self.static_set = true;
+        self
+    }
+    /// Set the transfer completion code, i.e. the bit set in IPR
+    pub fn completion_code(mut self, tcc: u8) -> Self {
+        self.tcc = tcc & 0x3f;
+        self
+    }
+    /// Raise the completion interrupt when the whole transfer is done
+    pub fn interrupt_on_completion(mut self) -> Self {
+        self.interrupt = true;
+        self
+    }
+    /// Raise the completion interrupt after every intermediate transfer
+    pub fn interrupt_on_intermediate(mut self) -> Self {
+        self.intermediate_interrupt = true;
+        self
+    }
+    /// Trigger the channel when the whole transfer is done
+    ///
+    /// Uses the number of the channel as completion code.
+    pub fn chain_to(mut self, channel: &Channel) -> Self {
+        self.tcc = channel.number;
+        self.chain = true;
+        self
+    }
+    /// Trigger the channel after every intermediate transfer
+    ///
+    /// Uses the number of the channel as completion code.
+    pub fn chain_intermediate_to(mut self, channel: &Channel) -> Self {
+        self.tcc = channel.number;
+        self.intermediate_chain = true;
+        self
+    }
+}
+
+/// Errors latched by the channel and transfer controllers
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ErrorStatus {
+    /// Channels that received an event while the previous one was pending
+    pub missed_events: u64,
+    /// QDMA channels with missed events
+    pub missed_qdma: u32,
+    /// Queue threshold and completion code errors (CCERR)
+    pub cc_error: u32,
+    /// Bus, transfer request and register errors of TPTC0 to TPTC2
+    pub tc_error: [u32; TC_NUM],
+}
+
+impl ErrorStatus {
+    pub fn is_empty(&self) -> bool {
+        self.missed_events == 0
+            && self.missed_qdma == 0
+            && self.cc_error == 0
+            && self.tc_error.iter().all(|&error| error == 0)
+    }
+}
+
+#[inline]
+fn split(bit: u8) -> (usize, u32) {
+    ((bit / 32) as usize, 1 << (bit % 32) as u32)
+}
+
+pub struct Edma {
+    cc: &'static CcRegisterBlock,
+    channels: &'static ChannelRegisterBlock,
+    params: &'static [ParamRegisterBlock; PARAM_NUM],
+    tcs: [&'static TcRegisterBlock; TC_NUM],
+    allocated_channels: u64,
+    allocated_params: [u32; PARAM_NUM / 32],
+}
+
+// Channels and parameter sets are handed out as plain numbers, every access to
+// their registers goes through the Edma
+unsafe impl Send for Edma {}
+
+impl Edma {
+    /// Creates the driver for the channel controller and the transfer
+    /// controllers
+    ///
+    /// # Safety
+    /// The virtual addresses have to point to the TPCC and to TPTC0 to TPTC2
+    pub unsafe fn new(cc_addr: VirtualAddress, tc_addrs: [VirtualAddress; TC_NUM]) -> Self {
+        let base = cc_addr.as_u32();
+        let edma = Edma {
+            cc: &*(base as *mut CcRegisterBlock),
+            channels: &*((base + 0x1000) as *mut ChannelRegisterBlock),
+            params: &*((base + 0x4000) as *mut [ParamRegisterBlock; PARAM_NUM]),
+            tcs: [
+                &*(tc_addrs[0].as_u32() as *mut TcRegisterBlock),
+                &*(tc_addrs[1].as_u32() as *mut TcRegisterBlock),
+                &*(tc_addrs[2].as_u32() as *mut TcRegisterBlock),
+            ],
+            allocated_channels: 0,
+            // The first sets belong to the channels
+            allocated_params: [0xffff_ffff, 0xffff_ffff, 0, 0, 0, 0, 0, 0],
+        };
+        edma.init();
+        edma
+    }
+    /// Disable all events and interrupts and clear all errors
+    fn init(&self) {
+        for bank in 0..2 {
+            self.channels.EECR[bank].set(0xffff_ffff);
+            self.channels.ECR[bank].set(0xffff_ffff);
+            self.channels.SECR[bank].set(0xffff_ffff);
+            self.channels.IECR[bank].set(0xffff_ffff);
+            self.channels.ICR[bank].set(0xffff_ffff);
+            self.cc.EMCR[bank].set(0xffff_ffff);
+            // Route the completion interrupts of all channels to region 0
+            self.cc.DRAE0[bank].set(0xffff_ffff);
+        }
+        self.cc.QEMCR.set(0xffff_ffff);
+        self.cc.CCERRCLR.set(0xffff_ffff);
+        for tc in self.tcs.iter() {
+            tc.ERRCLR
+                .write(ERRSTAT::BUSERR::SET + ERRSTAT::TRERR::SET + ERRSTAT::MMRAERR::SET);
+            tc.ERREN
+                .write(ERRSTAT::BUSERR::SET + ERRSTAT::TRERR::SET + ERRSTAT::MMRAERR::SET);
+        }
+        if self.cc.CCCFG.is_set(CCCFG::CHMAP_EXIST) {
+            for channel in 0..CHANNEL_NUM {
+                self.cc.DCHMAP[channel].write(DCHMAP::PAENTRY.val(channel as u32));
+            }
+        }
+    }
+    /// Allocate the channel of a DMA event
+    ///
+    /// The channel number is the event number of the peripheral, e.g. 26 for
+    /// the transmit event of UART0.
+    pub fn allocate_channel(&mut self, number: u8, queue: Queue) -> Result<Channel, EdmaError> {
+        if number as usize >= CHANNEL_NUM {
+            return Err(EdmaError::OutOfRange);
+        }
+        let bit = 1 << number as u64;
+        if self.allocated_channels & bit != 0 {
+            return Err(EdmaError::ChannelTaken);
+        }
+        self.allocated_channels |= bit;
+        self.set_queue(number, queue);
+        Ok(Channel { number })
+    }
+    /// Allocate any free channel for manually triggered transfers
+    ///
+    /// Starts from the highest channel, since these are used by fewer
+    /// peripherals.
+    pub fn allocate_any_channel(&mut self, queue: Queue) -> Result<Channel, EdmaError> {
+        let number = (0..CHANNEL_NUM as u8)
+            .rev()
+            .find(|&number| self.allocated_channels & (1 << number as u64) == 0)
+            .ok_or(EdmaError::NoFreeChannel)?;
+        self.allocate_channel(number, queue)
+    }
+    /// Stop the channel and give it back
+    pub fn free_channel(&mut self, channel: Channel) {
+        self.stop(&channel);
+        self.disable_completion_interrupt(channel.number);
+        self.allocated_channels &= !(1 << channel.number as u64);
+    }
+    fn set_queue(&self, number: u8, queue: Queue) {
+        let reg = &self.cc.DMAQNUM[(number / 8) as usize];
+        let shift = (number % 8) as u32 * 4;
+        let queue = match queue {
+            Queue::Q0 => 0,
+            Queue::Q1 => 1,
+            Queue::Q2 => 2,
+        };
+        reg.set((reg.get() & !(0x7 << shift)) | (queue << shift));
+    }
+    /// Allocate a parameter set for linking
+    pub fn allocate_param(&mut self) -> Result<ParamIndex, EdmaError> {
+        for index in CHANNEL_NUM..PARAM_NUM {
+            let (bank, bit) = (index / 32, 1 << (index % 32) as u32);
+            if self.allocated_params[bank] & bit == 0 {
+                self.allocated_params[bank] |= bit;
+                return Ok(ParamIndex(index as u16));
+            }
+        }
+        Err(EdmaError::NoFreeParam)
+    }
+    pub fn free_param(&mut self, param: ParamIndex) {
+        let index = param.0 as usize;
+        if !(CHANNEL_NUM..PARAM_NUM).contains(&index) {
+            return;
+        }
+        self.allocated_params[index / 32] &= !(1 << (index % 32) as u32);
+    }
+    /// Write a parameter set
+    pub fn write_param(&self, index: ParamIndex, param: &Param) {
+        let reg = &self.params[index.0 as usize];
+        let sync = match param.sync {
+            SyncMode::A => OPT::SYNCDIM::ASync,
+            SyncMode::AB => OPT::SYNCDIM::ABSync,
+        };
+        reg.OPT.write(
+            sync + OPT::STATIC.val((param.static_set && param.requests() == 1) as u32)
+                + OPT::TCC.val(param.tcc as u32)
+                + OPT::TCINTEN.val(param.interrupt as u32)
+                + OPT::ITCINTEN.val(param.intermediate_interrupt as u32)
+                + OPT::TCCHEN.val(param.chain as u32)
+                + OPT::ITCCHEN.val(param.intermediate_chain as u32),
+        );
+        reg.SRC.set(param.src);
+        reg.A_B_CNT
+            .write(A_B_CNT::ACNT.val(param.acnt as u32) + A_B_CNT::BCNT.val(param.bcnt as u32));
+        reg.DST.set(param.dst);
+        reg.SRC_DST_BIDX.write(
+            SRC_DST_IDX::SRC.val(param.src_bidx as u16 as u32)
+                + SRC_DST_IDX::DST.val(param.dst_bidx as u16 as u32),
+        );
+        let link = match param.link {
+            Some(next) => LINK_BCNTRLD::LINK.val(next.link_address() as u32),
+            None => LINK_BCNTRLD::LINK::Null,
+        };
+        reg.LINK_BCNTRLD
+            .write(link + LINK_BCNTRLD::BCNTRLD.val(param.bcnt_reload as u32));
+        reg.SRC_DST_CIDX.write(
+            SRC_DST_IDX::SRC.val(param.src_cidx as u16 as u32)
+                + SRC_DST_IDX::DST.val(param.dst_cidx as u16 as u32),
+        );
+        // Writing CCNT last, a QDMA trigger word would start here
+        reg.CCNT.write(CCNT::CCNT.val(param.ccnt as u32));
+    }
+    /// Change the link of a parameter set that was already written
+    pub fn link(&self, index: ParamIndex, next: Option<ParamIndex>) {
+        let link = match next {
+            Some(next) => LINK_BCNTRLD::LINK.val(next.link_address() as u32),
+            None => LINK_BCNTRLD::LINK::Null,
+        };
+        self.params[index.0 as usize].LINK_BCNTRLD.modify(link);
+    }
+    /// Returns the remaining number of arrays and frames of a parameter set
+    pub fn remaining(&self, index: ParamIndex) -> (u16, u16) {
+        let reg = &self.params[index.0 as usize];
+        (
+            reg.A_B_CNT.read(A_B_CNT::BCNT) as u16,
+            reg.CCNT.read(CCNT::CCNT) as u16,
+        )
+    }
+    /// Start the channel
+    ///
+    /// A manual trigger starts one synchronization event, an event trigger
+    /// lets the peripheral start them.
+    pub fn start(&self, channel: &Channel, trigger: Trigger) {
+        let (bank, bit) = split(channel.number);
+        match trigger {
+            Trigger::Manual => self.channels.ESR[bank].set(bit),
+            Trigger::Event => {
+                // Forget events and errors of a previous transfer
+                self.channels.ECR[bank].set(bit);
+                self.channels.SECR[bank].set(bit);
+                self.cc.EMCR[bank].set(bit);
+                self.channels.EESR[bank].set(bit);
+            }
+        }
+    }
+    /// Stop the channel from reacting to events and clear pending events
+    pub fn stop(&self, channel: &Channel) {
+        let (bank, bit) = split(channel.number);
+        self.channels.EECR[bank].set(bit);
+        self.channels.ECR[bank].set(bit);
+        self.channels.SECR[bank].set(bit);
+        self.cc.EMCR[bank].set(bit);
+    }
+    /// Returns whether an event of the channel is pending
+    pub fn is_event_pending(&self, channel: &Channel) -> bool {
+        let (bank, bit) = split(channel.number);
+        self.channels.ER[bank].get() & bit != 0
+    }
+    /// Returns whether the channel reacts to events
+    pub fn is_event_enabled(&self, channel: &Channel) -> bool {
+        let (bank, bit) = split(channel.number);
+        self.channels.EER[bank].get() & bit != 0
+    }
+    /// Returns whether a transfer with the completion code is done
+    pub fn is_complete(&self, tcc: u8) -> bool {
+        let (bank, bit) = split(tcc);
+        self.channels.IPR[bank].get() & bit != 0
+    }
+    pub fn clear_completion(&self, tcc: u8) {
+        let (bank, bit) = split(tcc);
+        self.channels.ICR[bank].set(bit);
+    }
+    pub fn enable_completion_interrupt(&self, tcc: u8) {
+        let (bank, bit) = split(tcc);
+        self.channels.IESR[bank].set(bit);
+    }
+    pub fn disable_completion_interrupt(&self, tcc: u8) {
+        let (bank, bit) = split(tcc);
+        self.channels.IECR[bank].set(bit);
+    }
+    /// Handle the completion interrupt
+    ///
+    /// Calls the closure with every completion code that is pending and
+    /// enabled and clears it. Afterwards the interrupt is evaluated again, so
+    /// that completions during the handler are not lost.
+    pub fn handle_completion<F: FnMut(u8)>(&self, mut f: F) {
+        for bank in 0..2 {
+            let pending = self.channels.IPR[bank].get() & self.channels.IER[bank].get();
+            for bit in 0..32 {
+                if pending & (1 << bit) != 0 {
+                    self.channels.ICR[bank].set(1 << bit);
+                    f((bank * 32 + bit) as u8);
+                }
+            }
+        }
+        self.channels.IEVAL.set(1);
+    }
+    /// Read the latched errors of the channel and transfer controllers
+    pub fn error_status(&self) -> ErrorStatus {
+        ErrorStatus {
+            missed_events: self.cc.EMR[0].get() as u64 | (self.cc.EMR[1].get() as u64) << 32,
+            missed_qdma: self.cc.QEMR.get(),
+            cc_error: self.cc.CCERR.get(),
+            tc_error: [
+                self.tcs[0].ERRSTAT.get(),
+                self.tcs[1].ERRSTAT.get(),
+                self.tcs[2].ERRSTAT.get(),
+            ],
+        }
+    }
+    /// Clear the errors and evaluate the error interrupt again
+    pub fn clear_errors(&self, status: &ErrorStatus) {
+        self.cc.EMCR[0].set(status.missed_events as u32);
+        self.cc.EMCR[1].set((status.missed_events >> 32) as u32);
+        self.cc.QEMCR.set(status.missed_qdma);
+        self.cc.CCERRCLR.set(status.cc_error);
+        for (tc, &error) in self.tcs.iter().zip(status.tc_error.iter()) {
+            tc.ERRCLR.set(error);
+        }
+        self.cc.EEVAL.set(1);
+    }
+}
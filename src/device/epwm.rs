@@ -0,0 +1,591 @@
+//! The enhanced high resolution PWM modules (ePWM)
+//!
+//! Every module has a 16 bit time-base counter, two comparators and two
+//! outputs A and B. The action qualifier decides what the outputs do on the
+//! counter events, followed by the dead-band generator and the trip zone.
+//!
+//! The clock of the module has to be enabled in the PWM subsystem and the
+//! time-base clock in the control module.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u16,
+    TBCTL [
+        CTRMODE OFFSET(0) NUMBITS(2) [
+            Up = 0,
+            Down = 1,
+            UpDown = 2,
+            Freeze = 3
+        ],
+        PHSEN OFFSET(2) NUMBITS(1) [],
+        PRDLD OFFSET(3) NUMBITS(1) [Shadow = 0, Immediate = 1],
+        SYNCOSEL OFFSET(4) NUMBITS(2) [
+            SyncIn = 0,
+            Zero = 1,
+            CompareB = 2,
+            Disabled = 3
+        ],
+        SWFSYNC OFFSET(6) NUMBITS(1) [],
+        HSPCLKDIV OFFSET(7) NUMBITS(3) [],
+        CLKDIV OFFSET(10) NUMBITS(3) [],
+        PHSDIR OFFSET(13) NUMBITS(1) [Down = 0, Up = 1],
+        FREE_SOFT OFFSET(14) NUMBITS(2) []
+    ],
+    TBSTS [
+        CTRDIR OFFSET(0) NUMBITS(1) [Down = 0, Up = 1],
+        SYNCI OFFSET(1) NUMBITS(1) [],
+        CTRMAX OFFSET(2) NUMBITS(1) []
+    ],
+    CMPCTL [
+        LOADAMODE OFFSET(0) NUMBITS(2) [],
+        LOADBMODE OFFSET(2) NUMBITS(2) [],
+        SHDWAMODE OFFSET(4) NUMBITS(1) [Shadow = 0, Immediate = 1],
+        SHDWBMODE OFFSET(6) NUMBITS(1) [Shadow = 0, Immediate = 1],
+        SHDWAFULL OFFSET(8) NUMBITS(1) [],
+        SHDWBFULL OFFSET(9) NUMBITS(1) []
+    ],
+    AQCTL [
+        ZRO OFFSET(0) NUMBITS(2) [],
+        PRD OFFSET(2) NUMBITS(2) [],
+        CAU OFFSET(4) NUMBITS(2) [],
+        CAD OFFSET(6) NUMBITS(2) [],
+        CBU OFFSET(8) NUMBITS(2) [],
+        CBD OFFSET(10) NUMBITS(2) []
+    ],
+    AQCSFRC [
+        CSFA OFFSET(0) NUMBITS(2) [],
+        CSFB OFFSET(2) NUMBITS(2) []
+    ],
+    DBCTL [
+        OUT_MODE OFFSET(0) NUMBITS(2) [Bypass = 0, Both = 3],
+        POLSEL OFFSET(2) NUMBITS(2) [],
+        IN_MODE OFFSET(4) NUMBITS(2) [A = 0]
+    ],
+    DB [
+        DEL OFFSET(0) NUMBITS(10) []
+    ],
+    TZCTL [
+        TZA OFFSET(0) NUMBITS(2) [],
+        TZB OFFSET(2) NUMBITS(2) []
+    ],
+    TZFLG [
+        INT OFFSET(0) NUMBITS(1) [],
+        CBC OFFSET(1) NUMBITS(1) [],
+        OST OFFSET(2) NUMBITS(1) []
+    ],
+    ETSEL [
+        INTSEL OFFSET(0) NUMBITS(3) [],
+        INTEN OFFSET(3) NUMBITS(1) []
+    ],
+    ETPS [
+        INTPRD OFFSET(0) NUMBITS(2) [],
+        INTCNT OFFSET(2) NUMBITS(2) []
+    ],
+    ETFLG [
+        INT OFFSET(0) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    TBCTL: ReadWrite<u16, TBCTL::Register>, // 0x00
+    TBSTS: ReadWrite<u16, TBSTS::Register>, // 0x02
+    _TBPHSHR: ReadWrite<u16, ()>,           // 0x04
+    TBPHS: ReadWrite<u16, ()>,              // 0x06
+    TBCNT: ReadWrite<u16, ()>,              // 0x08
+    TBPRD: ReadWrite<u16, ()>,              // 0x0A
+    __reserved_0: u16,
+    CMPCTL: ReadWrite<u16, CMPCTL::Register>,   // 0x0E
+    _CMPAHR: ReadWrite<u16, ()>,                // 0x10
+    CMPA: ReadWrite<u16, ()>,                   // 0x12
+    CMPB: ReadWrite<u16, ()>,                   // 0x14
+    AQCTLA: ReadWrite<u16, AQCTL::Register>,    // 0x16
+    AQCTLB: ReadWrite<u16, AQCTL::Register>,    // 0x18
+    _AQSFRC: ReadWrite<u16, ()>,                // 0x1A
+    AQCSFRC: ReadWrite<u16, AQCSFRC::Register>, // 0x1C
+    DBCTL: ReadWrite<u16, DBCTL::Register>,     // 0x1E
+    DBRED: ReadWrite<u16, DB::Register>,        // 0x20
+    DBFED: ReadWrite<u16, DB::Register>,        // 0x22
+    TZSEL: ReadWrite<u16, ()>,                  // 0x24
+    __reserved_1: u16,
+    TZCTL: ReadWrite<u16, TZCTL::Register>,  // 0x28
+    TZEINT: ReadWrite<u16, TZFLG::Register>, // 0x2A
+    TZFLG: ReadOnly<u16, TZFLG::Register>,   // 0x2C
+    TZCLR: WriteOnly<u16, TZFLG::Register>,  // 0x2E
+    TZFRC: WriteOnly<u16, TZFLG::Register>,  // 0x30
+    ETSEL: ReadWrite<u16, ETSEL::Register>,  // 0x32
+    ETPS: ReadWrite<u16, ETPS::Register>,    // 0x34
+    ETFLG: ReadOnly<u16, ETFLG::Register>,   // 0x36
+    ETCLR: WriteOnly<u16, ETFLG::Register>,  // 0x38
+    ETFRC: WriteOnly<u16, ETFLG::Register>,  // 0x3A
+}
+
+/// Event trigger interrupts of EPWM0 to EPWM2
+pub const INTERRUPTS: [InterruptSource; 3] = [
+    InterruptSource::Epwm0,
+    InterruptSource::Epwm1,
+    InterruptSource::Epwm2,
+];
+/// Trip zone interrupts of EPWM0 to EPWM2
+pub const TRIP_INTERRUPTS: [InterruptSource; 3] = [
+    InterruptSource::Epwm0Tz,
+    InterruptSource::Epwm1Tz,
+    InterruptSource::Epwm2Tz,
+];
+
+/// Frequency of the clock that drives the time-base
+pub const SYSCLK_HZ: u32 = 100_000_000;
+/// Number of trip zone inputs, TZ1 to TZ6
+pub const TRIP_ZONES: u8 = 6;
+
+/// The direction the time-base counter runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CounterMode {
+    /// Count up to the period and restart at zero, asymmetric PWM
+    Up,
+    /// Count down from the period to zero
+    Down,
+    /// Count up to the period and back to zero, symmetric PWM
+    UpDown,
+    /// Stop the counter
+    Freeze,
+}
+
+/// When the sync output pulse is generated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncOutput {
+    /// Pass the sync input through, to chain modules
+    SyncIn,
+    /// The counter is zero
+    Zero,
+    /// The counter equals compare B
+    CompareB,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparator {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    A,
+    B,
+}
+
+/// When a written compare value takes effect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareLoad {
+    Immediate,
+    /// Through the shadow register when the counter is zero
+    Zero,
+    /// Through the shadow register when the counter equals the period
+    Period,
+    /// Through the shadow register on zero or period
+    ZeroOrPeriod,
+}
+
+/// What an output does on a counter event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Nothing = 0,
+    Low = 1,
+    High = 2,
+    Toggle = 3,
+}
+
+/// The action qualifier of an output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Actions {
+    zero: Action,
+    period: Action,
+    compare_a_up: Action,
+    compare_a_down: Action,
+    compare_b_up: Action,
+    compare_b_down: Action,
+}
+
+impl Actions {
+    /// No action on any event
+    pub fn new() -> Self {
+        Actions {
+            zero: Action::Nothing,
+            period: Action::Nothing,
+            compare_a_up: Action::Nothing,
+            compare_a_down: Action::Nothing,
+            compare_b_up: Action::Nothing,
+            compare_b_down: Action::Nothing,
+        }
+    }
+    pub fn on_zero(mut self, action: Action) -> Self {
+        self.zero = action;
+        self
+    }
+    pub fn on_period(mut self, action: Action) -> Self {
+        self.period = action;
+        self
+    }
+    /// The counter equals the comparator while counting up
+    pub fn on_compare_up(mut self, comparator: Comparator, action: Action) -> Self {
+        match comparator {
+            Comparator::A => self.compare_a_up = action,
+            Comparator::B => self.compare_b_up = action,
+        }
+        self
+    }
+    /// The counter equals the comparator while counting down
+    pub fn on_compare_down(mut self, comparator: Comparator, action: Action) -> Self {
+        match comparator {
+            Comparator::A => self.compare_a_down = action,
+            Comparator::B => self.compare_b_down = action,
+        }
+        self
+    }
+}
+
+impl Default for Actions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The polarity of the dead-band outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Polarity {
+    /// Both outputs are delayed copies of output A
+    ActiveHigh = 0,
+    /// Output B is inverted, for complementary switches
+    ActiveHighComplementary = 2,
+    /// Output A is inverted
+    ActiveLowComplementary = 1,
+    /// Both outputs are inverted
+    ActiveLow = 3,
+}
+
+/// Delays of the rising and falling edges of output A in time-base clocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeadBand {
+    pub rising: u16,
+    pub falling: u16,
+    pub polarity: Polarity,
+}
+
+/// How a trip zone input disables the outputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TripMode {
+    /// Until the trip is cleared by software
+    OneShot,
+    /// Until the next zero of the counter
+    CycleByCycle,
+}
+
+impl TripMode {
+    fn shift(self) -> u16 {
+        match self {
+            TripMode::CycleByCycle => 0,
+            TripMode::OneShot => 8,
+        }
+    }
+    /// Bit of the trip zone input TZn in TZSEL, None if there is no such input
+    fn zone_bit(self, zone: u8) -> Option<u16> {
+        if !(1..=TRIP_ZONES).contains(&zone) {
+            return None;
+        }
+        Some(1 << (u16::from(zone - 1) + self.shift()))
+    }
+    fn flag(self) -> u16 {
+        match self {
+            TripMode::CycleByCycle => 1 << 1,
+            TripMode::OneShot => 1 << 2,
+        }
+    }
+}
+
+/// The state of an output while tripped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TripAction {
+    HighImpedance = 0,
+    High = 1,
+    Low = 2,
+    Nothing = 3,
+}
+
+/// The counter event that raises the event trigger interrupt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Zero = 1,
+    Period = 2,
+    CompareAUp = 4,
+    CompareADown = 5,
+    CompareBUp = 6,
+    CompareBDown = 7,
+}
+
+pub struct Epwm {
+    memory: &'static RegisterBlock,
+}
+
+// The clock gates of the module are in the Pwmss and the control module, the
+// Epwm only maps the registers of its own module
+unsafe impl Send for Epwm {}
+
+impl Epwm {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Epwm { memory }
+    }
+    /// Set the time-base clock to SYSCLK / (2^clkdiv * hspclkdiv)
+    ///
+    /// `clkdiv` is 0 to 7 and `hspclkdiv` is 1 or even up to 14. Returns None
+    /// for other values.
+    pub fn set_prescaler(&self, clkdiv: u8, hspclkdiv: u8) -> Option<()> {
+        let hsp = match hspclkdiv {
+            1 => 0,
+            2..=14 if hspclkdiv & 1 == 0 => hspclkdiv / 2,
+            _ => return None,
+        };
+        if clkdiv > 7 {
+            return None;
+        }
+        self.memory
+            .TBCTL
+            .modify(TBCTL::CLKDIV.val(clkdiv as u16) + TBCTL::HSPCLKDIV.val(hsp as u16));
+        Some(())
+    }
+    /// Returns the frequency of the time-base clock
+    pub fn timebase_hz(&self) -> u32 {
+        let clkdiv = 1 << self.memory.TBCTL.read(TBCTL::CLKDIV);
+        let hsp = match self.memory.TBCTL.read(TBCTL::HSPCLKDIV) {
+            0 => 1,
+            hsp => 2 * hsp as u32,
+        };
+        SYSCLK_HZ / (clkdiv * hsp)
+    }
+    pub fn set_counter_mode(&self, mode: CounterMode) {
+        let mode = match mode {
+            CounterMode::Up => TBCTL::CTRMODE::Up,
+            CounterMode::Down => TBCTL::CTRMODE::Down,
+            CounterMode::UpDown => TBCTL::CTRMODE::UpDown,
+            CounterMode::Freeze => TBCTL::CTRMODE::Freeze,
+        };
+        self.memory.TBCTL.modify(mode);
+    }
+    /// Set the period in time-base clocks
+    ///
+    /// The period takes effect when the counter is zero. In up-down mode the
+    /// PWM period is twice as long.
+    pub fn set_period(&self, period: u16) {
+        self.memory.TBCTL.modify(TBCTL::PRDLD::Shadow);
+        self.memory.TBPRD.set(period);
+    }
+    pub fn period(&self) -> u16 {
+        self.memory.TBPRD.get()
+    }
+    /// Set the period for a PWM frequency, returns None if it does not fit
+    pub fn set_frequency(&self, hz: u32) -> Option<()> {
+        if hz == 0 {
+            return None;
+        }
+        let counts = match self.memory.TBCTL.read(TBCTL::CTRMODE) {
+            2 => self.timebase_hz() / hz / 2,
+            _ => (self.timebase_hz() / hz).checked_sub(1)?,
+        };
+        if counts == 0 || counts > 0xffff {
+            return None;
+        }
+        self.set_period(counts as u16);
+        Some(())
+    }
+    pub fn counter(&self) -> u16 {
+        self.memory.TBCNT.get()
+    }
+    pub fn set_counter(&self, value: u16) {
+        self.memory.TBCNT.set(value);
+    }
+    /// Returns whether the counter counts up
+    pub fn is_counting_up(&self) -> bool {
+        self.memory.TBSTS.is_set(TBSTS::CTRDIR)
+    }
+    /// Load the phase into the counter on a sync input
+    ///
+    /// In up-down mode the counter continues in the given direction.
+    pub fn set_phase(&self, phase: Option<u16>, count_up: bool) {
+        match phase {
+            Some(phase) => {
+                self.memory.TBPHS.set(phase);
+                self.memory
+                    .TBCTL
+                    .modify(TBCTL::PHSEN::SET + TBCTL::PHSDIR.val(count_up as u16));
+            }
+            None => self.memory.TBCTL.modify(TBCTL::PHSEN::CLEAR),
+        }
+    }
+    pub fn set_sync_output(&self, sync: SyncOutput) {
+        let sync = match sync {
+            SyncOutput::SyncIn => TBCTL::SYNCOSEL::SyncIn,
+            SyncOutput::Zero => TBCTL::SYNCOSEL::Zero,
+            SyncOutput::CompareB => TBCTL::SYNCOSEL::CompareB,
+            SyncOutput::Disabled => TBCTL::SYNCOSEL::Disabled,
+        };
+        self.memory.TBCTL.modify(sync);
+    }
+    /// Generate a sync pulse by software
+    pub fn software_sync(&self) {
+        self.memory.TBCTL.modify(TBCTL::SWFSYNC::SET);
+    }
+    pub fn set_compare(&self, comparator: Comparator, value: u16) {
+        match comparator {
+            Comparator::A => self.memory.CMPA.set(value),
+            Comparator::B => self.memory.CMPB.set(value),
+        }
+    }
+    pub fn compare(&self, comparator: Comparator) -> u16 {
+        match comparator {
+            Comparator::A => self.memory.CMPA.get(),
+            Comparator::B => self.memory.CMPB.get(),
+        }
+    }
+    pub fn set_compare_load(&self, comparator: Comparator, load: CompareLoad) {
+        let (immediate, mode) = match load {
+            CompareLoad::Immediate => (1, 0),
+            CompareLoad::Zero => (0, 0),
+            CompareLoad::Period => (0, 1),
+            CompareLoad::ZeroOrPeriod => (0, 2),
+        };
+        match comparator {
+            Comparator::A => self
+                .memory
+                .CMPCTL
+                .modify(CMPCTL::SHDWAMODE.val(immediate) + CMPCTL::LOADAMODE.val(mode)),
+            Comparator::B => self
+                .memory
+                .CMPCTL
+                .modify(CMPCTL::SHDWBMODE.val(immediate) + CMPCTL::LOADBMODE.val(mode)),
+        }
+    }
+    /// Set the duty cycle in per mille of the period
+    ///
+    /// Assumes the output goes high on zero and low on the comparator.
+    pub fn set_duty(&self, comparator: Comparator, per_mille: u16) {
+        let value = self.period() as u32 * per_mille.min(1000) as u32 / 1000;
+        self.set_compare(comparator, value as u16);
+    }
+    pub fn set_actions(&self, output: Output, actions: &Actions) {
+        let value = AQCTL::ZRO.val(actions.zero as u16)
+            + AQCTL::PRD.val(actions.period as u16)
+            + AQCTL::CAU.val(actions.compare_a_up as u16)
+            + AQCTL::CAD.val(actions.compare_a_down as u16)
+            + AQCTL::CBU.val(actions.compare_b_up as u16)
+            + AQCTL::CBD.val(actions.compare_b_down as u16);
+        match output {
+            Output::A => self.memory.AQCTLA.write(value),
+            Output::B => self.memory.AQCTLB.write(value),
+        }
+    }
+    /// Force an output low or high, None gives it back to the action qualifier
+    pub fn force(&self, output: Output, level: Option<bool>) {
+        let value = match level {
+            Some(false) => 1,
+            Some(true) => 2,
+            None => 0,
+        };
+        match output {
+            Output::A => self.memory.AQCSFRC.modify(AQCSFRC::CSFA.val(value)),
+            Output::B => self.memory.AQCSFRC.modify(AQCSFRC::CSFB.val(value)),
+        }
+    }
+    /// Generate both outputs from output A with dead-band, None bypasses it
+    pub fn set_dead_band(&self, dead_band: Option<&DeadBand>) {
+        match dead_band {
+            Some(dead_band) => {
+                self.memory
+                    .DBRED
+                    .write(DB::DEL.val(dead_band.rising.min(0x3ff)));
+                self.memory
+                    .DBFED
+                    .write(DB::DEL.val(dead_band.falling.min(0x3ff)));
+                self.memory.DBCTL.write(
+                    DBCTL::OUT_MODE::Both
+                        + DBCTL::POLSEL.val(dead_band.polarity as u16)
+                        + DBCTL::IN_MODE::A,
+                );
+            }
+            None => self.memory.DBCTL.write(DBCTL::OUT_MODE::Bypass),
+        }
+    }
+    /// Connect the trip zone input TZn, 1 to 6, returns None otherwise
+    pub fn enable_trip(&self, zone: u8, mode: TripMode) -> Option<()> {
+        let bit = mode.zone_bit(zone)?;
+        let reg = &self.memory.TZSEL;
+        reg.set(reg.get() | bit);
+        Some(())
+    }
+    /// Disconnect the trip zone input TZn, 1 to 6, returns None otherwise
+    pub fn disable_trip(&self, zone: u8, mode: TripMode) -> Option<()> {
+        let bit = mode.zone_bit(zone)?;
+        let reg = &self.memory.TZSEL;
+        reg.set(reg.get() & !bit);
+        Some(())
+    }
+    pub fn set_trip_action(&self, output: Output, action: TripAction) {
+        match output {
+            Output::A => self.memory.TZCTL.modify(TZCTL::TZA.val(action as u16)),
+            Output::B => self.memory.TZCTL.modify(TZCTL::TZB.val(action as u16)),
+        }
+    }
+    pub fn enable_trip_interrupt(&self, mode: TripMode) {
+        let reg = &self.memory.TZEINT;
+        reg.set(reg.get() | mode.flag());
+    }
+    pub fn disable_trip_interrupt(&self, mode: TripMode) {
+        let reg = &self.memory.TZEINT;
+        reg.set(reg.get() & !mode.flag());
+    }
+    /// Returns whether the outputs are tripped in the mode
+    pub fn is_tripped(&self, mode: TripMode) -> bool {
+        self.memory.TZFLG.get() & mode.flag() != 0
+    }
+    /// Clear the trip and the trip zone interrupt
+    pub fn clear_trip(&self, mode: TripMode) {
+        self.memory.TZCLR.set(mode.flag() | 1);
+    }
+    /// Trip the outputs by software
+    pub fn force_trip(&self, mode: TripMode) {
+        self.memory.TZFRC.set(mode.flag());
+    }
+    /// Raise the event trigger interrupt on every `prescale`-th event
+    ///
+    /// `prescale` is 1 to 3, None disables the interrupt.
+    pub fn set_event_interrupt(&self, event: Option<Event>, prescale: u8) {
+        match event {
+            Some(event) => {
+                self.memory
+                    .ETPS
+                    .write(ETPS::INTPRD.val(prescale.clamp(1, 3) as u16));
+                self.memory
+                    .ETSEL
+                    .write(ETSEL::INTSEL.val(event as u16) + ETSEL::INTEN::SET);
+            }
+            None => self.memory.ETSEL.modify(ETSEL::INTEN::CLEAR),
+        }
+    }
+    pub fn is_event_pending(&self) -> bool {
+        self.memory.ETFLG.is_set(ETFLG::INT)
+    }
+    /// Clear the event trigger interrupt, the next one can be raised
+    pub fn clear_event_interrupt(&self) {
+        self.memory.ETCLR.write(ETFLG::INT::SET);
+    }
+    pub fn force_event_interrupt(&self) {
+        self.memory.ETFRC.write(ETFLG::INT::SET);
+    }
+}
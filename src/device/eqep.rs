@@ -0,0 +1,514 @@
+//! The enhanced quadrature encoder pulse modules (eQEP)
+//!
+//! The module decodes the A and B signals of an incremental encoder into a
+//! 32 bit position counter. The index and strobe inputs latch or reset the
+//! position. The unit timer measures high speeds as position per time, the
+//! capture timer low speeds as time per position.
+//!
+//! The clock of the module has to be enabled in the PWM subsystem.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_source::InterruptSource;
+use armv7::VirtualAddress;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u16,
+    QDECCTL [
+        QSP OFFSET(5) NUMBITS(1) [],
+        QIP OFFSET(6) NUMBITS(1) [],
+        QBP OFFSET(7) NUMBITS(1) [],
+        QAP OFFSET(8) NUMBITS(1) [],
+        IGATE OFFSET(9) NUMBITS(1) [],
+        SWAP OFFSET(10) NUMBITS(1) [],
+        XCR OFFSET(11) NUMBITS(1) [],
+        SPSEL OFFSET(12) NUMBITS(1) [],
+        SOEN OFFSET(13) NUMBITS(1) [],
+        QSRC OFFSET(14) NUMBITS(2) [
+            Quadrature = 0,
+            DirectionCount = 1,
+            UpCount = 2,
+            DownCount = 3
+        ]
+    ],
+    QEPCTL [
+        WDE OFFSET(0) NUMBITS(1) [],
+        UTE OFFSET(1) NUMBITS(1) [],
+        QCLM OFFSET(2) NUMBITS(1) [CpuRead = 0, UnitTimeout = 1],
+        QPEN OFFSET(3) NUMBITS(1) [],
+        IEL OFFSET(4) NUMBITS(2) [
+            Disabled = 0,
+            Rising = 1,
+            Falling = 2,
+            Marker = 3
+        ],
+        SEL OFFSET(6) NUMBITS(1) [Rising = 0, Direction = 1],
+        SWI OFFSET(7) NUMBITS(1) [],
+        IEI OFFSET(8) NUMBITS(2) [
+            Disabled = 0,
+            Rising = 2,
+            Falling = 3
+        ],
+        SEI OFFSET(10) NUMBITS(2) [
+            Disabled = 0,
+            Rising = 2,
+            Direction = 3
+        ],
+        PCRM OFFSET(12) NUMBITS(2) [
+            Index = 0,
+            Maximum = 1,
+            FirstIndex = 2,
+            UnitTime = 3
+        ],
+        FREE_SOFT OFFSET(14) NUMBITS(2) []
+    ],
+    QCAPCTL [
+        UPPS OFFSET(0) NUMBITS(4) [],
+        CCPS OFFSET(4) NUMBITS(3) [],
+        CEN OFFSET(15) NUMBITS(1) []
+    ],
+    QPOSCTL [
+        PCSPW OFFSET(0) NUMBITS(12) [],
+        PCE OFFSET(12) NUMBITS(1) [],
+        PCPOL OFFSET(13) NUMBITS(1) [ActiveHigh = 0, ActiveLow = 1],
+        PCLOAD OFFSET(14) NUMBITS(1) [],
+        PCSHDW OFFSET(15) NUMBITS(1) []
+    ],
+    QEPSTS [
+        PCEF OFFSET(0) NUMBITS(1) [],
+        FIMF OFFSET(1) NUMBITS(1) [],
+        CDEF OFFSET(2) NUMBITS(1) [],
+        COEF OFFSET(3) NUMBITS(1) [],
+        QDLF OFFSET(4) NUMBITS(1) [],
+        QDF OFFSET(5) NUMBITS(1) [Reverse = 0, Forward = 1],
+        FIDF OFFSET(6) NUMBITS(1) [],
+        UPEVNT OFFSET(7) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    QPOSCNT: ReadWrite<u32, ()>,                // 0x00
+    QPOSINIT: ReadWrite<u32, ()>,               // 0x04
+    QPOSMAX: ReadWrite<u32, ()>,                // 0x08
+    QPOSCMP: ReadWrite<u32, ()>,                // 0x0C
+    QPOSILAT: ReadOnly<u32, ()>,                // 0x10
+    QPOSSLAT: ReadOnly<u32, ()>,                // 0x14
+    QPOSLAT: ReadOnly<u32, ()>,                 // 0x18
+    QUTMR: ReadWrite<u32, ()>,                  // 0x1C
+    QUPRD: ReadWrite<u32, ()>,                  // 0x20
+    _QWDTMR: ReadWrite<u16, ()>,                // 0x24
+    _QWDPRD: ReadWrite<u16, ()>,                // 0x26
+    QDECCTL: ReadWrite<u16, QDECCTL::Register>, // 0x28
+    QEPCTL: ReadWrite<u16, QEPCTL::Register>,   // 0x2A
+    QCAPCTL: ReadWrite<u16, QCAPCTL::Register>, // 0x2C
+    QPOSCTL: ReadWrite<u16, QPOSCTL::Register>, // 0x2E
+    QEINT: ReadWrite<u16, ()>,                  // 0x30
+    QFLG: ReadOnly<u16, ()>,                    // 0x32
+    QCLR: WriteOnly<u16, ()>,                   // 0x34
+    QFRC: WriteOnly<u16, ()>,                   // 0x36
+    QEPSTS: ReadWrite<u16, QEPSTS::Register>,   // 0x38
+    QCTMR: ReadWrite<u16, ()>,                  // 0x3A
+    QCPRD: ReadWrite<u16, ()>,                  // 0x3C
+    _QCTMRLAT: ReadOnly<u16, ()>,               // 0x3E
+    QCPRDLAT: ReadOnly<u16, ()>,                // 0x40
+}
+
+/// Interrupts of EQEP0 to EQEP2
+pub const INTERRUPTS: [InterruptSource; 3] = [
+    InterruptSource::Eqep0,
+    InterruptSource::Eqep1,
+    InterruptSource::Eqep2,
+];
+
+/// Frequency of the unit and capture timers
+pub const SYSCLK_HZ: u32 = 100_000_000;
+
+/// How the A and B inputs are decoded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// Quadrature signals, four counts per line
+    Quadrature,
+    /// A is the clock and B the direction
+    DirectionCount,
+    /// Count A upwards
+    UpCount,
+    /// Count A downwards
+    DownCount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+}
+
+/// When the position counter is reset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionReset {
+    /// On every index pulse
+    Index,
+    /// When it passes the maximum position, for multi-turn counting
+    Maximum,
+    /// On the first index pulse only
+    FirstIndex,
+    /// On every unit timer event
+    UnitTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Rising,
+    Falling,
+}
+
+/// The configuration of the decoder and the position counter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncoderConfig {
+    source: Source,
+    swap: bool,
+    max_position: u32,
+    reset: PositionReset,
+    invert_a: bool,
+    invert_b: bool,
+    invert_index: bool,
+}
+
+impl EncoderConfig {
+    /// A quadrature encoder with `counts` positions per turn
+    ///
+    /// The position is reset when it passes `counts - 1`.
+    pub fn new(counts: u32) -> Self {
+        EncoderConfig {
+            source: Source::Quadrature,
+            swap: false,
+            max_position: counts.saturating_sub(1),
+            reset: PositionReset::Maximum,
+            invert_a: false,
+            invert_b: false,
+            invert_index: false,
+        }
+    }
+    pub fn source(mut self, source: Source) -> Self {
+        self.source = source;
+        self
+    }
+    /// Swap A and B, which reverses the direction
+    pub fn swap(mut self) -> Self {
+        self.swap = true;
+        self
+    }
+    pub fn reset(mut self, reset: PositionReset) -> Self {
+        self.reset = reset;
+        self
+    }
+    pub fn invert_inputs(mut self, a: bool, b: bool, index: bool) -> Self {
+        self.invert_a = a;
+        self.invert_b = b;
+        self.invert_index = index;
+        self
+    }
+}
+
+/// The position compare output on the strobe pin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompareOutput {
+    pub position: u32,
+    /// Width of the pulse in units of four SYSCLK cycles, 1 to 4096
+    pub pulse_width: u16,
+    pub active_high: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interrupt {
+    /// The position at the index differed from the expected one
+    PositionCounterError,
+    /// The position compare value was loaded from the shadow register
+    PositionCompareReady,
+    /// The input signals changed at the same time
+    PhaseError,
+    /// The direction changed
+    DirectionChange,
+    /// The watchdog expired without a position event
+    Watchdog,
+    /// The position counter went below zero
+    Underflow,
+    /// The position counter went above the maximum
+    Overflow,
+    /// The position matched the compare value
+    PositionCompare,
+    /// The position was latched on the strobe
+    StrobeLatch,
+    /// The position was latched on the index
+    IndexLatch,
+    /// The unit timer expired
+    UnitTimeout,
+}
+
+impl Interrupt {
+    fn mask(self) -> u16 {
+        match self {
+            Interrupt::PositionCounterError => 1 << 1,
+            Interrupt::PhaseError => 1 << 2,
+            Interrupt::DirectionChange => 1 << 3,
+            Interrupt::Watchdog => 1 << 4,
+            Interrupt::Underflow => 1 << 5,
+            Interrupt::Overflow => 1 << 6,
+            Interrupt::PositionCompareReady => 1 << 7,
+            Interrupt::PositionCompare => 1 << 8,
+            Interrupt::StrobeLatch => 1 << 9,
+            Interrupt::IndexLatch => 1 << 10,
+            Interrupt::UnitTimeout => 1 << 11,
+        }
+    }
+}
+
+/// A rotary encoder on an eQEP module
+pub struct Encoder {
+    memory: &'static RegisterBlock,
+    /// The position latched at the last unit timeout
+    last_latch: u32,
+}
+
+// The latched position for the velocity is kept next to the registers, the
+// unit timer latch is only acknowledged through the Encoder
+unsafe impl Send for Encoder {}
+
+impl Encoder {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Encoder {
+            memory,
+            last_latch: 0,
+        }
+    }
+    /// Configure the decoder and start counting at position zero
+    pub fn configure(&mut self, config: &EncoderConfig) {
+        self.disable();
+        let source = match config.source {
+            Source::Quadrature => QDECCTL::QSRC::Quadrature,
+            Source::DirectionCount => QDECCTL::QSRC::DirectionCount,
+            Source::UpCount => QDECCTL::QSRC::UpCount,
+            Source::DownCount => QDECCTL::QSRC::DownCount,
+        };
+        self.memory.QDECCTL.write(
+            source
+                + QDECCTL::SWAP.val(config.swap as u16)
+                + QDECCTL::QAP.val(config.invert_a as u16)
+                + QDECCTL::QBP.val(config.invert_b as u16)
+                + QDECCTL::QIP.val(config.invert_index as u16),
+        );
+        let reset = match config.reset {
+            PositionReset::Index => QEPCTL::PCRM::Index,
+            PositionReset::Maximum => QEPCTL::PCRM::Maximum,
+            PositionReset::FirstIndex => QEPCTL::PCRM::FirstIndex,
+            PositionReset::UnitTime => QEPCTL::PCRM::UnitTime,
+        };
+        self.memory
+            .QEPCTL
+            .modify(reset + QEPCTL::FREE_SOFT.val(0b10));
+        self.memory.QPOSMAX.set(config.max_position);
+        self.memory.QPOSCNT.set(0);
+        self.last_latch = 0;
+        self.memory.QCLR.set(0xffff);
+        self.enable();
+    }
+    /// Start the position counter
+    pub fn enable(&self) {
+        self.memory.QEPCTL.modify(QEPCTL::QPEN::SET);
+    }
+    /// Stop and reset the position counter
+    pub fn disable(&self) {
+        self.memory.QEPCTL.modify(QEPCTL::QPEN::CLEAR);
+    }
+    pub fn position(&self) -> u32 {
+        self.memory.QPOSCNT.get()
+    }
+    pub fn set_position(&self, position: u32) {
+        self.memory.QPOSCNT.set(position);
+    }
+    /// Returns the maximum position before the counter wraps
+    pub fn max_position(&self) -> u32 {
+        self.memory.QPOSMAX.get()
+    }
+    /// Returns the direction of the last position change
+    pub fn direction(&self) -> Direction {
+        if self.memory.QEPSTS.is_set(QEPSTS::QDF) {
+            Direction::Forward
+        } else {
+            Direction::Reverse
+        }
+    }
+    /// Returns whether the first index pulse was seen
+    pub fn index_seen(&self) -> bool {
+        self.memory.QEPSTS.is_set(QEPSTS::FIMF)
+    }
+    /// Latch the position on the index, None disables the latch
+    pub fn latch_on_index(&self, edge: Option<Edge>) {
+        let edge = match edge {
+            Some(Edge::Rising) => QEPCTL::IEL::Rising,
+            Some(Edge::Falling) => QEPCTL::IEL::Falling,
+            None => QEPCTL::IEL::Disabled,
+        };
+        self.memory.QEPCTL.modify(edge);
+    }
+    /// Returns the position latched on the last index pulse
+    pub fn index_position(&self) -> u32 {
+        self.memory.QPOSILAT.get()
+    }
+    /// Latch the position on the strobe
+    ///
+    /// With `by_direction` the position is latched on the rising edge when
+    /// moving forward and on the falling edge otherwise.
+    pub fn latch_on_strobe(&self, by_direction: bool) {
+        let select = if by_direction {
+            QEPCTL::SEL::Direction
+        } else {
+            QEPCTL::SEL::Rising
+        };
+        self.memory.QEPCTL.modify(select);
+    }
+    /// Returns the position latched on the last strobe pulse
+    pub fn strobe_position(&self) -> u32 {
+        self.memory.QPOSSLAT.get()
+    }
+    /// Load the position on the index, None disables the initialization
+    pub fn init_on_index(&self, position: u32, edge: Option<Edge>) {
+        self.memory.QPOSINIT.set(position);
+        let edge = match edge {
+            Some(Edge::Rising) => QEPCTL::IEI::Rising,
+            Some(Edge::Falling) => QEPCTL::IEI::Falling,
+            None => QEPCTL::IEI::Disabled,
+        };
+        self.memory.QEPCTL.modify(edge);
+    }
+    /// Start the unit timer for the velocity measurement
+    ///
+    /// The position and the capture period are latched every `period` SYSCLK
+    /// cycles.
+    pub fn enable_unit_timer(&mut self, period: u32) {
+        self.memory.QEPCTL.modify(QEPCTL::UTE::CLEAR);
+        self.memory.QUPRD.set(period);
+        self.memory.QUTMR.set(0);
+        self.memory
+            .QEPCTL
+            .modify(QEPCTL::QCLM::UnitTimeout + QEPCTL::UTE::SET);
+        self.last_latch = self.memory.QPOSLAT.get();
+    }
+    pub fn disable_unit_timer(&self) {
+        self.memory
+            .QEPCTL
+            .modify(QEPCTL::UTE::CLEAR + QEPCTL::QCLM::CpuRead);
+    }
+    /// Returns the velocity in counts per second after a unit timeout
+    ///
+    /// Measures the position change over the unit period, which is accurate
+    /// at high speeds. Returns None if the unit timer has not expired since
+    /// the last call.
+    pub fn velocity(&mut self) -> Option<i32> {
+        let timeout = Interrupt::UnitTimeout.mask();
+        if self.memory.QFLG.get() & timeout == 0 {
+            return None;
+        }
+        let latch = self.memory.QPOSLAT.get();
+        self.memory.QCLR.set(timeout);
+        let range = self.memory.QPOSMAX.get() as i64 + 1;
+        let mut delta = (latch as i64 - self.last_latch as i64).rem_euclid(range);
+        if delta > range / 2 {
+            delta -= range;
+        }
+        self.last_latch = latch;
+        let period = self.memory.QUPRD.get().max(1) as i64;
+        Some((delta * SYSCLK_HZ as i64 / period) as i32)
+    }
+    /// Start the capture timer for the low speed measurement
+    ///
+    /// The timer runs at SYSCLK / 2^`clock_prescale` and measures the time
+    /// between 2^`event_prescale` position events. `clock_prescale` is at
+    /// most 7 and `event_prescale` at most 11, returns None otherwise.
+    pub fn enable_capture(&self, clock_prescale: u8, event_prescale: u8) -> Option<()> {
+        if clock_prescale > 7 || event_prescale > 11 {
+            return None;
+        }
+        self.memory.QCAPCTL.write(QCAPCTL::CEN::CLEAR);
+        self.memory.QCAPCTL.write(
+            QCAPCTL::CCPS.val(clock_prescale as u16)
+                + QCAPCTL::UPPS.val(event_prescale as u16)
+                + QCAPCTL::CEN::SET,
+        );
+        Some(())
+    }
+    pub fn disable_capture(&self) {
+        self.memory.QCAPCTL.write(QCAPCTL::CEN::CLEAR);
+    }
+    /// Returns the speed in counts per second from the capture timer
+    ///
+    /// Measures the time between position events, which is accurate at low
+    /// speeds. Returns None if the timer overflowed or the direction changed
+    /// during the measurement, the errors are cleared.
+    pub fn low_speed(&self) -> Option<u32> {
+        let status = &self.memory.QEPSTS;
+        if status.is_set(QEPSTS::COEF) || status.is_set(QEPSTS::CDEF) {
+            status.write(QEPSTS::COEF::SET + QEPSTS::CDEF::SET);
+            return None;
+        }
+        // With the unit timer the period is latched at its timeout
+        let period = if self.memory.QEPCTL.is_set(QEPCTL::UTE) {
+            self.memory.QCPRDLAT.get()
+        } else {
+            self.memory.QCPRD.get()
+        } as u64;
+        if period == 0 {
+            return None;
+        }
+        let capture = self.memory.QCAPCTL.extract();
+        let events = 1u64 << capture.read(QCAPCTL::UPPS);
+        let clock = (SYSCLK_HZ >> capture.read(QCAPCTL::CCPS)) as u64;
+        Some((events * clock / period) as u32)
+    }
+    /// Pulse the strobe pin when the position matches, None disables it
+    pub fn set_compare(&self, compare: Option<&CompareOutput>) {
+        match compare {
+            Some(compare) => {
+                self.memory.QPOSCTL.write(QPOSCTL::PCE::CLEAR);
+                self.memory.QPOSCMP.set(compare.position);
+                let polarity = if compare.active_high {
+                    QPOSCTL::PCPOL::ActiveHigh
+                } else {
+                    QPOSCTL::PCPOL::ActiveLow
+                };
+                self.memory.QPOSCTL.write(
+                    QPOSCTL::PCSPW.val(compare.pulse_width.clamp(1, 4096) - 1)
+                        + polarity
+                        + QPOSCTL::PCE::SET,
+                );
+                self.memory.QDECCTL.modify(QDECCTL::SOEN::SET);
+            }
+            None => {
+                self.memory.QPOSCTL.write(QPOSCTL::PCE::CLEAR);
+                self.memory.QDECCTL.modify(QDECCTL::SOEN::CLEAR);
+            }
+        }
+    }
+    pub fn enable_interrupt(&self, interrupt: Interrupt) {
+        let reg = &self.memory.QEINT;
+        reg.set(reg.get() | interrupt.mask());
+    }
+    pub fn disable_interrupt(&self, interrupt: Interrupt) {
+        let reg = &self.memory.QEINT;
+        reg.set(reg.get() & !interrupt.mask());
+    }
+    pub fn is_pending(&self, interrupt: Interrupt) -> bool {
+        self.memory.QFLG.get() & interrupt.mask() != 0
+    }
+    /// Clear the flag and the global interrupt, so the next one is raised
+    pub fn clear_interrupt(&self, interrupt: Interrupt) {
+        self.memory.QCLR.set(interrupt.mask() | 1);
+    }
+    pub fn force_interrupt(&self, interrupt: Interrupt) {
+        self.memory.QFRC.set(interrupt.mask());
+    }
+}
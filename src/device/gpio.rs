@@ -3,7 +3,10 @@
 // Author: Moritz Doll
 // License: MIT
 
+use crate::critical_section;
+use crate::interrupt_source::InterruptSource;
 use armv7::VirtualAddress;
+use core::hint;
 use core::marker::PhantomData;
 use register::{mmio::*, register_bitfields};
 
@@ -69,6 +72,41 @@ struct RegisterBlock {
     SETDATAOUT: ReadWrite<u32, ()>,   // 0x194
 }
 
+/// Idle mode of the GPIO module
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleMode {
+    ForceIdle,
+    NoIdle,
+    SmartIdle,
+    /// Smart idle which may generate a wakeup event
+    SmartIdleWakeup,
+}
+
+/// Ratio between the interface clock and the internal gating clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GatingRatio {
+    Divide1,
+    Divide2,
+    Divide4,
+    Divide8,
+}
+
+/// The two interrupt lines of a GPIO bank
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IrqLine {
+    Line0,
+    Line1,
+}
+
+/// Edges on which an input pin raises an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    None,
+    Rising,
+    Falling,
+    Both,
+}
+
 #[derive(Debug)]
 pub struct Output;
 #[derive(Debug)]
@@ -109,8 +147,11 @@ impl Pin<Output> {
         self.memory.CLEARDATAOUT.set(self.bitmask());
     }
     pub fn switch(&self) {
-        let bits = self.memory.DATAOUT.get() ^ self.bitmask();
-        self.memory.DATAOUT.set(bits);
+        if self.read() {
+            self.clear();
+        } else {
+            self.set();
+        }
     }
 }
 
@@ -118,6 +159,154 @@ impl Pin<Input> {
     pub fn to_output(self) -> Pin<Output> {
         unimplemented! {}
     }
+    /// Select the edges on which the pin raises an interrupt or wakeup event
+    pub fn set_edge_detection(&self, edge: Edge) {
+        let (rising, falling) = match edge {
+            Edge::None => (false, false),
+            Edge::Rising => (true, false),
+            Edge::Falling => (false, true),
+            Edge::Both => (true, true),
+        };
+        let bitmask = self.bitmask();
+        // The registers are shared with the other pins of the bank
+        critical_section::free(|_| {
+            let bits = self.memory.RISINGDETECT.get() & !bitmask;
+            self.memory
+                .RISINGDETECT
+                .set(if rising { bits | bitmask } else { bits });
+            let bits = self.memory.FALLINGDETECT.get() & !bitmask;
+            self.memory
+                .FALLINGDETECT
+                .set(if falling { bits | bitmask } else { bits });
+        });
+    }
+}
+
+/// A group of pins of one bank that is accessed as a single port
+///
+/// The n-th pin of the group corresponds to the n-th bit of the values that
+/// are written and read. If the pins are contiguous and ascending, the value
+/// is simply shifted, otherwise every bit is moved to its position.
+pub struct PinGroup<T> {
+    pins: [u8; 32],
+    len: u8,
+    mask: u32,
+    shift: Option<u8>,
+    memory: &'static RegisterBlock,
+    gpio_type: PhantomData<T>,
+}
+
+impl<T> PinGroup<T> {
+    fn new(numbers: &[u8], memory: &'static RegisterBlock) -> Option<Self> {
+        if numbers.is_empty() || numbers.len() > 32 {
+            return None;
+        }
+        let mut pins = [0; 32];
+        let mut mask = 0;
+        let mut contiguous = true;
+        for (i, &number) in numbers.iter().enumerate() {
+            if number > 31 {
+                return None;
+            }
+            let bit = 1 << (number as u32);
+            // Every pin may only appear once
+            if mask & bit != 0 {
+                return None;
+            }
+            mask |= bit;
+            pins[i] = number;
+            if number as usize != numbers[0] as usize + i {
+                contiguous = false;
+            }
+        }
+        let shift = if contiguous { Some(numbers[0]) } else { None };
+        Some(PinGroup {
+            pins,
+            len: numbers.len() as u8,
+            mask,
+            shift,
+            memory,
+            gpio_type: PhantomData,
+        })
+    }
+    /// Returns the mask of all pins in the group
+    #[inline]
+    pub fn bitmask(&self) -> u32 {
+        self.mask
+    }
+    #[inline]
+    fn len(&self) -> usize {
+        self.len as usize
+    }
+    /// Move the bits of a value to the pin positions
+    fn pack(&self, value: u32) -> u32 {
+        if let Some(shift) = self.shift {
+            return (value << shift as u32) & self.mask;
+        }
+        let mut bits = 0;
+        for (i, &number) in self.pins[..self.len()].iter().enumerate() {
+            if value & (1 << i as u32) != 0 {
+                bits |= 1 << (number as u32);
+            }
+        }
+        bits
+    }
+    /// Collect the bits at the pin positions into a value
+    fn unpack(&self, bits: u32) -> u32 {
+        if let Some(shift) = self.shift {
+            return (bits & self.mask) >> shift as u32;
+        }
+        let mut value = 0;
+        for (i, &number) in self.pins[..self.len()].iter().enumerate() {
+            if bits & (1 << (number as u32)) != 0 {
+                value |= 1 << i as u32;
+            }
+        }
+        value
+    }
+}
+
+impl PinGroup<Output> {
+    /// Read back the value that is currently driven on the pins
+    pub fn read(&self) -> u32 {
+        self.unpack(self.memory.DATAOUT.get())
+    }
+    /// Write a value to the pins
+    ///
+    /// Uses one access to SETDATAOUT and one to CLEARDATAOUT, the other pins
+    /// of the bank are not touched.
+    pub fn write(&self, value: u32) {
+        let bits = self.pack(value);
+        self.memory.SETDATAOUT.set(bits);
+        self.memory.CLEARDATAOUT.set(self.mask & !bits);
+    }
+    /// Write a value to the pins with a single access to DATAOUT
+    ///
+    /// All pins change at the same time. The read-modify-write of DATAOUT is
+    /// done in a critical section, so that other pins of the bank that are
+    /// changed from an interrupt handler are not overwritten.
+    pub fn write_dataout(&self, value: u32) {
+        let bits = self.pack(value);
+        critical_section::free(|_| {
+            let current = self.memory.DATAOUT.get();
+            self.memory.DATAOUT.set((current & !self.mask) | bits);
+        });
+    }
+    /// Set all pins of the group
+    pub fn set_all(&self) {
+        self.memory.SETDATAOUT.set(self.mask);
+    }
+    /// Clear all pins of the group
+    pub fn clear_all(&self) {
+        self.memory.CLEARDATAOUT.set(self.mask);
+    }
+}
+
+impl PinGroup<Input> {
+    /// Read the value on the pins with a single access to DATAIN
+    pub fn read(&self) -> u32 {
+        self.unpack(self.memory.DATAIN.get())
+    }
 }
 
 pub struct Gpio {
@@ -125,21 +314,127 @@ pub struct Gpio {
     owned: u32,
 }
 
+// The pins of a bank share DATAOUT and the detect registers. DATAOUT is changed
+// through SETDATAOUT and CLEARDATAOUT, the other shared read-modify-writes are
+// done in a critical section. OE and IRQWAKEN are only changed by the Gpio.
+unsafe impl Send for Gpio {}
+unsafe impl<T> Send for Pin<T> {}
+unsafe impl<T> Send for PinGroup<T> {}
+
 impl Gpio {
     pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
         let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
         Gpio { memory, owned: 0 }
     }
+    /// Reset the module and wait until the reset is done
+    ///
+    /// # Safety
+    /// All pins that were handed out lose their configuration
+    pub unsafe fn reset(&self) {
+        self.memory.SYSCONFIG.write(SYSCONFIG::SOFTRESET::SoftReset);
+        loop {
+            if self.memory.SYSSTATUS.is_set(SYSSTATUS::RESETDONE) {
+                break;
+            }
+            hint::spin_loop();
+        }
+    }
+    /// Set the idle mode of the module
+    pub fn set_idle_mode(&self, mode: IdleMode) {
+        let field = match mode {
+            IdleMode::ForceIdle => SYSCONFIG::IDLEMODE::ForceIdle,
+            IdleMode::NoIdle => SYSCONFIG::IDLEMODE::NoIdle,
+            IdleMode::SmartIdle => SYSCONFIG::IDLEMODE::SmartIdle,
+            IdleMode::SmartIdleWakeup => SYSCONFIG::IDLEMODE::SmartIdleWakeup,
+        };
+        self.memory.SYSCONFIG.modify(field);
+    }
+    /// Let the interface clock be gated automatically
+    pub fn set_autoidle(&self, enable: bool) {
+        let field = if enable {
+            SYSCONFIG::AUTOIDLE::AutoIdle
+        } else {
+            SYSCONFIG::AUTOIDLE::FreeRunning
+        };
+        self.memory.SYSCONFIG.modify(field);
+    }
+    /// Allow the module to generate wakeup requests
+    pub fn enable_wakeup(&self) {
+        self.memory.SYSCONFIG.modify(SYSCONFIG::WAKEUP::Enable);
+    }
+    /// Prevent the module from generating wakeup requests
+    pub fn disable_wakeup(&self) {
+        self.memory.SYSCONFIG.modify(SYSCONFIG::WAKEUP::Disable);
+    }
+    /// Gate all internal clocks of the module
+    pub fn disable_module(&self) {
+        self.memory.CTRL.modify(CTRL::DISABLEMODULE.val(1));
+    }
+    /// Ungate the internal clocks of the module
+    pub fn enable_module(&self) {
+        self.memory.CTRL.modify(CTRL::DISABLEMODULE.val(0));
+    }
+    /// Set the ratio of the internal gating clock used for debouncing and
+    /// event detection
+    pub fn set_gating_ratio(&self, ratio: GatingRatio) {
+        let val = match ratio {
+            GatingRatio::Divide1 => 0,
+            GatingRatio::Divide2 => 1,
+            GatingRatio::Divide4 => 2,
+            GatingRatio::Divide8 => 3,
+        };
+        self.memory.CTRL.modify(CTRL::GATINGRATIO.val(val));
+    }
+    fn irqwaken(&self, line: IrqLine) -> &ReadWrite<u32, ()> {
+        match line {
+            IrqLine::Line0 => &self.memory.IRQWAKEN_0,
+            IrqLine::Line1 => &self.memory.IRQWAKEN_1,
+        }
+    }
+    /// Let an edge on the pin wake up the system through the given line,
+    /// returns None if there is no such pin
+    pub fn enable_pin_wakeup(&self, number: u8, line: IrqLine) -> Option<()> {
+        if number > 31 {
+            return None;
+        }
+        let reg = self.irqwaken(line);
+        reg.set(reg.get() | (1 << (number as u32)));
+        Some(())
+    }
+    /// Stop the pin from waking up the system through the given line, returns
+    /// None if there is no such pin
+    pub fn disable_pin_wakeup(&self, number: u8, line: IrqLine) -> Option<()> {
+        if number > 31 {
+            return None;
+        }
+        let reg = self.irqwaken(line);
+        reg.set(reg.get() & !(1 << (number as u32)));
+        Some(())
+    }
+    /// Returns the mask of all pins that may wake up the system through the line
+    pub fn pin_wakeup_mask(&self, line: IrqLine) -> u32 {
+        self.irqwaken(line).get()
+    }
+    /// Mark the pins as owned if none of them was already given to someone
+    fn claim(&mut self, mask: u32) -> bool {
+        if self.owned & mask != 0 {
+            return false;
+        }
+        self.owned |= mask;
+        true
+    }
+    /// Returns whether the pin was already given to someone
+    pub fn is_owned(&self, number: u8) -> bool {
+        number < 32 && self.owned & (1 << (number as u32)) != 0
+    }
     pub fn get_pin_as_input(&mut self, number: u8) -> Option<Pin<Input>> {
         if number > 31 {
             return None;
         }
         let bit = 1 << (number as u32);
-        // Check whether the pin was already given to someone
-        if self.owned & bit == 1 {
+        if !self.claim(bit) {
             return None;
         }
-        self.owned |= bit;
         let bitset = self.memory.OE.get();
         self.memory.OE.set(bitset | bit);
         Some(Pin::new(number, self.memory))
@@ -149,13 +444,120 @@ impl Gpio {
             return None;
         }
         let bit = 1 << (number as u32);
-        // Check whether the pin was already given to someone
-        if self.owned & bit == 1 {
+        if !self.claim(bit) {
             return None;
         }
-        self.owned |= bit;
         let bitset = self.memory.OE.get();
         self.memory.OE.set(bitset & !bit);
         Some(Pin::new(number, self.memory))
     }
+    /// Get a group of pins as inputs
+    ///
+    /// The order of `numbers` gives the bit order of the values read.
+    pub fn get_group_as_input(&mut self, numbers: &[u8]) -> Option<PinGroup<Input>> {
+        let group = PinGroup::new(numbers, self.memory)?;
+        let mask = group.bitmask();
+        if !self.claim(mask) {
+            return None;
+        }
+        let bitset = self.memory.OE.get();
+        self.memory.OE.set(bitset | mask);
+        Some(group)
+    }
+    /// Get a group of pins as outputs
+    ///
+    /// The order of `numbers` gives the bit order of the values written.
+    pub fn get_group_as_output(&mut self, numbers: &[u8]) -> Option<PinGroup<Output>> {
+        let group = PinGroup::new(numbers, self.memory)?;
+        let mask = group.bitmask();
+        if !self.claim(mask) {
+            return None;
+        }
+        let bitset = self.memory.OE.get();
+        self.memory.OE.set(bitset & !mask);
+        Some(group)
+    }
+}
+
+/// Number of GPIO banks
+pub const BANK_NUM: usize = 4;
+/// Number of GPIO pins over all banks
+pub const PIN_NUM: u8 = 32 * BANK_NUM as u8;
+
+/// Interrupts of line 0 and line 1 of GPIO0 to GPIO3
+pub const INTERRUPTS: [[InterruptSource; 2]; BANK_NUM] = [
+    [InterruptSource::GpioInt0A, InterruptSource::GpioInt0B],
+    [InterruptSource::GpioInt1A, InterruptSource::GpioInt1B],
+    [InterruptSource::GpioInt2A, InterruptSource::GpioInt2B],
+    [InterruptSource::GpioInt3A, InterruptSource::GpioInt3B],
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GpioError {
+    /// The pin or bank number does not exist
+    OutOfRange,
+    /// The pin was already given to someone
+    AlreadyTaken,
+}
+
+/// All four GPIO banks of the SoC
+///
+/// Pins are addressed by their global number `bank * 32 + bit`, e.g. GPIO1_28
+/// has the number 60. Every pin is handed out at most once.
+pub struct GpioController {
+    banks: [Gpio; BANK_NUM],
+}
+
+impl GpioController {
+    /// Creates the controller for GPIO0 to GPIO3
+    ///
+    /// # Safety
+    /// The virtual addresses have to point to the banks in ascending order and
+    /// no other `Gpio` for these banks may exist
+    pub unsafe fn new(bank_addrs: [VirtualAddress; BANK_NUM]) -> Self {
+        GpioController {
+            banks: [
+                Gpio::new(bank_addrs[0]),
+                Gpio::new(bank_addrs[1]),
+                Gpio::new(bank_addrs[2]),
+                Gpio::new(bank_addrs[3]),
+            ],
+        }
+    }
+    /// Split a global pin number into the bank and the pin within the bank
+    fn split(number: u8) -> Result<(usize, u8), GpioError> {
+        if number >= PIN_NUM {
+            return Err(GpioError::OutOfRange);
+        }
+        Ok(((number / 32) as usize, number % 32))
+    }
+    /// Returns the interrupt raised by a pin on the given line of its bank
+    pub fn interrupt(&self, number: u8, line: IrqLine) -> Result<InterruptSource, GpioError> {
+        let (bank, _) = Self::split(number)?;
+        Ok(match line {
+            IrqLine::Line0 => INTERRUPTS[bank][0],
+            IrqLine::Line1 => INTERRUPTS[bank][1],
+        })
+    }
+    /// Access a single bank, e.g. for pin groups or power management
+    pub fn bank(&mut self, bank: usize) -> Result<&mut Gpio, GpioError> {
+        self.banks.get_mut(bank).ok_or(GpioError::OutOfRange)
+    }
+    /// Returns whether the pin was already given to someone
+    pub fn is_owned(&self, number: u8) -> Result<bool, GpioError> {
+        let (bank, pin) = Self::split(number)?;
+        Ok(self.banks[bank].is_owned(pin))
+    }
+    pub fn get_pin_as_input(&mut self, number: u8) -> Result<Pin<Input>, GpioError> {
+        let (bank, pin) = Self::split(number)?;
+        self.banks[bank]
+            .get_pin_as_input(pin)
+            .ok_or(GpioError::AlreadyTaken)
+    }
+    pub fn get_pin_as_output(&mut self, number: u8) -> Result<Pin<Output>, GpioError> {
+        let (bank, pin) = Self::split(number)?;
+        self.banks[bank]
+            .get_pin_as_output(pin)
+            .ok_or(GpioError::AlreadyTaken)
+    }
 }
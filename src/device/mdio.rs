@@ -0,0 +1,207 @@
+//! The MDIO of the Ethernet switch
+//!
+//! The MDIO polls the status register of all PHYs on the bus and tracks which
+//! of them respond and have a link. Two user channels do clause 22 register
+//! accesses and can each monitor one PHY for link changes. The link interrupt
+//! is part of the misc interrupt of the switch.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::phy::MiiBus;
+use armv7::VirtualAddress;
+use core::hint;
+use register::{mmio::*, register_bitfields};
+
+register_bitfields! {
+    u32,
+    CONTROL [
+        CLKDIV OFFSET(0) NUMBITS(16) [],
+        FAULTENB OFFSET(18) NUMBITS(1) [],
+        FAULT OFFSET(19) NUMBITS(1) [],
+        PREAMBLE OFFSET(20) NUMBITS(1) [],
+        ENABLE OFFSET(30) NUMBITS(1) [],
+        IDLE OFFSET(31) NUMBITS(1) []
+    ],
+    USERACCESS [
+        DATA OFFSET(0) NUMBITS(16) [],
+        PHYADR OFFSET(16) NUMBITS(5) [],
+        REGADR OFFSET(21) NUMBITS(5) [],
+        ACK OFFSET(29) NUMBITS(1) [],
+        WRITE OFFSET(30) NUMBITS(1) [],
+        GO OFFSET(31) NUMBITS(1) []
+    ],
+    USERPHYSEL [
+        PHYADRMON OFFSET(0) NUMBITS(5) [],
+        LINKINTENB OFFSET(6) NUMBITS(1) [],
+        LINKSEL OFFSET(7) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct UserRegisterBlock {
+    USERACCESS: ReadWrite<u32, USERACCESS::Register>, // 0x00
+    USERPHYSEL: ReadWrite<u32, USERPHYSEL::Register>, // 0x04
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    _VER: ReadOnly<u32, ()>,                    // 0x00
+    CONTROL: ReadWrite<u32, CONTROL::Register>, // 0x04
+    ALIVE: ReadWrite<u32, ()>,                  // 0x08
+    LINK: ReadOnly<u32, ()>,                    // 0x0C
+    _LINKINTRAW: ReadWrite<u32, ()>,            // 0x10
+    LINKINTMASKED: ReadWrite<u32, ()>,          // 0x14
+    __reserved_0: [u32; 2],
+    _USERINTRAW: ReadWrite<u32, ()>,       // 0x20
+    _USERINTMASKED: ReadWrite<u32, ()>,    // 0x24
+    _USERINTMASKSET: ReadWrite<u32, ()>,   // 0x28
+    _USERINTMASKCLEAR: ReadWrite<u32, ()>, // 0x2C
+    __reserved_1: [u32; 20],
+    USER: [UserRegisterBlock; 2], // 0x80
+}
+
+/// Functional clock of the MDIO
+pub const CLOCK_HZ: u32 = 125_000_000;
+/// Highest frequency of the bus
+pub const MAX_BUS_HZ: u32 = 2_500_000;
+/// Number of addresses on the bus
+pub const PHY_NUM: u8 = 32;
+/// Number of registers of a PHY
+pub const REGISTER_NUM: u8 = 32;
+/// Number of user channels
+pub const CHANNEL_NUM: usize = 2;
+
+pub struct Mdio {
+    memory: &'static RegisterBlock,
+}
+
+// The MDIO module is not part of the registers the Cpsw maps, all bus accesses
+// go through the Mdio
+unsafe impl Send for Mdio {}
+
+impl Mdio {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Mdio { memory }
+    }
+    /// Enable the state machine with a bus clock of at most `bus_hz`
+    ///
+    /// The bus is limited to 2.5 MHz.
+    pub fn init(&self, bus_hz: u32) {
+        let bus_hz = bus_hz.clamp(1, MAX_BUS_HZ);
+        // The bus runs at CLOCK_HZ / (div + 1)
+        let div = ((CLOCK_HZ - 1) / bus_hz).min(0xffff);
+        self.memory
+            .CONTROL
+            .write(CONTROL::ENABLE::SET + CONTROL::FAULTENB::SET + CONTROL::CLKDIV.val(div));
+    }
+    pub fn disable(&self) {
+        self.memory.CONTROL.modify(CONTROL::ENABLE::CLEAR);
+    }
+    /// Returns whether the state machine is idle
+    pub fn is_idle(&self) -> bool {
+        self.memory.CONTROL.is_set(CONTROL::IDLE)
+    }
+    /// Returns a bit mask of the PHYs that responded to the last access
+    pub fn alive(&self) -> u32 {
+        self.memory.ALIVE.get()
+    }
+    /// Returns a bit mask of the PHYs that report a link
+    pub fn link(&self) -> u32 {
+        self.memory.LINK.get()
+    }
+    /// Returns the lowest address of a PHY that responded, if any
+    ///
+    /// The state machine needs some time after `init` to poll all addresses.
+    pub fn scan(&self) -> Option<u8> {
+        let alive = self.alive();
+        if alive == 0 {
+            None
+        } else {
+            Some(alive.trailing_zeros() as u8)
+        }
+    }
+    fn wait(&self, channel: usize) {
+        while self.memory.USER[channel].USERACCESS.is_set(USERACCESS::GO) {
+            hint::spin_loop();
+        }
+    }
+    /// Read a register, None if the address is invalid or the PHY did not
+    /// acknowledge
+    pub fn read(&self, phy: u8, reg: u8) -> Option<u16> {
+        if phy >= PHY_NUM || reg >= REGISTER_NUM {
+            return None;
+        }
+        let access = &self.memory.USER[0].USERACCESS;
+        self.wait(0);
+        access.write(
+            USERACCESS::GO::SET
+                + USERACCESS::REGADR.val(reg as u32)
+                + USERACCESS::PHYADR.val(phy as u32),
+        );
+        self.wait(0);
+        if !access.is_set(USERACCESS::ACK) {
+            return None;
+        }
+        Some(access.read(USERACCESS::DATA) as u16)
+    }
+    /// Write a register, None if the address is invalid
+    pub fn write(&self, phy: u8, reg: u8, value: u16) -> Option<()> {
+        if phy >= PHY_NUM || reg >= REGISTER_NUM {
+            return None;
+        }
+        self.wait(0);
+        self.memory.USER[0].USERACCESS.write(
+            USERACCESS::GO::SET
+                + USERACCESS::WRITE::SET
+                + USERACCESS::REGADR.val(reg as u32)
+                + USERACCESS::PHYADR.val(phy as u32)
+                + USERACCESS::DATA.val(value as u32),
+        );
+        self.wait(0);
+        Some(())
+    }
+    /// Monitor the link of a PHY with a channel and raise the link interrupt
+    /// on changes
+    ///
+    /// Returns None if the channel or the address is invalid.
+    pub fn enable_link_interrupt(&self, channel: usize, phy: u8) -> Option<()> {
+        if channel >= CHANNEL_NUM || phy >= PHY_NUM {
+            return None;
+        }
+        self.memory.USER[channel].USERPHYSEL.write(
+            USERPHYSEL::LINKINTENB::SET
+                + USERPHYSEL::LINKSEL::CLEAR
+                + USERPHYSEL::PHYADRMON.val(phy as u32),
+        );
+        Some(())
+    }
+    pub fn disable_link_interrupt(&self, channel: usize) {
+        if channel < CHANNEL_NUM {
+            self.memory.USER[channel]
+                .USERPHYSEL
+                .modify(USERPHYSEL::LINKINTENB::CLEAR);
+        }
+    }
+    /// Returns whether the link of the PHY monitored by the channel changed
+    pub fn is_link_changed(&self, channel: usize) -> bool {
+        channel < CHANNEL_NUM && self.memory.LINKINTMASKED.get() & (1 << channel) != 0
+    }
+    pub fn clear_link_interrupt(&self, channel: usize) {
+        if channel < CHANNEL_NUM {
+            self.memory.LINKINTMASKED.set(1 << channel);
+        }
+    }
+}
+
+impl MiiBus for Mdio {
+    fn read(&self, phy: u8, reg: u8) -> Option<u16> {
+        Mdio::read(self, phy, reg)
+    }
+    fn write(&self, phy: u8, reg: u8, value: u16) -> Option<()> {
+        Mdio::write(self, phy, reg, value)
+    }
+}
@@ -0,0 +1,846 @@
+//! The MMC/SD host controllers (MMCHS)
+//!
+//! The driver identifies SD cards of version 1 and 2, including SDHC and
+//! SDXC, and eMMC, and reads and writes blocks either by polling the data
+//! register or with the EDMA.
+//!
+//! The functional clock of 96 MHz has to be enabled in the PRCM and the pins
+//! muxed. Boards that wire the card detect switch to a GPIO, like the
+//! BeagleBone, have to use the GPIO instead of the card detect of the
+//! controller.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::block::{BlockDevice, BLOCK_SIZE};
+use crate::device::dma::{DmaMemory, ReadBuffer, Transfer, WriteBuffer};
+use crate::device::edma::{Channel, Edma};
+use crate::interrupt_source::InterruptSource;
+use armv7::{PhysicalAddress, VirtualAddress};
+use core::hint;
+use register::{mmio::*, register_bitfields, Field, FieldValue, LocalRegisterCopy};
+
+register_bitfields! {
+    u32,
+    SYSCONFIG [
+        AUTOIDLE OFFSET(0) NUMBITS(1) [],
+        SOFTRESET OFFSET(1) NUMBITS(1) []
+    ],
+    SYSSTATUS [
+        RESETDONE OFFSET(0) NUMBITS(1) []
+    ],
+    CON [
+        OD OFFSET(0) NUMBITS(1) [],
+        INIT OFFSET(1) NUMBITS(1) [],
+        DW8 OFFSET(5) NUMBITS(1) [],
+        DMA_MNS OFFSET(20) NUMBITS(1) []
+    ],
+    BLK [
+        BLEN OFFSET(0) NUMBITS(12) [],
+        NBLK OFFSET(16) NUMBITS(16) []
+    ],
+    CMD [
+        DE OFFSET(0) NUMBITS(1) [],
+        BCE OFFSET(1) NUMBITS(1) [],
+        ACEN OFFSET(2) NUMBITS(2) [Disabled = 0, Cmd12 = 1],
+        DDIR OFFSET(4) NUMBITS(1) [Write = 0, Read = 1],
+        MSBS OFFSET(5) NUMBITS(1) [],
+        RSP_TYPE OFFSET(16) NUMBITS(2) [None = 0, Long = 1, Short = 2, ShortBusy = 3],
+        CCCE OFFSET(19) NUMBITS(1) [],
+        CICE OFFSET(20) NUMBITS(1) [],
+        DP OFFSET(21) NUMBITS(1) [],
+        INDX OFFSET(24) NUMBITS(6) []
+    ],
+    PSTATE [
+        CMDI OFFSET(0) NUMBITS(1) [],
+        DATI OFFSET(1) NUMBITS(1) [],
+        CINS OFFSET(16) NUMBITS(1) [],
+        WP OFFSET(19) NUMBITS(1) []
+    ],
+    HCTL [
+        DTW OFFSET(1) NUMBITS(1) [],
+        HSPE OFFSET(2) NUMBITS(1) [],
+        SDBP OFFSET(8) NUMBITS(1) [],
+        SDVS OFFSET(9) NUMBITS(3) [V18 = 5, V30 = 6, V33 = 7]
+    ],
+    SYSCTL [
+        ICE OFFSET(0) NUMBITS(1) [],
+        ICS OFFSET(1) NUMBITS(1) [],
+        CEN OFFSET(2) NUMBITS(1) [],
+        CLKD OFFSET(6) NUMBITS(10) [],
+        DTO OFFSET(16) NUMBITS(4) [],
+        SRA OFFSET(24) NUMBITS(1) [],
+        SRC OFFSET(25) NUMBITS(1) [],
+        SRD OFFSET(26) NUMBITS(1) []
+    ],
+    STAT [
+        CC OFFSET(0) NUMBITS(1) [],
+        TC OFFSET(1) NUMBITS(1) [],
+        BWR OFFSET(4) NUMBITS(1) [],
+        BRR OFFSET(5) NUMBITS(1) [],
+        CINS OFFSET(6) NUMBITS(1) [],
+        CREM OFFSET(7) NUMBITS(1) [],
+        ERRI OFFSET(15) NUMBITS(1) [],
+        CTO OFFSET(16) NUMBITS(1) [],
+        CCRC OFFSET(17) NUMBITS(1) [],
+        CEB OFFSET(18) NUMBITS(1) [],
+        CIE OFFSET(19) NUMBITS(1) [],
+        DTO OFFSET(20) NUMBITS(1) [],
+        DCRC OFFSET(21) NUMBITS(1) [],
+        DEB OFFSET(22) NUMBITS(1) [],
+        ACE OFFSET(24) NUMBITS(1) [],
+        CERR OFFSET(28) NUMBITS(1) [],
+        BADA OFFSET(29) NUMBITS(1) []
+    ],
+    CAPA [
+        VS33 OFFSET(24) NUMBITS(1) [],
+        VS30 OFFSET(25) NUMBITS(1) [],
+        VS18 OFFSET(26) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    __reserved_0: [u32; 68],
+    SYSCONFIG: ReadWrite<u32, SYSCONFIG::Register>, // 0x110
+    SYSSTATUS: ReadOnly<u32, SYSSTATUS::Register>,  // 0x114
+    __reserved_1: [u32; 3],
+    _CSRE: ReadWrite<u32, ()>,          // 0x124
+    _SYSTEST: ReadWrite<u32, ()>,       // 0x128
+    CON: ReadWrite<u32, CON::Register>, // 0x12C
+    _PWCNT: ReadWrite<u32, ()>,         // 0x130
+    __reserved_2: [u32; 51],
+    _SDMASA: ReadWrite<u32, ()>,              // 0x200
+    BLK: ReadWrite<u32, BLK::Register>,       // 0x204
+    ARG: ReadWrite<u32, ()>,                  // 0x208
+    CMD: ReadWrite<u32, CMD::Register>,       // 0x20C
+    RSP: [ReadOnly<u32, ()>; 4],              // 0x210
+    DATA: ReadWrite<u32, ()>,                 // 0x220
+    PSTATE: ReadOnly<u32, PSTATE::Register>,  // 0x224
+    HCTL: ReadWrite<u32, HCTL::Register>,     // 0x228
+    SYSCTL: ReadWrite<u32, SYSCTL::Register>, // 0x22C
+    STAT: ReadWrite<u32, STAT::Register>,     // 0x230
+    IE: ReadWrite<u32, STAT::Register>,       // 0x234
+    ISE: ReadWrite<u32, STAT::Register>,      // 0x238
+    _AC12: ReadOnly<u32, ()>,                 // 0x23C
+    CAPA: ReadWrite<u32, CAPA::Register>,     // 0x240
+}
+
+/// Interrupts of MMC0 to MMC2
+pub const INTERRUPTS: [InterruptSource; 3] = [
+    InterruptSource::Mmc0,
+    InterruptSource::Mmc1,
+    InterruptSource::Mmc2,
+];
+
+/// DMA events (transmit, receive) of MMC0 and MMC1
+///
+/// The events of MMC2 are only reachable through the event crossbar.
+pub const DMA_EVENTS: [(u8, u8); 2] = [(24, 25), (2, 3)];
+
+/// Functional clock of the controllers
+pub const CLOCK_HZ: u32 = 96_000_000;
+/// Clock during the identification
+pub const IDENTIFICATION_HZ: u32 = 400_000;
+/// Clock of the default speed mode of SD cards and eMMC
+pub const DEFAULT_SPEED_HZ: u32 = 25_000_000;
+/// Largest number of blocks of a single command
+pub const MAX_BLOCKS: usize = 0xffff;
+
+/// Number of ACMD41 or CMD1 until the card has to be ready, about a second
+const OCR_RETRIES: u32 = 5000;
+/// Voltage window 2.7 to 3.6 V of the OCR
+const OCR_VOLTAGE: u32 = 0x00ff_8000;
+const OCR_BUSY: u32 = 1 << 31;
+const OCR_HCS: u32 = 1 << 30;
+/// Sector addressing of eMMC in the OCR
+const OCR_SECTOR_MODE: u32 = 0b10 << 29;
+/// Pattern and 2.7 to 3.6 V of SEND_IF_COND
+const IF_COND: u32 = 0x1aa;
+/// Error bits of the card status
+const STATUS_ERRORS: u32 = 0xfdf9_8008;
+/// The card expects an application specific command
+const STATUS_APP_CMD: u32 = 1 << 5;
+/// The relative card address assigned to eMMC
+const MMC_RCA: u16 = 1;
+/// Index of the bus width in the extended CSD of eMMC
+const EXT_CSD_BUS_WIDTH: u32 = 183;
+/// Offset of the data register from the base of the controller
+const DATA_OFFSET: u32 = 0x220;
+/// Index of the sector count in the extended CSD of eMMC
+const EXT_CSD_SEC_COUNT: usize = 212;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MmcError {
+    /// No card was identified
+    NoCard,
+    /// The card did not answer in time
+    Timeout,
+    /// Checksum error of a response or data
+    Crc,
+    /// Missing end bit of a response or data
+    EndBit,
+    /// The index of the response does not match the command
+    Index,
+    /// The automatic stop command failed
+    AutoStop,
+    /// Any other error of the controller, the raw status
+    Controller(u32),
+    /// The card reported an error, the card status
+    Card(u32),
+    /// The card or the mode is not supported
+    Unsupported,
+    /// The blocks are outside the card or the buffer is no multiple of blocks
+    OutOfRange,
+}
+
+impl MmcError {
+    fn from_status(stat: LocalRegisterCopy<u32, STAT::Register>) -> Self {
+        if stat.is_set(STAT::CTO) || stat.is_set(STAT::DTO) {
+            MmcError::Timeout
+        } else if stat.is_set(STAT::CCRC) || stat.is_set(STAT::DCRC) {
+            MmcError::Crc
+        } else if stat.is_set(STAT::CEB) || stat.is_set(STAT::DEB) {
+            MmcError::EndBit
+        } else if stat.is_set(STAT::CIE) {
+            MmcError::Index
+        } else if stat.is_set(STAT::ACE) {
+            MmcError::AutoStop
+        } else {
+            MmcError::Controller(stat.get())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CardType {
+    /// SD card of version 1, byte addressed
+    SdV1,
+    /// SD card of version 2 with standard capacity, byte addressed
+    SdV2,
+    /// SDHC or SDXC card, block addressed
+    SdHc,
+    /// MMC or eMMC up to 2 GB, byte addressed
+    Mmc,
+    /// eMMC above 2 GB, block addressed
+    MmcHc,
+}
+
+impl CardType {
+    pub fn is_sd(self) -> bool {
+        match self {
+            CardType::SdV1 | CardType::SdV2 | CardType::SdHc => true,
+            CardType::Mmc | CardType::MmcHc => false,
+        }
+    }
+    /// Returns whether the card is addressed in blocks instead of bytes
+    pub fn is_block_addressed(self) -> bool {
+        self == CardType::SdHc || self == CardType::MmcHc
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusWidth {
+    One,
+    Four,
+    /// Only eMMC
+    Eight,
+}
+
+/// An identified card
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Card {
+    pub card_type: CardType,
+    /// Relative card address
+    pub rca: u16,
+    /// Card identification register
+    pub cid: u128,
+    /// Card specific data register
+    pub csd: u128,
+    /// Number of blocks
+    pub blocks: u64,
+}
+
+impl Card {
+    /// Returns the manufacturer ID of the CID
+    pub fn manufacturer(&self) -> u8 {
+        bits(self.cid, 127, 120) as u8
+    }
+    /// Returns the serial number of the CID
+    pub fn serial(&self) -> u32 {
+        if self.card_type.is_sd() {
+            bits(self.cid, 55, 24)
+        } else {
+            bits(self.cid, 47, 16)
+        }
+    }
+    /// Returns the capacity in bytes
+    pub fn capacity(&self) -> u64 {
+        self.blocks * BLOCK_SIZE as u64
+    }
+}
+
+/// Returns the bits `high` down to `low` of a register of the card
+fn bits(value: u128, high: u32, low: u32) -> u32 {
+    ((value >> low) & ((1 << (high - low + 1)) - 1)) as u32
+}
+
+/// Returns the number of blocks of a card with CSD version 1
+fn csd_v1_blocks(csd: u128) -> u64 {
+    let c_size = u64::from(bits(csd, 73, 62));
+    let c_size_mult = bits(csd, 49, 47);
+    let read_bl_len = bits(csd, 83, 80);
+    let bytes = (c_size + 1) << (c_size_mult + 2 + read_bl_len);
+    bytes / BLOCK_SIZE as u64
+}
+
+/// Returns the number of blocks of an SD card with CSD version 2
+fn csd_v2_blocks(csd: u128) -> u64 {
+    (u64::from(bits(csd, 69, 48)) + 1) * 1024
+}
+
+/// The responses of the commands
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Response {
+    None,
+    /// 48 bits with card status
+    R1,
+    /// R1 and the card signals busy afterwards
+    R1b,
+    /// 136 bits with CID or CSD
+    R2,
+    /// 48 bits with OCR, without checksum
+    R3,
+    /// 48 bits with the relative card address of SD cards
+    R6,
+    /// 48 bits with the echo of SEND_IF_COND
+    R7,
+}
+
+impl Response {
+    fn field_value(self) -> FieldValue<u32, CMD::Register> {
+        match self {
+            Response::None => CMD::RSP_TYPE::None,
+            Response::R1 => CMD::RSP_TYPE::Short + CMD::CCCE::SET + CMD::CICE::SET,
+            Response::R1b => CMD::RSP_TYPE::ShortBusy + CMD::CCCE::SET + CMD::CICE::SET,
+            Response::R2 => CMD::RSP_TYPE::Long + CMD::CCCE::SET,
+            Response::R3 => CMD::RSP_TYPE::Short,
+            Response::R6 | Response::R7 => CMD::RSP_TYPE::Short + CMD::CCCE::SET + CMD::CICE::SET,
+        }
+    }
+}
+
+/// The data phase of a command
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Data {
+    read: bool,
+    blocks: usize,
+    dma: bool,
+}
+
+impl Data {
+    fn field_value(self) -> FieldValue<u32, CMD::Register> {
+        let direction = if self.read {
+            CMD::DDIR::Read
+        } else {
+            CMD::DDIR::Write
+        };
+        let blocks = if self.blocks > 1 {
+            CMD::MSBS::SET + CMD::BCE::SET + CMD::ACEN::Cmd12
+        } else {
+            CMD::ACEN::Disabled
+        };
+        let dma = if self.dma {
+            CMD::DE::SET
+        } else {
+            CMD::DE::CLEAR
+        };
+        CMD::DP::SET + direction + blocks + dma
+    }
+}
+
+pub struct Mmc {
+    memory: &'static RegisterBlock,
+    card: Option<Card>,
+}
+
+// The identified card is kept next to the registers and only changed together
+// with them by the Mmc
+unsafe impl Send for Mmc {}
+
+impl Mmc {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Mmc { memory, card: None }
+    }
+    /// Reset the controller, power the bus with 3.0 V and send the
+    /// initialization stream
+    pub fn init(&mut self) {
+        self.card = None;
+        self.memory.SYSCONFIG.write(SYSCONFIG::SOFTRESET::SET);
+        while !self.memory.SYSSTATUS.is_set(SYSSTATUS::RESETDONE) {
+            hint::spin_loop();
+        }
+        self.reset_line(SYSCTL::SRA);
+        self.memory.SYSCONFIG.write(SYSCONFIG::AUTOIDLE::SET);
+        self.memory.CAPA.modify(CAPA::VS30::SET + CAPA::VS18::SET);
+        self.memory.CON.set(0);
+        self.memory.HCTL.write(HCTL::SDVS::V30);
+        self.set_clock(IDENTIFICATION_HZ);
+        self.memory.HCTL.modify(HCTL::SDBP::SET);
+        while !self.memory.HCTL.is_set(HCTL::SDBP) {
+            hint::spin_loop();
+        }
+        // The status only shows the enabled events
+        self.memory.IE.write(
+            STAT::CC::SET
+                + STAT::TC::SET
+                + STAT::BWR::SET
+                + STAT::BRR::SET
+                + STAT::CINS::SET
+                + STAT::CREM::SET
+                + STAT::CTO::SET
+                + STAT::CCRC::SET
+                + STAT::CEB::SET
+                + STAT::CIE::SET
+                + STAT::DTO::SET
+                + STAT::DCRC::SET
+                + STAT::DEB::SET
+                + STAT::ACE::SET
+                + STAT::CERR::SET
+                + STAT::BADA::SET,
+        );
+        // 80 clock cycles before the first command
+        self.memory.CON.modify(CON::INIT::SET);
+        self.memory.CMD.set(0);
+        while !self.memory.STAT.is_set(STAT::CC) {
+            hint::spin_loop();
+        }
+        self.memory.CON.modify(CON::INIT::CLEAR);
+        self.memory.STAT.set(0xffff_ffff);
+    }
+    /// Set the bus clock to at most `hz`, returns the actual clock
+    pub fn set_clock(&self, hz: u32) -> u32 {
+        let div = ((CLOCK_HZ - 1) / hz.max(1) + 1).clamp(1, 1023);
+        self.memory.SYSCTL.modify(SYSCTL::CEN::CLEAR);
+        self.memory
+            .SYSCTL
+            .modify(SYSCTL::ICE::SET + SYSCTL::CLKD.val(div) + SYSCTL::DTO.val(0xe));
+        while !self.memory.SYSCTL.is_set(SYSCTL::ICS) {
+            hint::spin_loop();
+        }
+        self.memory.SYSCTL.modify(SYSCTL::CEN::SET);
+        CLOCK_HZ / div
+    }
+    fn reset_line(&self, line: Field<u32, SYSCTL::Register>) {
+        self.memory.SYSCTL.modify(line.val(1));
+        while self.memory.SYSCTL.is_set(line) {
+            hint::spin_loop();
+        }
+    }
+    /// Returns whether the controller sees a card
+    pub fn is_card_present(&self) -> bool {
+        self.memory.PSTATE.is_set(PSTATE::CINS)
+    }
+    pub fn is_write_protected(&self) -> bool {
+        self.memory.PSTATE.is_set(PSTATE::WP)
+    }
+    /// Returns the identified card
+    pub fn card(&self) -> Option<&Card> {
+        self.card.as_ref()
+    }
+    /// Wait for a status flag, resets the data line on errors
+    fn wait_status(&self, flag: Field<u32, STAT::Register>) -> Result<(), MmcError> {
+        loop {
+            let stat = self.memory.STAT.extract();
+            if stat.is_set(STAT::ERRI) {
+                self.memory.STAT.set(stat.get());
+                self.reset_line(SYSCTL::SRC);
+                self.reset_line(SYSCTL::SRD);
+                return Err(MmcError::from_status(stat));
+            }
+            if stat.is_set(flag) {
+                self.memory.STAT.write(flag.val(1));
+                return Ok(());
+            }
+            hint::spin_loop();
+        }
+    }
+    /// Send a command and return the first word of the response
+    fn command(
+        &self,
+        index: u8,
+        arg: u32,
+        response: Response,
+        data: Option<Data>,
+    ) -> Result<u32, MmcError> {
+        while self.memory.PSTATE.is_set(PSTATE::CMDI) {
+            hint::spin_loop();
+        }
+        if data.is_some() || response == Response::R1b {
+            while self.memory.PSTATE.is_set(PSTATE::DATI) {
+                hint::spin_loop();
+            }
+        }
+        self.memory.STAT.set(0xffff_ffff);
+        let data = match data {
+            Some(data) => {
+                self.memory
+                    .BLK
+                    .write(BLK::BLEN.val(BLOCK_SIZE as u32) + BLK::NBLK.val(data.blocks as u32));
+                data.field_value()
+            }
+            None => CMD::DP::CLEAR,
+        };
+        let cmd = CMD::INDX.val(index as u32) + response.field_value() + data;
+        self.memory.ARG.set(arg);
+        self.memory.CMD.write(cmd);
+        self.wait_status(STAT::CC)?;
+        if response == Response::R1b {
+            self.wait_status(STAT::TC)?;
+        }
+        let status = self.memory.RSP[0].get();
+        if (response == Response::R1 || response == Response::R1b) && status & STATUS_ERRORS != 0 {
+            return Err(MmcError::Card(status));
+        }
+        Ok(status)
+    }
+    fn long_response(&self) -> u128 {
+        self.memory
+            .RSP
+            .iter()
+            .rev()
+            .fold(0, |value, word| value << 32 | u128::from(word.get()))
+    }
+    /// Send an application specific command of SD cards
+    fn app_command(
+        &self,
+        rca: u16,
+        index: u8,
+        arg: u32,
+        response: Response,
+    ) -> Result<u32, MmcError> {
+        // The error bits may still be latched from the previous command, e.g.
+        // ILLEGAL_COMMAND after CMD8 on a version 1 card
+        let status = match self.command(55, u32::from(rca) << 16, Response::R1, None) {
+            Ok(status) | Err(MmcError::Card(status)) => status,
+            Err(error) => return Err(error),
+        };
+        if status & STATUS_APP_CMD == 0 {
+            return Err(MmcError::Card(status));
+        }
+        self.command(index, arg, response, None)
+    }
+    /// Identify and select the card and switch to the default speed
+    ///
+    /// The bus is 1 bit wide afterwards. The card detect of the controller is
+    /// not checked, since eMMC and boards with a GPIO for it do not wire it.
+    /// Without a card the commands time out.
+    pub fn identify(&mut self) -> Result<Card, MmcError> {
+        self.card = None;
+        self.set_clock(IDENTIFICATION_HZ);
+        self.set_host_bus_width(BusWidth::One);
+        self.command(0, 0, Response::None, None)?;
+        // eMMC does not answer or rejects the commands of SD cards
+        let card_type = match self.identify_sd() {
+            Err(MmcError::Timeout) | Err(MmcError::Card(_)) => {
+                self.command(0, 0, Response::None, None)?;
+                self.identify_mmc()?
+            }
+            result => result?,
+        };
+        let cid = {
+            self.command(2, 0, Response::R2, None)?;
+            self.long_response()
+        };
+        let rca = if card_type.is_sd() {
+            (self.command(3, 0, Response::R6, None)? >> 16) as u16
+        } else {
+            self.command(3, u32::from(MMC_RCA) << 16, Response::R1, None)?;
+            self.memory.CON.modify(CON::OD::CLEAR);
+            MMC_RCA
+        };
+        self.command(9, u32::from(rca) << 16, Response::R2, None)?;
+        let csd = self.long_response();
+        self.command(7, u32::from(rca) << 16, Response::R1b, None)?;
+        if !card_type.is_block_addressed() {
+            self.command(16, BLOCK_SIZE as u32, Response::R1, None)?;
+        }
+        self.set_clock(DEFAULT_SPEED_HZ);
+        let blocks = match card_type {
+            CardType::SdHc => csd_v2_blocks(csd),
+            CardType::MmcHc => self.mmc_sector_count()?,
+            _ => csd_v1_blocks(csd),
+        };
+        let card = Card {
+            card_type,
+            rca,
+            cid,
+            csd,
+            blocks,
+        };
+        self.card = Some(card);
+        Ok(card)
+    }
+    fn identify_sd(&self) -> Result<CardType, MmcError> {
+        let version2 = match self.command(8, IF_COND, Response::R7, None) {
+            Ok(echo) if echo & 0xfff == IF_COND => true,
+            Ok(_) => return Err(MmcError::Unsupported),
+            Err(MmcError::Timeout) => false,
+            Err(error) => return Err(error),
+        };
+        let arg = if version2 {
+            OCR_VOLTAGE | OCR_HCS
+        } else {
+            OCR_VOLTAGE
+        };
+        for _ in 0..OCR_RETRIES {
+            let ocr = self.app_command(0, 41, arg, Response::R3)?;
+            if ocr & OCR_BUSY != 0 {
+                return Ok(match (version2, ocr & OCR_HCS != 0) {
+                    (true, true) => CardType::SdHc,
+                    (true, false) => CardType::SdV2,
+                    _ => CardType::SdV1,
+                });
+            }
+        }
+        Err(MmcError::Unsupported)
+    }
+    fn identify_mmc(&self) -> Result<CardType, MmcError> {
+        // eMMC is identified on an open drain bus
+        self.memory.CON.modify(CON::OD::SET);
+        for _ in 0..OCR_RETRIES {
+            let ocr = self.command(1, OCR_VOLTAGE | OCR_SECTOR_MODE, Response::R3, None)?;
+            if ocr & OCR_BUSY != 0 {
+                return Ok(if ocr & OCR_SECTOR_MODE != 0 {
+                    CardType::MmcHc
+                } else {
+                    CardType::Mmc
+                });
+            }
+        }
+        self.memory.CON.modify(CON::OD::CLEAR);
+        Err(MmcError::Unsupported)
+    }
+    /// Returns the sector count of the extended CSD of eMMC
+    fn mmc_sector_count(&self) -> Result<u64, MmcError> {
+        let mut ext_csd = [0; BLOCK_SIZE];
+        self.read_data(8, 0, &mut ext_csd)?;
+        let count = &ext_csd[EXT_CSD_SEC_COUNT..EXT_CSD_SEC_COUNT + 4];
+        Ok(u64::from(u32::from_le_bytes([
+            count[0], count[1], count[2], count[3],
+        ])))
+    }
+    fn set_host_bus_width(&self, width: BusWidth) {
+        match width {
+            BusWidth::One => {
+                self.memory.CON.modify(CON::DW8::CLEAR);
+                self.memory.HCTL.modify(HCTL::DTW::CLEAR);
+            }
+            BusWidth::Four => {
+                self.memory.CON.modify(CON::DW8::CLEAR);
+                self.memory.HCTL.modify(HCTL::DTW::SET);
+            }
+            BusWidth::Eight => {
+                self.memory.CON.modify(CON::DW8::SET);
+                self.memory.HCTL.modify(HCTL::DTW::CLEAR);
+            }
+        }
+    }
+    /// Switch the card and the controller to a bus width
+    ///
+    /// SD cards only support 1 and 4 bits.
+    pub fn set_bus_width(&self, width: BusWidth) -> Result<(), MmcError> {
+        let card = self.card.ok_or(MmcError::NoCard)?;
+        if card.card_type.is_sd() {
+            let arg = match width {
+                BusWidth::One => 0,
+                BusWidth::Four => 2,
+                BusWidth::Eight => return Err(MmcError::Unsupported),
+            };
+            self.app_command(card.rca, 6, arg, Response::R1)?;
+        } else {
+            let value = match width {
+                BusWidth::One => 0,
+                BusWidth::Four => 1,
+                BusWidth::Eight => 2,
+            };
+            // SWITCH, write the byte of the extended CSD
+            let arg = 0b11 << 24 | EXT_CSD_BUS_WIDTH << 16 | value << 8;
+            self.command(6, arg, Response::R1b, None)?;
+        }
+        self.set_host_bus_width(width);
+        Ok(())
+    }
+    /// Returns the address argument of the first block and checks the range
+    fn block_address(&self, lba: u64, len: usize) -> Result<(u32, usize), MmcError> {
+        let card = self.card.ok_or(MmcError::NoCard)?;
+        let blocks = len / BLOCK_SIZE;
+        if len == 0 || len % BLOCK_SIZE != 0 || blocks > MAX_BLOCKS {
+            return Err(MmcError::OutOfRange);
+        }
+        if lba
+            .checked_add(blocks as u64)
+            .map_or(true, |end| end > card.blocks)
+        {
+            return Err(MmcError::OutOfRange);
+        }
+        let address = if card.card_type.is_block_addressed() {
+            lba
+        } else {
+            lba * BLOCK_SIZE as u64
+        };
+        Ok((address as u32, blocks))
+    }
+    fn read_data(&self, index: u8, arg: u32, buffer: &mut [u8]) -> Result<(), MmcError> {
+        let data = Data {
+            read: true,
+            blocks: buffer.len() / BLOCK_SIZE,
+            dma: false,
+        };
+        self.command(index, arg, Response::R1, Some(data))?;
+        for block in buffer.chunks_exact_mut(BLOCK_SIZE) {
+            self.wait_status(STAT::BRR)?;
+            for word in block.chunks_exact_mut(4) {
+                word.copy_from_slice(&self.memory.DATA.get().to_le_bytes());
+            }
+        }
+        self.wait_status(STAT::TC)
+    }
+    /// Read blocks by polling the data register
+    pub fn read_blocks(&self, lba: u64, buffer: &mut [u8]) -> Result<(), MmcError> {
+        let (address, blocks) = self.block_address(lba, buffer.len())?;
+        let index = if blocks > 1 { 18 } else { 17 };
+        self.read_data(index, address, buffer)
+    }
+    /// Write blocks by polling the data register
+    pub fn write_blocks(&self, lba: u64, buffer: &[u8]) -> Result<(), MmcError> {
+        let (address, blocks) = self.block_address(lba, buffer.len())?;
+        let index = if blocks > 1 { 25 } else { 24 };
+        let data = Data {
+            read: false,
+            blocks,
+            dma: false,
+        };
+        self.command(index, address, Response::R1, Some(data))?;
+        for block in buffer.chunks_exact(BLOCK_SIZE) {
+            self.wait_status(STAT::BWR)?;
+            for word in block.chunks_exact(4) {
+                let word = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+                self.memory.DATA.set(word);
+            }
+        }
+        // Completes after the card is no longer busy
+        self.wait_status(STAT::TC)
+    }
+    /// Read blocks with the receive channel of the controller
+    ///
+    /// `mmc` is the physical address of the controller, e.g.
+    /// `memory_map::MMC0`. `finish_dma` has to be called after the transfer.
+    /// Gives the buffer back if there is no card, the blocks are outside of
+    /// it or the card rejected the command.
+    pub fn read_blocks_dma<B: WriteBuffer, M: DmaMemory>(
+        &self,
+        edma: &Edma,
+        channel: Channel,
+        lba: u64,
+        mut buffer: B,
+        mmc: PhysicalAddress,
+        memory: &M,
+    ) -> Result<Transfer<B>, (B, Channel)> {
+        let (_, len) = buffer.dma_write_buffer();
+        let (address, blocks) = match self.block_address(lba, len) {
+            Ok(block) => block,
+            Err(_) => return Err((buffer, channel)),
+        };
+        let data = mmc.as_u32() + DATA_OFFSET;
+        let transfer = Transfer::fifo_to_memory(edma, channel, buffer, data, BLOCK_SIZE, memory)?;
+        let index = if blocks > 1 { 18 } else { 17 };
+        let data = Data {
+            read: true,
+            blocks,
+            dma: true,
+        };
+        match self.command(index, address, Response::R1, Some(data)) {
+            Ok(_) => Ok(transfer),
+            Err(_) => Err(transfer.abort(edma, memory)),
+        }
+    }
+    /// Write blocks with the transmit channel of the controller
+    ///
+    /// `mmc` is the physical address of the controller, e.g.
+    /// `memory_map::MMC0`. `finish_dma` has to be called after the transfer.
+    /// Gives the buffer back if there is no card, the blocks are outside of
+    /// it or the card rejected the command.
+    pub fn write_blocks_dma<B: ReadBuffer, M: DmaMemory>(
+        &self,
+        edma: &Edma,
+        channel: Channel,
+        lba: u64,
+        buffer: B,
+        mmc: PhysicalAddress,
+        memory: &M,
+    ) -> Result<Transfer<B>, (B, Channel)> {
+        let (_, len) = buffer.dma_read_buffer();
+        let (address, blocks) = match self.block_address(lba, len) {
+            Ok(block) => block,
+            Err(_) => return Err((buffer, channel)),
+        };
+        let data = mmc.as_u32() + DATA_OFFSET;
+        let transfer = Transfer::memory_to_fifo(edma, channel, buffer, data, BLOCK_SIZE, memory)?;
+        let index = if blocks > 1 { 25 } else { 24 };
+        let data = Data {
+            read: false,
+            blocks,
+            dma: true,
+        };
+        match self.command(index, address, Response::R1, Some(data)) {
+            Ok(_) => Ok(transfer),
+            Err(_) => Err(transfer.abort(edma, memory)),
+        }
+    }
+    /// Wait until the data of a DMA transfer is complete and check for errors
+    pub fn finish_dma(&self) -> Result<(), MmcError> {
+        self.wait_status(STAT::TC)
+    }
+    /// Raise the interrupt when a card is inserted or removed
+    pub fn enable_card_detect_interrupt(&self) {
+        self.memory.ISE.modify(STAT::CINS::SET + STAT::CREM::SET);
+    }
+    pub fn disable_card_detect_interrupt(&self) {
+        self.memory
+            .ISE
+            .modify(STAT::CINS::CLEAR + STAT::CREM::CLEAR);
+    }
+    /// Returns whether a card was inserted (true) or removed (false) and
+    /// clears the event
+    ///
+    /// A removed card has to be identified again.
+    pub fn card_change(&mut self) -> Option<bool> {
+        let stat = self.memory.STAT.extract();
+        if stat.is_set(STAT::CREM) {
+            self.memory.STAT.write(STAT::CREM::SET + STAT::CINS::SET);
+            self.card = None;
+            Some(false)
+        } else if stat.is_set(STAT::CINS) {
+            self.memory.STAT.write(STAT::CINS::SET);
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+impl BlockDevice for Mmc {
+    type Error = MmcError;
+    fn block_count(&self) -> u64 {
+        self.card.map_or(0, |card| card.blocks)
+    }
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), MmcError> {
+        Mmc::read_blocks(self, lba, buffer)
+    }
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<(), MmcError> {
+        Mmc::write_blocks(self, lba, buffer)
+    }
+}
@@ -1,11 +1,34 @@
 //! Provides access to the memory-mapped devices of the Sitara.
+//!
+//! A driver is created by an unsafe `new` from the address of its module, the
+//! caller guarantees that no second driver is created for the same module. The
+//! drivers are `Send` but not `Sync`, so all accesses to the registers move
+//! with the driver. The comment at each `unsafe impl Send` names what else
+//! shares the registers or state of the driver.
 
 // Author: Moritz Doll
 // License: MIT
 
+pub mod adc;
+pub mod block;
 pub mod console;
 pub mod control_mod;
+pub mod cpdma;
+pub mod cpsw;
+pub mod dma;
+pub mod ecap;
+pub mod edma;
+pub mod epwm;
+pub mod eqep;
 pub mod gpio;
+pub mod mdio;
+pub mod mmc;
+#[cfg(feature = "smoltcp")]
+pub mod net;
+pub mod pad;
+pub mod phy;
+pub mod pin_manager;
+pub mod pwmss;
 pub mod timer;
 pub mod uart;
 pub mod watchdog;
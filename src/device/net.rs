@@ -0,0 +1,125 @@
+//! The Ethernet switch as a network device of smoltcp
+//!
+//! Only available with the `smoltcp` feature. The tokens borrow the buffers
+//! of the CPDMA rings, so received frames are handed to smoltcp in place and
+//! frames to send are built directly in a transmit buffer.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::cpsw::{Cpsw, Receiver, Sender};
+use crate::device::dma::DmaMemory;
+use crate::device::timer::Monotonic;
+use smoltcp::iface::{Interface, PollResult, SocketSet};
+use smoltcp::phy::{self, DeviceCapabilities, Medium};
+use smoltcp::time::{Duration, Instant};
+
+/// Longest frame without CRC, including the Ethernet header
+pub const MTU: usize = 1514;
+
+/// Returns the time of the clock for smoltcp
+pub fn now(clock: &mut Monotonic) -> Instant {
+    Instant::from_micros(clock.micros() as i64)
+}
+
+pub struct CpswDevice<M: DmaMemory> {
+    cpsw: Cpsw,
+    memory: M,
+}
+
+impl<M: DmaMemory> CpswDevice<M> {
+    /// Wrap an initialized switch
+    pub fn new(cpsw: Cpsw, memory: M) -> Self {
+        CpswDevice { cpsw, memory }
+    }
+    /// Returns the switch, e.g. to handle its interrupts
+    pub fn cpsw(&mut self) -> &mut Cpsw {
+        &mut self.cpsw
+    }
+    pub fn release(self) -> (Cpsw, M) {
+        (self.cpsw, self.memory)
+    }
+    /// Process the received frames and the sockets at the time of the clock
+    pub fn poll(
+        &mut self,
+        iface: &mut Interface,
+        sockets: &mut SocketSet<'_>,
+        clock: &mut Monotonic,
+    ) -> PollResult {
+        iface.poll(now(clock), self, sockets)
+    }
+    /// Returns the time until `poll` has to be called again, None if only
+    /// after the next received frame
+    pub fn poll_delay(
+        &self,
+        iface: &mut Interface,
+        sockets: &SocketSet<'_>,
+        clock: &mut Monotonic,
+    ) -> Option<Duration> {
+        iface.poll_delay(now(clock), sockets)
+    }
+}
+
+/// A received frame in a buffer of the receive ring
+pub struct RxToken<'a, M: DmaMemory> {
+    rx: Receiver<'a>,
+    memory: &'a M,
+}
+
+/// A free buffer of the transmit ring
+pub struct TxToken<'a, M: DmaMemory> {
+    tx: Sender<'a>,
+    memory: &'a M,
+}
+
+impl<M: DmaMemory> phy::Device for CpswDevice<M> {
+    type RxToken<'a>
+        = RxToken<'a, M>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, M>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(RxToken<'_, M>, TxToken<'_, M>)> {
+        let (mut rx, mut tx) = self.cpsw.split();
+        if !rx.poll(&self.memory) || tx.free() == 0 {
+            return None;
+        }
+        let memory = &self.memory;
+        Some((RxToken { rx, memory }, TxToken { tx, memory }))
+    }
+    fn transmit(&mut self, _timestamp: Instant) -> Option<TxToken<'_, M>> {
+        let (_, mut tx) = self.cpsw.split();
+        if tx.free() == 0 {
+            return None;
+        }
+        Some(TxToken {
+            tx,
+            memory: &self.memory,
+        })
+    }
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ethernet;
+        capabilities.max_transmission_unit = MTU;
+        capabilities
+    }
+}
+
+impl<'a, M: DmaMemory> phy::RxToken for RxToken<'a, M> {
+    fn consume<R, F: FnOnce(&[u8]) -> R>(mut self, f: F) -> R {
+        self.rx
+            .receive(self.memory, f)
+            .expect("the token is only given out for a received frame")
+    }
+}
+
+impl<'a, M: DmaMemory> phy::TxToken for TxToken<'a, M> {
+    fn consume<R, F: FnOnce(&mut [u8]) -> R>(mut self, len: usize, f: F) -> R {
+        self.tx
+            .send_with(len, self.memory, f)
+            .expect("the token is only given out for a free buffer")
+    }
+}
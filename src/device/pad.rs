@@ -0,0 +1,486 @@
+//! The pads of the AM335x and the signals they can be multiplexed to
+//!
+//! The offsets and signals are taken from the pin attributes of the AM335x
+//! datasheet (ZCZ package).
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::control_mod::{MuxMode, CONF_NUM};
+
+/// A pad with a configuration register in the control module
+///
+/// The discriminant is the offset of the conf register from the start of the
+/// control module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Pad {
+    GpmcAd0 = 0x800,
+    GpmcAd1 = 0x804,
+    GpmcAd2 = 0x808,
+    GpmcAd3 = 0x80c,
+    GpmcAd4 = 0x810,
+    GpmcAd5 = 0x814,
+    GpmcAd6 = 0x818,
+    GpmcAd7 = 0x81c,
+    GpmcAd8 = 0x820,
+    GpmcAd9 = 0x824,
+    GpmcAd10 = 0x828,
+    GpmcAd11 = 0x82c,
+    GpmcAd12 = 0x830,
+    GpmcAd13 = 0x834,
+    GpmcAd14 = 0x838,
+    GpmcAd15 = 0x83c,
+    GpmcA0 = 0x840,
+    GpmcA1 = 0x844,
+    GpmcA2 = 0x848,
+    GpmcA3 = 0x84c,
+    GpmcA4 = 0x850,
+    GpmcA5 = 0x854,
+    GpmcA6 = 0x858,
+    GpmcA7 = 0x85c,
+    GpmcA8 = 0x860,
+    GpmcA9 = 0x864,
+    GpmcA10 = 0x868,
+    GpmcA11 = 0x86c,
+    GpmcWait0 = 0x870,
+    GpmcWpn = 0x874,
+    GpmcBe1n = 0x878,
+    GpmcCsn0 = 0x87c,
+    GpmcCsn1 = 0x880,
+    GpmcCsn2 = 0x884,
+    GpmcCsn3 = 0x888,
+    GpmcClk = 0x88c,
+    GpmcAdvnAle = 0x890,
+    GpmcOenRen = 0x894,
+    GpmcWen = 0x898,
+    GpmcBe0nCle = 0x89c,
+    LcdData0 = 0x8a0,
+    LcdData1 = 0x8a4,
+    LcdData2 = 0x8a8,
+    LcdData3 = 0x8ac,
+    LcdData4 = 0x8b0,
+    LcdData5 = 0x8b4,
+    LcdData6 = 0x8b8,
+    LcdData7 = 0x8bc,
+    LcdData8 = 0x8c0,
+    LcdData9 = 0x8c4,
+    LcdData10 = 0x8c8,
+    LcdData11 = 0x8cc,
+    LcdData12 = 0x8d0,
+    LcdData13 = 0x8d4,
+    LcdData14 = 0x8d8,
+    LcdData15 = 0x8dc,
+    LcdVsync = 0x8e0,
+    LcdHsync = 0x8e4,
+    LcdPclk = 0x8e8,
+    LcdAcBiasEn = 0x8ec,
+    Mmc0Dat3 = 0x8f0,
+    Mmc0Dat2 = 0x8f4,
+    Mmc0Dat1 = 0x8f8,
+    Mmc0Dat0 = 0x8fc,
+    Mmc0Clk = 0x900,
+    Mmc0Cmd = 0x904,
+    Mii1Col = 0x908,
+    Mii1Crs = 0x90c,
+    Mii1RxEr = 0x910,
+    Mii1TxEn = 0x914,
+    Mii1RxDv = 0x918,
+    Mii1Txd3 = 0x91c,
+    Mii1Txd2 = 0x920,
+    Mii1Txd1 = 0x924,
+    Mii1Txd0 = 0x928,
+    Mii1TxClk = 0x92c,
+    Mii1RxClk = 0x930,
+    Mii1Rxd3 = 0x934,
+    Mii1Rxd2 = 0x938,
+    Mii1Rxd1 = 0x93c,
+    Mii1Rxd0 = 0x940,
+    Rmii1RefClk = 0x944,
+    Mdio = 0x948,
+    Mdc = 0x94c,
+    Spi0Sclk = 0x950,
+    Spi0D0 = 0x954,
+    Spi0D1 = 0x958,
+    Spi0Cs0 = 0x95c,
+    Spi0Cs1 = 0x960,
+    Ecap0InPwm0Out = 0x964,
+    Uart0Ctsn = 0x968,
+    Uart0Rtsn = 0x96c,
+    Uart0Rxd = 0x970,
+    Uart0Txd = 0x974,
+    Uart1Ctsn = 0x978,
+    Uart1Rtsn = 0x97c,
+    Uart1Rxd = 0x980,
+    Uart1Txd = 0x984,
+    I2c0Sda = 0x988,
+    I2c0Scl = 0x98c,
+    Mcasp0Aclkx = 0x990,
+    Mcasp0Fsx = 0x994,
+    Mcasp0Axr0 = 0x998,
+    Mcasp0Ahclkr = 0x99c,
+    Mcasp0Aclkr = 0x9a0,
+    Mcasp0Fsr = 0x9a4,
+    Mcasp0Axr1 = 0x9a8,
+    Mcasp0Ahclkx = 0x9ac,
+    XdmaEventIntr0 = 0x9b0,
+    XdmaEventIntr1 = 0x9b4,
+    NresetinOut = 0x9b8,
+    Nnmi = 0x9c0,
+    Tms = 0x9d0,
+    Tdi = 0x9d4,
+    Tdo = 0x9d8,
+    Tck = 0x9dc,
+    Ntrst = 0x9e0,
+    Emu0 = 0x9e4,
+    Emu1 = 0x9e8,
+    RtcPorz = 0x9f8,
+    PmicPowerEn = 0x9fc,
+    ExtWakeup = 0xa00,
+    EnzKaldo1p8v = 0xa04,
+    Usb0Drvvbus = 0xa1c,
+    Usb1Drvvbus = 0xa34,
+}
+
+/// Number of pads, the conf registers without the reserved offsets
+pub const PAD_NUM: usize = CONF_NUM - 17;
+
+/// All pads ordered by their offset
+pub static PADS: [Pad; PAD_NUM] = [
+    Pad::GpmcAd0,
+    Pad::GpmcAd1,
+    Pad::GpmcAd2,
+    Pad::GpmcAd3,
+    Pad::GpmcAd4,
+    Pad::GpmcAd5,
+    Pad::GpmcAd6,
+    Pad::GpmcAd7,
+    Pad::GpmcAd8,
+    Pad::GpmcAd9,
+    Pad::GpmcAd10,
+    Pad::GpmcAd11,
+    Pad::GpmcAd12,
+    Pad::GpmcAd13,
+    Pad::GpmcAd14,
+    Pad::GpmcAd15,
+    Pad::GpmcA0,
+    Pad::GpmcA1,
+    Pad::GpmcA2,
+    Pad::GpmcA3,
+    Pad::GpmcA4,
+    Pad::GpmcA5,
+    Pad::GpmcA6,
+    Pad::GpmcA7,
+    Pad::GpmcA8,
+    Pad::GpmcA9,
+    Pad::GpmcA10,
+    Pad::GpmcA11,
+    Pad::GpmcWait0,
+    Pad::GpmcWpn,
+    Pad::GpmcBe1n,
+    Pad::GpmcCsn0,
+    Pad::GpmcCsn1,
+    Pad::GpmcCsn2,
+    Pad::GpmcCsn3,
+    Pad::GpmcClk,
+    Pad::GpmcAdvnAle,
+    Pad::GpmcOenRen,
+    Pad::GpmcWen,
+    Pad::GpmcBe0nCle,
+    Pad::LcdData0,
+    Pad::LcdData1,
+    Pad::LcdData2,
+    Pad::LcdData3,
+    Pad::LcdData4,
+    Pad::LcdData5,
+    Pad::LcdData6,
+    Pad::LcdData7,
+    Pad::LcdData8,
+    Pad::LcdData9,
+    Pad::LcdData10,
+    Pad::LcdData11,
+    Pad::LcdData12,
+    Pad::LcdData13,
+    Pad::LcdData14,
+    Pad::LcdData15,
+    Pad::LcdVsync,
+    Pad::LcdHsync,
+    Pad::LcdPclk,
+    Pad::LcdAcBiasEn,
+    Pad::Mmc0Dat3,
+    Pad::Mmc0Dat2,
+    Pad::Mmc0Dat1,
+    Pad::Mmc0Dat0,
+    Pad::Mmc0Clk,
+    Pad::Mmc0Cmd,
+    Pad::Mii1Col,
+    Pad::Mii1Crs,
+    Pad::Mii1RxEr,
+    Pad::Mii1TxEn,
+    Pad::Mii1RxDv,
+    Pad::Mii1Txd3,
+    Pad::Mii1Txd2,
+    Pad::Mii1Txd1,
+    Pad::Mii1Txd0,
+    Pad::Mii1TxClk,
+    Pad::Mii1RxClk,
+    Pad::Mii1Rxd3,
+    Pad::Mii1Rxd2,
+    Pad::Mii1Rxd1,
+    Pad::Mii1Rxd0,
+    Pad::Rmii1RefClk,
+    Pad::Mdio,
+    Pad::Mdc,
+    Pad::Spi0Sclk,
+    Pad::Spi0D0,
+    Pad::Spi0D1,
+    Pad::Spi0Cs0,
+    Pad::Spi0Cs1,
+    Pad::Ecap0InPwm0Out,
+    Pad::Uart0Ctsn,
+    Pad::Uart0Rtsn,
+    Pad::Uart0Rxd,
+    Pad::Uart0Txd,
+    Pad::Uart1Ctsn,
+    Pad::Uart1Rtsn,
+    Pad::Uart1Rxd,
+    Pad::Uart1Txd,
+    Pad::I2c0Sda,
+    Pad::I2c0Scl,
+    Pad::Mcasp0Aclkx,
+    Pad::Mcasp0Fsx,
+    Pad::Mcasp0Axr0,
+    Pad::Mcasp0Ahclkr,
+    Pad::Mcasp0Aclkr,
+    Pad::Mcasp0Fsr,
+    Pad::Mcasp0Axr1,
+    Pad::Mcasp0Ahclkx,
+    Pad::XdmaEventIntr0,
+    Pad::XdmaEventIntr1,
+    Pad::NresetinOut,
+    Pad::Nnmi,
+    Pad::Tms,
+    Pad::Tdi,
+    Pad::Tdo,
+    Pad::Tck,
+    Pad::Ntrst,
+    Pad::Emu0,
+    Pad::Emu1,
+    Pad::RtcPorz,
+    Pad::PmicPowerEn,
+    Pad::ExtWakeup,
+    Pad::EnzKaldo1p8v,
+    Pad::Usb0Drvvbus,
+    Pad::Usb1Drvvbus,
+];
+
+/// Signals selected by mode 0 to 7 of every conf register, empty if the mode
+/// or the whole register is reserved
+#[rustfmt::skip]
+static SIGNALS: [[&str; 8]; CONF_NUM] = [
+    ["gpmc_ad0", "mmc1_dat0", "", "", "", "", "", "gpio1_0"],
+    ["gpmc_ad1", "mmc1_dat1", "", "", "", "", "", "gpio1_1"],
+    ["gpmc_ad2", "mmc1_dat2", "", "", "", "", "", "gpio1_2"],
+    ["gpmc_ad3", "mmc1_dat3", "", "", "", "", "", "gpio1_3"],
+    ["gpmc_ad4", "mmc1_dat4", "", "", "", "", "", "gpio1_4"],
+    ["gpmc_ad5", "mmc1_dat5", "", "", "", "", "", "gpio1_5"],
+    ["gpmc_ad6", "mmc1_dat6", "", "", "", "", "", "gpio1_6"],
+    ["gpmc_ad7", "mmc1_dat7", "", "", "", "", "", "gpio1_7"],
+    ["gpmc_ad8", "lcd_data23", "mmc1_dat0", "mmc2_dat4", "ehrpwm2A", "pr1_mii_mt0_clk", "", "gpio0_22"],
+    ["gpmc_ad9", "lcd_data22", "mmc1_dat1", "mmc2_dat5", "ehrpwm2B", "pr1_mii0_col", "", "gpio0_23"],
+    ["gpmc_ad10", "lcd_data21", "mmc1_dat2", "mmc2_dat6", "ehrpwm2_tripzone_input", "pr1_mii0_txen", "", "gpio0_26"],
+    ["gpmc_ad11", "lcd_data20", "mmc1_dat3", "mmc2_dat7", "ehrpwm0_synco", "pr1_mii0_txd3", "", "gpio0_27"],
+    ["gpmc_ad12", "lcd_data19", "mmc1_dat4", "mmc2_dat0", "eQEP2A_in", "pr1_mii0_txd2", "pr1_pru0_pru_r30_14", "gpio1_12"],
+    ["gpmc_ad13", "lcd_data18", "mmc1_dat5", "mmc2_dat1", "eQEP2B_in", "pr1_mii0_txd1", "pr1_pru0_pru_r30_15", "gpio1_13"],
+    ["gpmc_ad14", "lcd_data17", "mmc1_dat6", "mmc2_dat2", "eQEP2_index", "pr1_mii0_txd0", "pr1_pru0_pru_r31_14", "gpio1_14"],
+    ["gpmc_ad15", "lcd_data16", "mmc1_dat7", "mmc2_dat3", "eQEP2_strobe", "pr1_ecap0_ecap_capin_apwm_o", "pr1_pru0_pru_r31_15", "gpio1_15"],
+    ["gpmc_a0", "gmii2_txen", "rgmii2_tctl", "rmii2_txen", "gpmc_a16", "pr1_mii_mt1_clk", "ehrpwm1_tripzone_input", "gpio1_16"],
+    ["gpmc_a1", "gmii2_rxdv", "rgmii2_rctl", "mmc2_dat0", "gpmc_a17", "pr1_mii1_txd3", "ehrpwm0_synco", "gpio1_17"],
+    ["gpmc_a2", "gmii2_txd3", "rgmii2_td3", "mmc2_dat1", "gpmc_a18", "pr1_mii1_txd2", "ehrpwm1A", "gpio1_18"],
+    ["gpmc_a3", "gmii2_txd2", "rgmii2_td2", "mmc2_dat2", "gpmc_a19", "pr1_mii1_txd1", "ehrpwm1B", "gpio1_19"],
+    ["gpmc_a4", "gmii2_txd1", "rgmii2_td1", "rmii2_txd1", "gpmc_a20", "pr1_mii1_txd0", "eQEP1A_in", "gpio1_20"],
+    ["gpmc_a5", "gmii2_txd0", "rgmii2_td0", "rmii2_txd0", "gpmc_a21", "pr1_mii1_rxd3", "eQEP1B_in", "gpio1_21"],
+    ["gpmc_a6", "gmii2_txclk", "rgmii2_tclk", "mmc2_dat4", "gpmc_a22", "pr1_mii1_rxd2", "eQEP1_index", "gpio1_22"],
+    ["gpmc_a7", "gmii2_rxclk", "rgmii2_rclk", "mmc2_dat5", "gpmc_a23", "pr1_mii1_rxd1", "eQEP1_strobe", "gpio1_23"],
+    ["gpmc_a8", "gmii2_rxd3", "rgmii2_rd3", "mmc2_dat6", "gpmc_a24", "pr1_mii1_rxd0", "mcasp0_aclkx", "gpio1_24"],
+    ["gpmc_a9", "gmii2_rxd2", "rgmii2_rd2", "mmc2_dat7", "gpmc_a25", "pr1_mii_mr1_clk", "mcasp0_fsx", "gpio1_25"],
+    ["gpmc_a10", "gmii2_rxd1", "rgmii2_rd1", "rmii2_rxd1", "gpmc_a26", "pr1_mii1_rxdv", "mcasp0_axr0", "gpio1_26"],
+    ["gpmc_a11", "gmii2_rxd0", "rgmii2_rd0", "rmii2_rxd0", "gpmc_a27", "pr1_mii1_rxer", "mcasp0_axr1", "gpio1_27"],
+    ["gpmc_wait0", "gmii2_crs", "gpmc_csn4", "rmii2_crs_dv", "mmc1_sdcd", "pr1_mii1_col", "uart4_rxd", "gpio0_30"],
+    ["gpmc_wpn", "gmii2_rxerr", "gpmc_csn5", "rmii2_rxerr", "mmc2_sdcd", "pr1_mii1_txen", "uart4_txd", "gpio0_31"],
+    ["gpmc_be1n", "gmii2_col", "gpmc_csn6", "mmc2_dat3", "gpmc_dir", "pr1_mii1_rxlink", "mcasp0_aclkr", "gpio1_28"],
+    ["gpmc_csn0", "", "", "", "", "", "", "gpio1_29"],
+    ["gpmc_csn1", "gpmc_clk", "mmc1_clk", "pr1_edio_data_in6", "pr1_edio_data_out6", "pr1_pru1_pru_r30_12", "pr1_pru1_pru_r31_12", "gpio1_30"],
+    ["gpmc_csn2", "gpmc_be1n", "mmc1_cmd", "pr1_edio_data_in7", "pr1_edio_data_out7", "pr1_pru1_pru_r30_13", "pr1_pru1_pru_r31_13", "gpio1_31"],
+    ["gpmc_csn3", "gpmc_a3", "rmii2_crs_dv", "mmc2_cmd", "pr1_mii0_crs", "pr1_mdio_data", "emu4", "gpio2_0"],
+    ["gpmc_clk", "lcd_memory_clk", "gpmc_wait1", "mmc2_clk", "pr1_mii1_crs", "pr1_mdio_mdclk", "mcasp0_fsr", "gpio2_1"],
+    ["gpmc_advn_ale", "", "timer4", "", "", "", "", "gpio2_2"],
+    ["gpmc_oen_ren", "", "timer7", "", "", "", "", "gpio2_3"],
+    ["gpmc_wen", "", "timer6", "", "", "", "", "gpio2_4"],
+    ["gpmc_be0n_cle", "", "timer5", "", "", "", "", "gpio2_5"],
+    ["lcd_data0", "gpmc_a0", "pr1_mii_mt0_clk", "ehrpwm2A", "", "pr1_pru1_pru_r30_0", "pr1_pru1_pru_r31_0", "gpio2_6"],
+    ["lcd_data1", "gpmc_a1", "pr1_mii0_txen", "ehrpwm2B", "", "pr1_pru1_pru_r30_1", "pr1_pru1_pru_r31_1", "gpio2_7"],
+    ["lcd_data2", "gpmc_a2", "pr1_mii0_txd3", "ehrpwm2_tripzone_input", "", "pr1_pru1_pru_r30_2", "pr1_pru1_pru_r31_2", "gpio2_8"],
+    ["lcd_data3", "gpmc_a3", "pr1_mii0_txd2", "ehrpwm0_synco", "", "pr1_pru1_pru_r30_3", "pr1_pru1_pru_r31_3", "gpio2_9"],
+    ["lcd_data4", "gpmc_a4", "pr1_mii0_txd1", "eQEP2A_in", "", "pr1_pru1_pru_r30_4", "pr1_pru1_pru_r31_4", "gpio2_10"],
+    ["lcd_data5", "gpmc_a5", "pr1_mii0_txd0", "eQEP2B_in", "", "pr1_pru1_pru_r30_5", "pr1_pru1_pru_r31_5", "gpio2_11"],
+    ["lcd_data6", "gpmc_a6", "pr1_edio_data_in6", "eQEP2_index", "pr1_edio_data_out6", "pr1_pru1_pru_r30_6", "pr1_pru1_pru_r31_6", "gpio2_12"],
+    ["lcd_data7", "gpmc_a7", "pr1_edio_data_in7", "eQEP2_strobe", "pr1_edio_data_out7", "pr1_pru1_pru_r30_7", "pr1_pru1_pru_r31_7", "gpio2_13"],
+    ["lcd_data8", "gpmc_a12", "ehrpwm1_tripzone_input", "mcasp0_aclkx", "uart5_txd", "pr1_mii0_rxd3", "uart2_ctsn", "gpio2_14"],
+    ["lcd_data9", "gpmc_a13", "ehrpwm0_synco", "mcasp0_fsx", "uart5_rxd", "pr1_mii0_rxd2", "uart2_rtsn", "gpio2_15"],
+    ["lcd_data10", "gpmc_a14", "ehrpwm1A", "mcasp0_axr0", "", "pr1_mii0_rxd1", "uart3_ctsn", "gpio2_16"],
+    ["lcd_data11", "gpmc_a15", "ehrpwm1B", "mcasp0_ahclkr", "mcasp0_axr2", "pr1_mii0_rxd0", "uart3_rtsn", "gpio2_17"],
+    ["lcd_data12", "gpmc_a16", "eQEP1A_in", "mcasp0_aclkr", "mcasp0_axr2", "pr1_mii0_rxlink", "uart4_ctsn", "gpio0_8"],
+    ["lcd_data13", "gpmc_a17", "eQEP1B_in", "mcasp0_fsr", "mcasp0_axr3", "pr1_mii0_rxer", "uart4_rtsn", "gpio0_9"],
+    ["lcd_data14", "gpmc_a18", "eQEP1_index", "mcasp0_axr1", "uart5_rxd", "pr1_mii_mr0_clk", "uart5_ctsn", "gpio0_10"],
+    ["lcd_data15", "gpmc_a19", "eQEP1_strobe", "mcasp0_ahclkx", "mcasp0_axr3", "pr1_mii0_rxdv", "uart5_rtsn", "gpio0_11"],
+    ["lcd_vsync", "gpmc_a8", "gpmc_a1", "pr1_edio_data_in2", "pr1_edio_data_out2", "pr1_pru1_pru_r30_8", "pr1_pru1_pru_r31_8", "gpio2_22"],
+    ["lcd_hsync", "gpmc_a9", "gpmc_a2", "pr1_edio_data_in3", "pr1_edio_data_out3", "pr1_pru1_pru_r30_9", "pr1_pru1_pru_r31_9", "gpio2_23"],
+    ["lcd_pclk", "gpmc_a10", "pr1_mii0_crs", "pr1_edio_data_in4", "pr1_edio_data_out4", "pr1_pru1_pru_r30_10", "pr1_pru1_pru_r31_10", "gpio2_24"],
+    ["lcd_ac_bias_en", "gpmc_a11", "pr1_mii1_crs", "pr1_edio_data_in5", "pr1_edio_data_out5", "pr1_pru1_pru_r30_11", "pr1_pru1_pru_r31_11", "gpio2_25"],
+    ["mmc0_dat3", "gpmc_a20", "uart4_ctsn", "timer5", "uart1_dcdn", "pr1_pru0_pru_r30_8", "pr1_pru0_pru_r31_8", "gpio2_26"],
+    ["mmc0_dat2", "gpmc_a21", "uart4_rtsn", "timer6", "uart1_dsrn", "pr1_pru0_pru_r30_9", "pr1_pru0_pru_r31_9", "gpio2_27"],
+    ["mmc0_dat1", "gpmc_a22", "uart5_ctsn", "uart3_rxd", "uart1_dtrn", "pr1_pru0_pru_r30_10", "pr1_pru0_pru_r31_10", "gpio2_28"],
+    ["mmc0_dat0", "gpmc_a23", "uart5_rtsn", "uart3_txd", "uart1_rin", "pr1_pru0_pru_r30_11", "pr1_pru0_pru_r31_11", "gpio2_29"],
+    ["mmc0_clk", "gpmc_a24", "uart3_ctsn", "uart2_rxd", "dcan1_tx", "pr1_pru0_pru_r30_12", "pr1_pru0_pru_r31_12", "gpio2_30"],
+    ["mmc0_cmd", "gpmc_a25", "uart3_rtsn", "uart2_txd", "dcan1_rx", "pr1_pru0_pru_r30_13", "pr1_pru0_pru_r31_13", "gpio2_31"],
+    ["gmii1_col", "rmii2_refclk", "spi1_sclk", "uart5_rxd", "mcasp1_axr2", "mmc2_dat3", "mcasp0_axr2", "gpio3_0"],
+    ["gmii1_crs", "rmii1_crs_dv", "spi1_d0", "i2c1_sda", "mcasp1_aclkx", "uart5_ctsn", "uart2_rxd", "gpio3_1"],
+    ["gmii1_rxerr", "rmii1_rxerr", "spi1_d1", "i2c1_scl", "mcasp1_fsx", "uart5_rtsn", "uart2_txd", "gpio3_2"],
+    ["gmii1_txen", "rmii1_txen", "rgmii1_tctl", "timer4", "mcasp1_axr0", "eQEP0_index", "mmc2_cmd", "gpio3_3"],
+    ["gmii1_rxdv", "lcd_memory_clk", "rgmii1_rctl", "uart5_txd", "mcasp1_aclkx", "mmc2_dat0", "mcasp0_aclkr", "gpio3_4"],
+    ["gmii1_txd3", "dcan0_tx", "rgmii1_td3", "uart4_rxd", "mcasp1_fsx", "mmc2_dat1", "mcasp0_fsr", "gpio0_16"],
+    ["gmii1_txd2", "dcan0_rx", "rgmii1_td2", "uart4_txd", "mcasp1_axr0", "mmc2_dat2", "mcasp0_ahclkx", "gpio0_17"],
+    ["gmii1_txd1", "rmii1_txd1", "rgmii1_td1", "mcasp1_fsr", "mcasp1_axr1", "eQEP0A_in", "mmc1_cmd", "gpio0_21"],
+    ["gmii1_txd0", "rmii1_txd0", "rgmii1_td0", "mcasp1_axr2", "mcasp1_aclkr", "eQEP0B_in", "mmc1_clk", "gpio0_28"],
+    ["gmii1_txclk", "uart2_rxd", "rgmii1_tclk", "mmc0_dat7", "mmc1_dat0", "uart1_dcdn", "mcasp0_aclkx", "gpio3_9"],
+    ["gmii1_rxclk", "uart2_txd", "rgmii1_rclk", "mmc0_dat6", "mmc1_dat1", "uart1_dsrn", "mcasp0_fsx", "gpio3_10"],
+    ["gmii1_rxd3", "uart3_rxd", "rgmii1_rd3", "mmc0_dat5", "mmc1_dat2", "uart1_dtrn", "mcasp0_axr0", "gpio2_18"],
+    ["gmii1_rxd2", "uart3_txd", "rgmii1_rd2", "mmc0_dat4", "mmc1_dat3", "uart1_rin", "mcasp0_axr1", "gpio2_19"],
+    ["gmii1_rxd1", "rmii1_rxd1", "rgmii1_rd1", "mcasp1_axr3", "mcasp1_fsr", "eQEP0_strobe", "mmc2_clk", "gpio2_20"],
+    ["gmii1_rxd0", "rmii1_rxd0", "rgmii1_rd0", "mcasp1_ahclkx", "mcasp1_ahclkr", "mcasp1_aclkr", "mcasp0_axr3", "gpio2_21"],
+    ["rmii1_refclk", "xdma_event_intr2", "spi1_cs0", "uart5_txd", "mcasp1_axr3", "mmc0_pow", "mcasp1_ahclkx", "gpio0_29"],
+    ["mdio_data", "timer6", "uart5_rxd", "uart3_ctsn", "mmc0_sdcd", "mmc1_cmd", "mmc2_cmd", "gpio0_0"],
+    ["mdio_clk", "timer5", "uart5_txd", "uart3_rtsn", "mmc0_sdwp", "mmc1_clk", "mmc2_clk", "gpio0_1"],
+    ["spi0_sclk", "uart2_rxd", "i2c2_sda", "ehrpwm0A", "pr1_uart0_cts_n", "pr1_edio_sof", "emu2", "gpio0_2"],
+    ["spi0_d0", "uart2_txd", "i2c2_scl", "ehrpwm0B", "pr1_uart0_rts_n", "pr1_edio_latch_in", "emu3", "gpio0_3"],
+    ["spi0_d1", "mmc1_sdwp", "i2c1_sda", "ehrpwm0_tripzone_input", "pr1_uart0_rxd", "pr1_edio_data_in0", "pr1_edio_data_out0", "gpio0_4"],
+    ["spi0_cs0", "mmc2_sdwp", "i2c1_scl", "ehrpwm0_synci", "pr1_uart0_txd", "pr1_edio_data_in1", "pr1_edio_data_out1", "gpio0_5"],
+    ["spi0_cs1", "uart3_rxd", "eCAP1_in_PWM1_out", "mmc0_pow", "xdma_event_intr2", "mmc0_sdcd", "emu4", "gpio0_6"],
+    ["eCAP0_in_PWM0_out", "uart3_txd", "spi1_cs1", "pr1_ecap0_ecap_capin_apwm_o", "spi1_sclk", "mmc0_sdwp", "xdma_event_intr2", "gpio0_7"],
+    ["uart0_ctsn", "uart4_rxd", "dcan1_tx", "i2c1_sda", "spi1_d0", "timer7", "pr1_edc_sync0_out", "gpio1_8"],
+    ["uart0_rtsn", "uart4_txd", "dcan1_rx", "i2c1_scl", "spi1_d1", "spi1_cs0", "pr1_edc_sync1_out", "gpio1_9"],
+    ["uart0_rxd", "spi1_cs0", "dcan0_tx", "i2c2_sda", "eCAP2_in_PWM2_out", "pr1_pru1_pru_r30_14", "pr1_pru1_pru_r31_14", "gpio1_10"],
+    ["uart0_txd", "spi1_cs1", "dcan0_rx", "i2c2_scl", "eCAP1_in_PWM1_out", "pr1_pru1_pru_r30_15", "pr1_pru1_pru_r31_15", "gpio1_11"],
+    ["uart1_ctsn", "timer6_mux1", "dcan0_tx", "i2c2_sda", "spi1_cs0", "pr1_uart0_cts_n", "pr1_edc_latch0_in", "gpio0_12"],
+    ["uart1_rtsn", "timer5_mux1", "dcan0_rx", "i2c2_scl", "spi1_cs1", "pr1_uart0_rts_n", "pr1_edc_latch1_in", "gpio0_13"],
+    ["uart1_rxd", "mmc1_sdwp", "dcan1_tx", "i2c1_sda", "", "pr1_uart0_rxd", "pr1_pru1_pru_r31_16", "gpio0_14"],
+    ["uart1_txd", "mmc2_sdwp", "dcan1_rx", "i2c1_scl", "", "pr1_uart0_txd", "pr1_pru0_pru_r31_16", "gpio0_15"],
+    ["i2c0_sda", "timer4", "uart2_ctsn", "eCAP2_in_PWM2_out", "", "", "", "gpio3_5"],
+    ["i2c0_scl", "timer7", "uart2_rtsn", "eCAP1_in_PWM1_out", "", "", "", "gpio3_6"],
+    ["mcasp0_aclkx", "ehrpwm0A", "", "spi1_sclk", "mmc0_sdcd", "pr1_pru0_pru_r30_0", "pr1_pru0_pru_r31_0", "gpio3_14"],
+    ["mcasp0_fsx", "ehrpwm0B", "", "spi1_d0", "mmc1_sdcd", "pr1_pru0_pru_r30_1", "pr1_pru0_pru_r31_1", "gpio3_15"],
+    ["mcasp0_axr0", "ehrpwm0_tripzone_input", "", "spi1_d1", "mmc2_sdcd", "pr1_pru0_pru_r30_2", "pr1_pru0_pru_r31_2", "gpio3_16"],
+    ["mcasp0_ahclkr", "ehrpwm0_synci", "mcasp0_axr2", "spi1_cs0", "eCAP2_in_PWM2_out", "pr1_pru0_pru_r30_3", "pr1_pru0_pru_r31_3", "gpio3_17"],
+    ["mcasp0_aclkr", "eQEP0A_in", "mcasp0_axr2", "mcasp1_aclkx", "mmc0_sdwp", "pr1_pru0_pru_r30_4", "pr1_pru0_pru_r31_4", "gpio3_18"],
+    ["mcasp0_fsr", "eQEP0B_in", "mcasp0_axr3", "mcasp1_fsx", "emu2", "pr1_pru0_pru_r30_5", "pr1_pru0_pru_r31_5", "gpio3_19"],
+    ["mcasp0_axr1", "eQEP0_index", "", "mcasp1_axr0", "emu3", "pr1_pru0_pru_r30_6", "pr1_pru0_pru_r31_6", "gpio3_20"],
+    ["mcasp0_ahclkx", "eQEP0_strobe", "mcasp0_axr3", "mcasp1_axr1", "emu4", "pr1_pru0_pru_r30_7", "pr1_pru0_pru_r31_7", "gpio3_21"],
+    ["xdma_event_intr0", "", "timer4", "clkout1", "spi1_cs1", "pr1_pru1_pru_r31_16", "emu2", "gpio0_19"],
+    ["xdma_event_intr1", "", "tclkin", "clkout2", "timer7", "pr1_pru0_pru_r31_16", "emu3", "gpio0_20"],
+    ["nresetin_out", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["nnmi", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["tms", "", "", "", "", "", "", ""],
+    ["tdi", "", "", "", "", "", "", ""],
+    ["tdo", "", "", "", "", "", "", ""],
+    ["tck", "", "", "", "", "", "", ""],
+    ["ntrst", "", "", "", "", "", "", ""],
+    ["emu0", "", "", "", "", "", "", "gpio3_7"],
+    ["emu1", "", "", "", "", "", "", "gpio3_8"],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["rtc_porz", "", "", "", "", "", "", ""],
+    ["pmic_power_en", "", "", "", "", "", "", ""],
+    ["ext_wakeup", "", "", "", "", "", "", ""],
+    ["enz_kaldo_1p8v", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["usb0_drvvbus", "", "", "", "", "", "", "gpio0_18"],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["", "", "", "", "", "", "", ""],
+    ["usb1_drvvbus", "", "", "", "", "", "", "gpio3_13"],
+];
+
+impl Pad {
+    /// Get the pad from the index of its conf register
+    ///
+    /// Returns None for the reserved offsets of the analog and oscillator
+    /// balls, which have no conf register.
+    pub fn from_index(index: usize) -> Option<Pad> {
+        PADS.iter().copied().find(|pad| pad.index() == index)
+    }
+    /// Get the pad from the offset of its conf register in the control module
+    pub fn from_offset(offset: u32) -> Option<Pad> {
+        if offset < 0x800 || offset & 0b11 != 0 {
+            return None;
+        }
+        Self::from_index(((offset - 0x800) / 4) as usize)
+    }
+    /// Returns the offset of the conf register in the control module
+    #[inline]
+    pub fn offset(self) -> u32 {
+        self as u32
+    }
+    /// Returns the index of the conf register
+    #[inline]
+    pub fn index(self) -> usize {
+        ((self as u32 - 0x800) / 4) as usize
+    }
+    /// Returns the signal selected by the mode, if the mode is not reserved
+    pub fn signal(self, mode: MuxMode) -> Option<&'static str> {
+        match SIGNALS[self.index()][mode as usize] {
+            "" => None,
+            signal => Some(signal),
+        }
+    }
+    /// Returns whether the mode selects a signal on this pad
+    pub fn is_legal(self, mode: MuxMode) -> bool {
+        self.signal(mode).is_some()
+    }
+    /// Iterate over all legal modes of the pad and the signal each selects
+    pub fn modes(self) -> impl Iterator<Item = (MuxMode, &'static str)> {
+        MuxMode::ALL
+            .iter()
+            .filter_map(move |&mode| self.signal(mode).map(|signal| (mode, signal)))
+    }
+    /// Find the pad and mode for the GPIO with the global number
+    /// `bank * 32 + bit`
+    pub fn for_gpio(number: u8) -> Option<Pad> {
+        PADS.iter()
+            .copied()
+            .find(|pad| match pad.signal(MuxMode::Mode7) {
+                Some(signal) => parse_gpio(signal) == Some(number),
+                None => false,
+            })
+    }
+}
+
+/// Parse a signal of the form `gpioB_N` into the global number
+fn parse_gpio(signal: &str) -> Option<u8> {
+    let rest = signal.strip_prefix("gpio")?;
+    let mut parts = rest.splitn(2, '_');
+    let bank: u8 = parts.next()?.parse().ok()?;
+    let bit: u8 = parts.next()?.parse().ok()?;
+    Some(bank * 32 + bit)
+}
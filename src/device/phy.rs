@@ -0,0 +1,223 @@
+//! Ethernet PHYs on the MII management bus
+//!
+//! The `Phy` trait provides the clause 22 registers that all PHYs share,
+//! a driver only adds the link status and the interrupts of its vendor
+//! registers.
+
+// Author: Moritz Doll
+// License: MIT
+
+pub use crate::device::cpsw::Speed;
+use core::hint;
+
+/// Basic mode control register
+pub const BMCR: u8 = 0;
+/// Basic mode status register
+pub const BMSR: u8 = 1;
+/// PHY identifier
+pub const PHYID1: u8 = 2;
+pub const PHYID2: u8 = 3;
+/// Auto-negotiation advertisement register
+pub const ANAR: u8 = 4;
+/// Auto-negotiation link partner ability register
+pub const ANLPAR: u8 = 5;
+
+const BMCR_RESET: u16 = 1 << 15;
+const BMCR_SPEED100: u16 = 1 << 13;
+const BMCR_AN_ENABLE: u16 = 1 << 12;
+const BMCR_POWER_DOWN: u16 = 1 << 11;
+const BMCR_AN_RESTART: u16 = 1 << 9;
+const BMCR_FULL_DUPLEX: u16 = 1 << 8;
+
+const BMSR_AN_COMPLETE: u16 = 1 << 5;
+const BMSR_LINK: u16 = 1 << 2;
+
+const AN_100_FULL: u16 = 1 << 8;
+const AN_100_HALF: u16 = 1 << 7;
+const AN_10_FULL: u16 = 1 << 6;
+const AN_10_HALF: u16 = 1 << 5;
+/// Selector of IEEE 802.3
+const AN_SELECTOR: u16 = 0x01;
+/// Number of BMCR reads until the reset has to be done, a read takes tens of
+/// microseconds, so this is longer than the 0.5 s clause 22 allows
+const RESET_RETRIES: u32 = 20_000;
+
+/// Clause 22 register access, e.g. the MDIO
+pub trait MiiBus {
+    /// Returns None if the PHY did not respond
+    fn read(&self, phy: u8, reg: u8) -> Option<u16>;
+    fn write(&self, phy: u8, reg: u8, value: u16) -> Option<()>;
+}
+
+/// A link that is up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    pub speed: Speed,
+    pub full_duplex: bool,
+}
+
+/// An Ethernet PHY
+///
+/// All methods return None if the PHY did not respond.
+pub trait Phy {
+    /// Returns the address of the PHY on the bus
+    fn address(&self) -> u8;
+    /// Returns the negotiated link, None if it is down
+    fn link<B: MiiBus>(&self, bus: &B) -> Option<Link>;
+    /// Raise the interrupt of the PHY on link changes
+    fn enable_link_interrupt<B: MiiBus>(&self, bus: &B) -> Option<()>;
+    fn disable_link_interrupt<B: MiiBus>(&self, bus: &B) -> Option<()>;
+    /// Clear the interrupt, returns whether it was pending
+    fn clear_interrupt<B: MiiBus>(&self, bus: &B) -> Option<bool>;
+
+    fn read<B: MiiBus>(&self, bus: &B, reg: u8) -> Option<u16> {
+        bus.read(self.address(), reg)
+    }
+    fn write<B: MiiBus>(&self, bus: &B, reg: u8, value: u16) -> Option<()> {
+        bus.write(self.address(), reg, value)
+    }
+    /// Returns the 32 bit identifier, OUI, model and revision
+    fn id<B: MiiBus>(&self, bus: &B) -> Option<u32> {
+        let high = self.read(bus, PHYID1)?;
+        let low = self.read(bus, PHYID2)?;
+        Some(u32::from(high) << 16 | u32::from(low))
+    }
+    /// Reset the PHY and wait until it is done
+    ///
+    /// Returns None if the PHY does not answer or does not leave the reset in
+    /// time.
+    fn reset<B: MiiBus>(&self, bus: &B) -> Option<()> {
+        self.write(bus, BMCR, BMCR_RESET)?;
+        for _ in 0..RESET_RETRIES {
+            if self.read(bus, BMCR)? & BMCR_RESET == 0 {
+                return Some(());
+            }
+            hint::spin_loop();
+        }
+        None
+    }
+    /// Advertise 10 and 100 Mbit in both duplex modes and restart the
+    /// auto-negotiation
+    fn start_autonegotiation<B: MiiBus>(&self, bus: &B) -> Option<()> {
+        let advertise = AN_100_FULL | AN_100_HALF | AN_10_FULL | AN_10_HALF | AN_SELECTOR;
+        self.write(bus, ANAR, advertise)?;
+        let bmcr = self.read(bus, BMCR)? & !BMCR_POWER_DOWN;
+        self.write(bus, BMCR, bmcr | BMCR_AN_ENABLE | BMCR_AN_RESTART)
+    }
+    fn is_autonegotiation_done<B: MiiBus>(&self, bus: &B) -> Option<bool> {
+        Some(self.read(bus, BMSR)? & BMSR_AN_COMPLETE != 0)
+    }
+    /// Returns whether the link is up
+    fn is_link_up<B: MiiBus>(&self, bus: &B) -> Option<bool> {
+        // The link bit latches low, the first read clears a past link loss
+        self.read(bus, BMSR)?;
+        Some(self.read(bus, BMSR)? & BMSR_LINK != 0)
+    }
+    /// Disable the auto-negotiation and force the speed and duplex
+    ///
+    /// Returns None for gigabit.
+    fn force_link<B: MiiBus>(&self, bus: &B, link: Link) -> Option<()> {
+        let mut bmcr = match link.speed {
+            Speed::Mbit10 => 0,
+            Speed::Mbit100 => BMCR_SPEED100,
+            Speed::Mbit1000 => return None,
+        };
+        if link.full_duplex {
+            bmcr |= BMCR_FULL_DUPLEX;
+        }
+        self.write(bus, BMCR, bmcr)
+    }
+    /// Returns the best common mode of the advertisement and the partner
+    fn negotiated_link<B: MiiBus>(&self, bus: &B) -> Option<Link> {
+        if !self.is_link_up(bus)? {
+            return None;
+        }
+        let common = self.read(bus, ANAR)? & self.read(bus, ANLPAR)?;
+        let (speed, full_duplex) = if common & AN_100_FULL != 0 {
+            (Speed::Mbit100, true)
+        } else if common & AN_100_HALF != 0 {
+            (Speed::Mbit100, false)
+        } else if common & AN_10_FULL != 0 {
+            (Speed::Mbit10, true)
+        } else {
+            (Speed::Mbit10, false)
+        };
+        Some(Link { speed, full_duplex })
+    }
+}
+
+/// The SMSC LAN8710A of the BeagleBone Black
+pub struct Lan8710a {
+    address: u8,
+}
+
+/// Identifier of the LAN8710A without the revision
+pub const LAN8710A_ID: u32 = 0x0007_C0F0;
+const LAN8710A_ID_MASK: u32 = 0xffff_fff0;
+/// Address of the PHY on the BeagleBone Black
+pub const LAN8710A_BBB_ADDRESS: u8 = 0;
+
+/// Interrupt source flags, cleared by reading
+const ISF: u8 = 29;
+/// Interrupt mask
+const IMR: u8 = 30;
+/// PHY special control and status
+const PSCSR: u8 = 31;
+
+const INT_AN_COMPLETE: u16 = 1 << 6;
+const INT_LINK_DOWN: u16 = 1 << 4;
+const PSCSR_SPEED_SHIFT: u16 = 2;
+const PSCSR_SPEED_MASK: u16 = 0b111 << PSCSR_SPEED_SHIFT;
+
+impl Lan8710a {
+    /// Returns the driver if the PHY at the address is a LAN8710A
+    pub fn new<B: MiiBus>(bus: &B, address: u8) -> Option<Self> {
+        let phy = Lan8710a { address };
+        if phy.id(bus)? & LAN8710A_ID_MASK != LAN8710A_ID {
+            return None;
+        }
+        Some(phy)
+    }
+}
+
+impl Phy for Lan8710a {
+    fn address(&self) -> u8 {
+        self.address
+    }
+    fn link<B: MiiBus>(&self, bus: &B) -> Option<Link> {
+        if !self.is_link_up(bus)? {
+            return None;
+        }
+        let speed = (self.read(bus, PSCSR)? & PSCSR_SPEED_MASK) >> PSCSR_SPEED_SHIFT;
+        match speed {
+            0b001 => Some(Link {
+                speed: Speed::Mbit10,
+                full_duplex: false,
+            }),
+            0b101 => Some(Link {
+                speed: Speed::Mbit10,
+                full_duplex: true,
+            }),
+            0b010 => Some(Link {
+                speed: Speed::Mbit100,
+                full_duplex: false,
+            }),
+            0b110 => Some(Link {
+                speed: Speed::Mbit100,
+                full_duplex: true,
+            }),
+            _ => None,
+        }
+    }
+    fn enable_link_interrupt<B: MiiBus>(&self, bus: &B) -> Option<()> {
+        let mask = self.read(bus, IMR)?;
+        self.write(bus, IMR, mask | INT_AN_COMPLETE | INT_LINK_DOWN)
+    }
+    fn disable_link_interrupt<B: MiiBus>(&self, bus: &B) -> Option<()> {
+        let mask = self.read(bus, IMR)?;
+        self.write(bus, IMR, mask & !(INT_AN_COMPLETE | INT_LINK_DOWN))
+    }
+    fn clear_interrupt<B: MiiBus>(&self, bus: &B) -> Option<bool> {
+        Some(self.read(bus, ISF)? & (INT_AN_COMPLETE | INT_LINK_DOWN) != 0)
+    }
+}
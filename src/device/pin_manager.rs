@@ -0,0 +1,181 @@
+//! Bookkeeping of the pin multiplexer
+//!
+//! The pin manager owns the control module and hands out pads. Every pad can
+//! only be claimed once and every signal can only be routed to a single pad,
+//! except for output signals that are claimed with `claim_shared` on every pad
+//! they drive.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::device::control_mod::{Control, PadConfig, PadError, Pull, Slew, CONF_NUM};
+use crate::device::pad::{Pad, PADS};
+use core::fmt;
+
+/// A pad claimed for a signal of a peripheral
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Claim {
+    /// The peripheral that claimed the pad, e.g. "uart1"
+    pub owner: &'static str,
+    /// The signal selected on the pad
+    pub signal: &'static str,
+    /// The signal may be routed to other shared claims as well
+    pub shared: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PinError {
+    /// The mode does not select a signal on the pad
+    IllegalMode,
+    /// The pad was already claimed
+    PadTaken(Claim),
+    /// The signal is already routed to another pad
+    SignalRouted(Pad),
+    /// The signal is not available on the pad
+    UnknownSignal,
+}
+
+impl From<PadError> for PinError {
+    fn from(err: PadError) -> Self {
+        match err {
+            PadError::IllegalMode => PinError::IllegalMode,
+        }
+    }
+}
+
+pub struct PinManager {
+    control: Control,
+    claims: [Option<Claim>; CONF_NUM],
+}
+
+impl PinManager {
+    pub fn new(control: Control) -> Self {
+        PinManager {
+            control,
+            claims: [None; CONF_NUM],
+        }
+    }
+    /// Gives back the control module, all claims are forgotten
+    pub fn release_all(self) -> Control {
+        self.control
+    }
+    /// Returns the claim of a pad
+    pub fn claim_of(&self, pad: Pad) -> Option<Claim> {
+        self.claims[pad.index()]
+    }
+    /// Returns the pad a signal is routed to, the first one if it is shared
+    pub fn pad_of(&self, signal: &str) -> Option<Pad> {
+        self.claims
+            .iter()
+            .position(|claim| match claim {
+                Some(claim) => claim.signal == signal,
+                None => false,
+            })
+            .and_then(Pad::from_index)
+    }
+    /// Claim a pad and configure it
+    ///
+    /// Fails if the pad is already claimed or the signal selected by the mode
+    /// is already routed to another pad.
+    pub fn claim(
+        &mut self,
+        pad: Pad,
+        config: PadConfig,
+        owner: &'static str,
+    ) -> Result<(), PinError> {
+        self.claim_with(pad, config, owner, false)
+    }
+    /// Claim a pad for an output signal that may drive several pads, e.g.
+    /// `ehrpwm0_synco`
+    ///
+    /// Fails if the pad is already claimed or the signal is routed to another
+    /// pad by `claim`.
+    pub fn claim_shared(
+        &mut self,
+        pad: Pad,
+        config: PadConfig,
+        owner: &'static str,
+    ) -> Result<(), PinError> {
+        self.claim_with(pad, config, owner, true)
+    }
+    fn claim_with(
+        &mut self,
+        pad: Pad,
+        config: PadConfig,
+        owner: &'static str,
+        shared: bool,
+    ) -> Result<(), PinError> {
+        let signal = pad.signal(config.mode()).ok_or(PinError::IllegalMode)?;
+        if let Some(claim) = self.claims[pad.index()] {
+            return Err(PinError::PadTaken(claim));
+        }
+        let conflict = self.claims.iter().position(|claim| match claim {
+            Some(claim) => claim.signal == signal && !(shared && claim.shared),
+            None => false,
+        });
+        if let Some(other) = conflict.and_then(Pad::from_index) {
+            return Err(PinError::SignalRouted(other));
+        }
+        self.control.configure(pad, config)?;
+        self.claims[pad.index()] = Some(Claim {
+            owner,
+            signal,
+            shared,
+        });
+        Ok(())
+    }
+    /// Claim a pad for a signal, the mode is looked up from the pad
+    ///
+    /// Only the pull, receiver and slew settings of the config are used.
+    pub fn claim_signal(
+        &mut self,
+        pad: Pad,
+        signal: &str,
+        config: PadConfig,
+        owner: &'static str,
+    ) -> Result<(), PinError> {
+        let (mode, _) = pad
+            .modes()
+            .find(|&(_, other)| other == signal)
+            .ok_or(PinError::UnknownSignal)?;
+        self.claim(pad, config.with_mode(mode), owner)
+    }
+    /// Release a pad, its configuration stays unchanged
+    pub fn release(&mut self, pad: Pad) -> Option<Claim> {
+        self.claims[pad.index()].take()
+    }
+    /// Print the current configuration of every pad together with its claim
+    pub fn dump_mux_table<T: fmt::Write>(&self, writer: &mut T) -> fmt::Result {
+        writeln!(writer, "Dumping pin multiplexer:")?;
+        for &pad in PADS.iter() {
+            let config = self.control.config(pad);
+            let pull = match config.pull() {
+                Pull::Up => "up",
+                Pull::Down => "down",
+                Pull::Disabled => "none",
+            };
+            let slew = match config.slew() {
+                Slew::Fast => "fast",
+                Slew::Slow => "slow",
+            };
+            write!(
+                writer,
+                "{:#x} {:?}: mode {} ({}), pull {}, rx {}, slew {}",
+                pad.offset(),
+                pad,
+                config.mode() as u8,
+                pad.signal(config.mode()).unwrap_or("reserved"),
+                pull,
+                config.receiver(),
+                slew
+            )?;
+            match self.claims[pad.index()] {
+                Some(claim) => {
+                    writeln!(writer, ", claimed by {} for {}", claim.owner, claim.signal)?
+                }
+                None => writeln!(writer)?,
+            }
+        }
+        Ok(())
+    }
+}
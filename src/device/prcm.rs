@@ -2,7 +2,6 @@
 //!
 
 use armv7::VirtualAddress;
-use core::arch::arm;
 use register::{mmio::*, register_bitfields, Field};
 
 register_bitfields! {
@@ -0,0 +1,122 @@
+//! The PWM subsystems (PWMSS)
+//!
+//! Each of the three subsystems contains an eCAP, an eQEP and an ePWM module.
+//! The subsystem configuration gates the clocks of the modules. The
+//! time-base clock of the ePWM is additionally gated in the control module.
+
+// Author: Moritz Doll
+// License: MIT
+
+use armv7::VirtualAddress;
+use core::hint;
+use register::{mmio::*, register_bitfields, Field};
+
+register_bitfields! {
+    u32,
+    CLKCONFIG [
+        ECAPCLK_EN OFFSET(0) NUMBITS(1) [],
+        ECAPCLKSTOP_REQ OFFSET(1) NUMBITS(1) [],
+        EQEPCLK_EN OFFSET(4) NUMBITS(1) [],
+        EQEPCLKSTOP_REQ OFFSET(5) NUMBITS(1) [],
+        EPWMCLK_EN OFFSET(8) NUMBITS(1) [],
+        EPWMCLKSTOP_REQ OFFSET(9) NUMBITS(1) []
+    ],
+    CLKSTATUS [
+        ECAP_CLK_EN_ACK OFFSET(0) NUMBITS(1) [],
+        ECAP_CLKSTOP_ACK OFFSET(1) NUMBITS(1) [],
+        EQEP_CLK_EN_ACK OFFSET(4) NUMBITS(1) [],
+        EQEP_CLKSTOP_ACK OFFSET(5) NUMBITS(1) [],
+        EPWM_CLK_EN_ACK OFFSET(8) NUMBITS(1) [],
+        EPWM_CLKSTOP_ACK OFFSET(9) NUMBITS(1) []
+    ]
+}
+
+#[allow(non_snake_case)]
+#[repr(C)]
+struct RegisterBlock {
+    _IDVER: ReadOnly<u32, ()>,                      // 0x00
+    _SYSCONFIG: ReadWrite<u32, ()>,                 // 0x04
+    CLKCONFIG: ReadWrite<u32, CLKCONFIG::Register>, // 0x08
+    CLKSTATUS: ReadOnly<u32, CLKSTATUS::Register>,  // 0x0C
+}
+
+/// Offset of the eCAP module from the subsystem
+pub const ECAP_OFFSET: u32 = 0x100;
+/// Offset of the eQEP module from the subsystem
+pub const EQEP_OFFSET: u32 = 0x180;
+/// Offset of the ePWM module from the subsystem
+pub const EPWM_OFFSET: u32 = 0x200;
+
+/// The modules of a subsystem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Module {
+    Ecap,
+    Eqep,
+    Epwm,
+}
+
+impl Module {
+    /// Returns the enable and the stop request field
+    fn config(
+        self,
+    ) -> (
+        Field<u32, CLKCONFIG::Register>,
+        Field<u32, CLKCONFIG::Register>,
+    ) {
+        match self {
+            Module::Ecap => (CLKCONFIG::ECAPCLK_EN, CLKCONFIG::ECAPCLKSTOP_REQ),
+            Module::Eqep => (CLKCONFIG::EQEPCLK_EN, CLKCONFIG::EQEPCLKSTOP_REQ),
+            Module::Epwm => (CLKCONFIG::EPWMCLK_EN, CLKCONFIG::EPWMCLKSTOP_REQ),
+        }
+    }
+    /// Returns the enable and the stop acknowledge field
+    fn status(
+        self,
+    ) -> (
+        Field<u32, CLKSTATUS::Register>,
+        Field<u32, CLKSTATUS::Register>,
+    ) {
+        match self {
+            Module::Ecap => (CLKSTATUS::ECAP_CLK_EN_ACK, CLKSTATUS::ECAP_CLKSTOP_ACK),
+            Module::Eqep => (CLKSTATUS::EQEP_CLK_EN_ACK, CLKSTATUS::EQEP_CLKSTOP_ACK),
+            Module::Epwm => (CLKSTATUS::EPWM_CLK_EN_ACK, CLKSTATUS::EPWM_CLKSTOP_ACK),
+        }
+    }
+}
+
+pub struct Pwmss {
+    memory: &'static RegisterBlock,
+}
+
+// The ePWM, eCAP and eQEP drivers map their own modules, not the subsystem
+// configuration
+unsafe impl Send for Pwmss {}
+
+impl Pwmss {
+    pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
+        let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
+        Pwmss { memory }
+    }
+    /// Enable the clock of a module and wait for the acknowledge
+    pub fn enable_clock(&self, module: Module) {
+        let (enable, stop) = module.config();
+        let (enable_ack, _) = module.status();
+        self.memory.CLKCONFIG.modify(enable.val(1) + stop.val(0));
+        while !self.memory.CLKSTATUS.is_set(enable_ack) {
+            hint::spin_loop();
+        }
+    }
+    /// Request the clock of a module to stop and wait for the acknowledge
+    pub fn disable_clock(&self, module: Module) {
+        let (enable, stop) = module.config();
+        let (_, stop_ack) = module.status();
+        self.memory.CLKCONFIG.modify(stop.val(1) + enable.val(0));
+        while !self.memory.CLKSTATUS.is_set(stop_ack) {
+            hint::spin_loop();
+        }
+    }
+    pub fn is_clock_enabled(&self, module: Module) -> bool {
+        let (enable_ack, _) = module.status();
+        self.memory.CLKSTATUS.is_set(enable_ack)
+    }
+}
@@ -3,8 +3,9 @@
 // Author: Moritz Doll
 // License: MIT
 
+use crate::interrupt_source::InterruptSource;
 use armv7::VirtualAddress;
-use core::arch::arm;
+use core::hint;
 use register::{mmio::*, register_bitfields, Field};
 
 register_bitfields! {
@@ -51,7 +52,7 @@ struct RegisterBlock {
     _IRQENABLE_CLR: ReadWrite<u32, MODE::Register>, // 0x30
     IRQWAKEEN: ReadWrite<u32, MODE::Register>,      // 0x34
     TCLR: ReadWrite<u32, TCLR::Register>,           // 0x38
-    _TCRR: ReadWrite<u32, ()>,                      // 0x3C
+    TCRR: ReadWrite<u32, ()>,                       // 0x3C
     TLDR: ReadWrite<u32, ()>,                       // 0x40
     TTGR: ReadWrite<u32, ()>,                       // 0x44
     TWPS: ReadOnly<u32, TWPS::Register>,            // 0x48
@@ -61,10 +62,25 @@ struct RegisterBlock {
     _TCAR2: ReadWrite<u32, ()>,                     // 0x58
 }
 
+/// Interrupts of TIMER0 to TIMER7
+pub const INTERRUPTS: [InterruptSource; 8] = [
+    InterruptSource::Timer0,
+    InterruptSource::Timer1,
+    InterruptSource::Timer2,
+    InterruptSource::Timer3,
+    InterruptSource::Timer4,
+    InterruptSource::Timer5,
+    InterruptSource::Timer6,
+    InterruptSource::Timer7,
+];
+
 pub struct Timer {
     memory: &'static RegisterBlock,
 }
 
+// The Monotonic takes the Timer by value instead of mapping the registers again
+unsafe impl Send for Timer {}
+
 impl Timer {
     /// Creates a new timer
     ///
@@ -117,7 +133,7 @@ impl Timer {
             if !self.memory.TWPS.is_set(reg) {
                 break;
             }
-            unsafe { arm::__nop() };
+            hint::spin_loop();
         }
     }
     /// Set the raw status bit for the overflow interrupt
@@ -132,4 +148,55 @@ impl Timer {
     pub fn clear_overflow_irq(&self) {
         self.memory.IRQSTATUS.write(MODE::OVERFLOW::Enable);
     }
+    /// Read the counter
+    pub fn counter(&self) -> u32 {
+        self.memory.TCRR.get()
+    }
+}
+
+/// A monotonic clock on a free running timer
+///
+/// The counter wraps after 2^32 ticks, so the clock has to be read at least
+/// once in that time, e.g. in the overflow interrupt.
+pub struct Monotonic {
+    timer: Timer,
+    hz: u32,
+    last: u32,
+    ticks: u64,
+}
+
+impl Monotonic {
+    /// Start the timer over its full range
+    ///
+    /// `hz` is the frequency of the functional clock of the timer.
+    pub fn new(timer: Timer, hz: u32) -> Self {
+        timer.init(0xffff_ffff);
+        let last = timer.counter();
+        Monotonic {
+            timer,
+            hz,
+            last,
+            ticks: 0,
+        }
+    }
+    /// Returns the ticks since the start
+    pub fn ticks(&mut self) -> u64 {
+        let counter = self.timer.counter();
+        self.ticks += u64::from(counter.wrapping_sub(self.last));
+        self.last = counter;
+        self.ticks
+    }
+    /// Returns the microseconds since the start
+    pub fn micros(&mut self) -> u64 {
+        let ticks = self.ticks();
+        let hz = u64::from(self.hz);
+        ticks / hz * 1_000_000 + ticks % hz * 1_000_000 / hz
+    }
+    /// Returns the milliseconds since the start
+    pub fn millis(&mut self) -> u64 {
+        self.micros() / 1000
+    }
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
 }
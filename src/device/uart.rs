@@ -1,9 +1,12 @@
 //! The UART devices
 
 use crate::device::console;
-use armv7::VirtualAddress;
-use core::arch::arm;
+use crate::device::dma::{DmaMemory, ReadBuffer, Transfer, WriteBuffer};
+use crate::device::edma::{Channel, Edma};
+use crate::interrupt_source::InterruptSource;
+use armv7::{PhysicalAddress, VirtualAddress};
 use core::fmt;
+use core::hint;
 use register::{mmio::*, register_bitfields, Field};
 
 register_bitfields! {
@@ -40,6 +43,13 @@ register_bitfields! {
         ],
         FCR_MIRROR OFFSET(0) NUMBITS(2) []
     ],
+    FCR [
+        FIFO_EN OFFSET(0) NUMBITS(1) [],
+        RX_FIFO_CLEAR OFFSET(1) NUMBITS(1) [],
+        TX_FIFO_CLEAR OFFSET(2) NUMBITS(1) [],
+        TX_FIFO_TRIG OFFSET(4) NUMBITS(2) [],
+        RX_FIFO_TRIG OFFSET(6) NUMBITS(2) []
+    ],
     EFR [
         ENHANCED OFFSET(4) NUMBITS(1) [Enable = 1, Disable = 0]
     ],
@@ -98,7 +108,15 @@ register_bitfields! {
         PARITYTYPE OFFSET(6) NUMBITS(2) []
     ],
     SCR [
-        DMAMODECTL OFFSET(0) NUMBITS(1) []
+        DMAMODECTL OFFSET(0) NUMBITS(1) [],
+        DMAMODE2 OFFSET(1) NUMBITS(2) [
+            Disabled = 0,
+            Mode1 = 1,
+            Mode2 = 2,
+            Mode3 = 3
+        ],
+        TXTRIGGRANU1 OFFSET(6) NUMBITS(1) [],
+        RXTRIGGRANU1 OFFSET(7) NUMBITS(1) []
     ],
     SSR [
         TXFIFOFULL OFFSET(0) NUMBITS(1) [Full = 0b1]
@@ -123,6 +141,9 @@ register_bitfields! {
     ]
 }
 
+/// Offset of THR and RHR, the registers the DMA moves the data through
+const DATA_OFFSET: u32 = 0x00;
+
 #[allow(non_snake_case)]
 #[repr(C)]
 struct RegisterBlock {
@@ -171,6 +192,21 @@ struct RegisterBlockConfigB {
     SYSS: ReadOnly<u32, SYSS::Register>,  // 0x58
 }
 
+/// Interrupts of UART0 to UART5
+pub const INTERRUPTS: [InterruptSource; 6] = [
+    InterruptSource::Uart0,
+    InterruptSource::Uart1,
+    InterruptSource::Uart2,
+    InterruptSource::Uart3,
+    InterruptSource::Uart4,
+    InterruptSource::Uart5,
+];
+
+/// DMA events (transmit, receive) of UART0 to UART2
+///
+/// The events of UART3 to UART5 are only reachable through the event crossbar.
+pub const DMA_EVENTS: [(u8, u8); 3] = [(26, 27), (28, 29), (30, 31)];
+
 pub enum BaudRate {
     Baud115200,
 }
@@ -218,7 +254,7 @@ impl Uart {
             if self.memory.SYSS.is_set(SYSS::RESETDONE) {
                 break;
             }
-            arm::__nop();
+            hint::spin_loop();
         }
     }
     pub fn disable_irq(&self) {
@@ -283,7 +319,7 @@ impl Uart {
             if !self.memory.SSR.is_set(reg) {
                 break;
             }
-            unsafe { arm::__nop() };
+            hint::spin_loop();
         }
     }
     pub fn flush_txfifo(&self) {
@@ -294,15 +330,75 @@ impl Uart {
         self.wait(SSR::TXFIFOFULL);
         self.memory.DATA.set(c as u32);
     }
+
+    /// Enable the FIFOs and the DMA requests
+    ///
+    /// Every free byte in the transmit FIFO and every received byte raises a
+    /// DMA event. Has to be called before `write_dma` and `read_dma`.
+    pub fn enable_dma(&self) {
+        self.memory.SCR.write(
+            SCR::DMAMODECTL::SET
+                + SCR::DMAMODE2::Mode1
+                + SCR::TXTRIGGRANU1::SET
+                + SCR::RXTRIGGRANU1::SET,
+        );
+        self.memory.TLR.set(0);
+        // Writes FCR, the trigger levels are one byte
+        self.memory.IIR.set(u32::from(
+            FCR::FIFO_EN::SET
+                + FCR::RX_FIFO_CLEAR::SET
+                + FCR::TX_FIFO_CLEAR::SET
+                + FCR::TX_FIFO_TRIG.val(1)
+                + FCR::RX_FIFO_TRIG.val(1),
+        ));
+    }
+    /// Disable the DMA requests
+    pub fn disable_dma(&self) {
+        self.memory.SCR.write(SCR::DMAMODE2::Disabled);
+    }
+    /// Send the buffer with the transmit channel of the UART
+    ///
+    /// `uart` is the physical address of the UART, e.g. `memory_map::UART0`.
+    /// Gives the buffer back if it is empty or too long for a single transfer.
+    pub fn write_dma<B: ReadBuffer, M: DmaMemory>(
+        &self,
+        edma: &Edma,
+        channel: Channel,
+        buffer: B,
+        uart: PhysicalAddress,
+        memory: &M,
+    ) -> Result<Transfer<B>, (B, Channel)> {
+        let data = uart.as_u32() + DATA_OFFSET;
+        Transfer::memory_to_peripheral(edma, channel, buffer, data, memory)
+    }
+    /// Fill the buffer with the receive channel of the UART
+    ///
+    /// `uart` is the physical address of the UART, e.g. `memory_map::UART0`.
+    /// Gives the buffer back if it is empty or too long for a single transfer.
+    pub fn read_dma<B: WriteBuffer, M: DmaMemory>(
+        &self,
+        edma: &Edma,
+        channel: Channel,
+        buffer: B,
+        uart: PhysicalAddress,
+        memory: &M,
+    ) -> Result<Transfer<B>, (B, Channel)> {
+        let data = uart.as_u32() + DATA_OFFSET;
+        Transfer::peripheral_to_memory(edma, channel, buffer, data, memory)
+    }
 }
 
+// UartConfigB consumes the Uart, so the two register layouts are never in use
+// at the same time
+unsafe impl Send for Uart {}
+
 impl console::Console for Uart {
     fn getc(&self) -> char {
         loop {
             if self.memory.LSR.is_set(LSR::RXFIFOE) {
                 break;
             }
-            unsafe { arm::__nop() };
+            hint::spin_loop();
         }
         let mut ret = self.memory.DATA.get() as u8 as char;
         if ret == '\r' {
@@ -3,8 +3,9 @@
 // Author: Moritz Doll
 // License: MIT
 
+use crate::interrupt_source::InterruptSource;
 use armv7::VirtualAddress;
-use core::arch::arm;
+use core::hint;
 use register::{mmio::*, register_bitfields, Field};
 
 register_bitfields! {
@@ -48,11 +49,17 @@ struct RegisterBlock {
     WIRQENCLR: ReadWrite<u32, WDT_WIRQ::Register>,   // 0x60
 }
 
+/// Interrupt of the watchdog
+pub const INTERRUPT: InterruptSource = InterruptSource::Watchdog1;
+
 pub struct Watchdog {
     memory: &'static RegisterBlock,
     counter: u32,
 }
 
+// The trigger value is kept next to the registers, nothing else writes WTGR
+unsafe impl Send for Watchdog {}
+
 impl Watchdog {
     pub unsafe fn new(memory_addr: VirtualAddress) -> Self {
         let memory = &*(memory_addr.as_u32() as *mut RegisterBlock);
@@ -66,7 +73,7 @@ impl Watchdog {
             if !self.memory.WWPS.is_set(reg) {
                 break;
             }
-            unsafe { arm::__nop() };
+            hint::spin_loop();
         }
     }
 
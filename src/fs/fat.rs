@@ -0,0 +1,1194 @@
+//! FAT16 and FAT32 file systems
+//!
+//! Files are looked up by their long or short name, case insensitive. New
+//! files and directories get a short 8.3 name only, a name that is all lower
+//! case is shown like that by other systems. The file system keeps one sector
+//! in a cache, so `flush` or `close` have to be called before the card is
+//! removed.
+//!
+//! The free cluster count of FAT32 is not tracked and marked as unknown after
+//! the first change.
+
+// Author: Moritz Doll
+// License: MIT
+
+use super::partition::Partition;
+use super::{le16, le32, set_le16, set_le32, Error};
+use crate::device::block::{BlockDevice, BLOCK_SIZE};
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+pub const ATTR_HIDDEN: u8 = 0x02;
+pub const ATTR_SYSTEM: u8 = 0x04;
+pub const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+const ENTRY_SIZE: usize = 32;
+const ENTRIES_PER_SECTOR: u32 = (BLOCK_SIZE / ENTRY_SIZE) as u32;
+const DELETED: u8 = 0xe5;
+/// Flags of the reserved byte of Windows for a lower case name and extension
+const CASE_BASE: u8 = 0x08;
+const CASE_EXTENSION: u8 = 0x10;
+/// Positions of the 13 characters of a long name entry
+const LONG_NAME_OFFSETS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const LONG_NAME_ENTRIES: usize = 20;
+/// Longest long name in UTF-16 code units
+pub const MAX_NAME: usize = 255;
+/// 1980-01-01, there is no clock
+const DATE: u16 = 1 << 5 | 1;
+const FS_INFO_LEAD: u32 = 0x4161_5252;
+const FS_INFO_STRUCT: u32 = 0x6141_7272;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    /// Returns the end of chain marker
+    fn end(self) -> u32 {
+        match self {
+            FatType::Fat16 => 0xffff,
+            FatType::Fat32 => 0x0fff_ffff,
+        }
+    }
+    fn is_end(self, value: u32) -> bool {
+        value >= self.end() - 7
+    }
+}
+
+/// Position in a directory, cluster 0 is the root directory of FAT16
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Cursor {
+    cluster: u32,
+    index: u32,
+}
+
+/// An entry of a directory
+#[derive(Clone)]
+pub struct DirEntry {
+    short_name: [u8; 12],
+    short_len: usize,
+    long_name: [u16; LONG_NAME_ENTRIES * 13],
+    long_len: usize,
+    attributes: u8,
+    cluster: u32,
+    size: u32,
+    lba: u64,
+    offset: usize,
+    long_start: Cursor,
+    long_entries: u32,
+}
+
+impl DirEntry {
+    fn new() -> Self {
+        DirEntry {
+            short_name: [0; 12],
+            short_len: 0,
+            long_name: [0; LONG_NAME_ENTRIES * 13],
+            long_len: 0,
+            attributes: 0,
+            cluster: 0,
+            size: 0,
+            lba: 0,
+            offset: 0,
+            long_start: Cursor::default(),
+            long_entries: 0,
+        }
+    }
+    /// Returns the 8.3 name, e.g. `README.TXT`
+    ///
+    /// Characters outside of ASCII are replaced by `_`.
+    pub fn short_name(&self) -> &str {
+        core::str::from_utf8(&self.short_name[..self.short_len]).unwrap_or("")
+    }
+    /// Returns the long name in UTF-16, None if there is none
+    pub fn long_name(&self) -> Option<&[u16]> {
+        if self.long_len == 0 {
+            None
+        } else {
+            Some(&self.long_name[..self.long_len])
+        }
+    }
+    /// Returns whether the long or the short name match, case insensitive
+    pub fn matches(&self, name: &str) -> bool {
+        fn fold(c: u16) -> u16 {
+            if (u16::from(b'A')..=u16::from(b'Z')).contains(&c) {
+                c + 0x20
+            } else {
+                c
+            }
+        }
+        if let Some(long_name) = self.long_name() {
+            if name
+                .encode_utf16()
+                .map(fold)
+                .eq(long_name.iter().map(|&c| fold(c)))
+            {
+                return true;
+            }
+        }
+        name.eq_ignore_ascii_case(self.short_name())
+    }
+    pub fn attributes(&self) -> u8 {
+        self.attributes
+    }
+    pub fn is_dir(&self) -> bool {
+        self.attributes & ATTR_DIRECTORY != 0
+    }
+    /// Returns the size of a file in bytes
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    fn is_dot(&self) -> bool {
+        self.short_name() == "." || self.short_name() == ".."
+    }
+}
+
+/// An open directory, the entries are read with `FileSystem::read_dir`
+pub struct Dir {
+    cursor: Cursor,
+    end: bool,
+}
+
+impl Dir {
+    fn new(cluster: u32) -> Self {
+        Dir {
+            cursor: Cursor { cluster, index: 0 },
+            end: false,
+        }
+    }
+}
+
+/// An open file, it is read and written through the `FileSystem`
+pub struct File {
+    lba: u64,
+    offset: usize,
+    first_cluster: u32,
+    size: u32,
+    position: u32,
+    /// The cluster with the index in the chain, 0 if not looked up yet
+    cluster: u32,
+    cluster_index: u32,
+    dirty: bool,
+}
+
+impl File {
+    fn new(entry: &DirEntry) -> Self {
+        File {
+            lba: entry.lba,
+            offset: entry.offset,
+            first_cluster: entry.cluster,
+            size: entry.size,
+            position: 0,
+            cluster: 0,
+            cluster_index: 0,
+            dirty: false,
+        }
+    }
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+    /// Move to a position, at most the end of the file
+    pub fn seek(&mut self, position: u32) {
+        self.position = position.min(self.size);
+    }
+}
+
+struct Cache {
+    lba: Option<u64>,
+    dirty: bool,
+    data: [u8; BLOCK_SIZE],
+}
+
+pub struct FileSystem<D: BlockDevice> {
+    device: D,
+    /// First block of the volume on the device
+    start: u64,
+    fat_type: FatType,
+    sectors_per_cluster: u32,
+    fat_start: u32,
+    fat_size: u32,
+    fats: u32,
+    root_start: u32,
+    root_entries: u32,
+    root_cluster: u32,
+    data_start: u32,
+    clusters: u32,
+    fs_info: Option<u32>,
+    fs_info_dirty: bool,
+    next_free: u32,
+    cache: Cache,
+}
+
+/// Returns the 8.3 name and the case flags of a name, None if it has none
+fn short_name(name: &str) -> Option<([u8; 11], u8)> {
+    const SPECIAL: &[u8] = b"!#$%&'()-@^_`{}~";
+    let (base, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], &name[dot + 1..]),
+        _ => (name, ""),
+    };
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+    let valid = |part: &str| {
+        part.bytes()
+            .all(|c| c.is_ascii_alphanumeric() || SPECIAL.contains(&c))
+    };
+    if !valid(base) || !valid(extension) {
+        return None;
+    }
+    let lower = |part: &str| {
+        part.bytes().any(|c| c.is_ascii_lowercase())
+            && !part.bytes().any(|c| c.is_ascii_uppercase())
+    };
+    let mut case = 0;
+    if lower(base) {
+        case |= CASE_BASE;
+    }
+    if lower(extension) {
+        case |= CASE_EXTENSION;
+    }
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    short.make_ascii_uppercase();
+    Some((short, case))
+}
+
+/// Returns the checksum of a short name that the long name entries repeat
+fn checksum(short: &[u8]) -> u8 {
+    short
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Returns the directory and the name of a path
+fn split(path: &str) -> (&str, &str) {
+    let path = path.trim_end_matches('/');
+    match path.rfind('/') {
+        Some(slash) => (&path[..slash], &path[slash + 1..]),
+        None => ("", path),
+    }
+}
+
+fn write_entry(raw: &mut [u8], short: &[u8; 11], case: u8, attributes: u8, cluster: u32) {
+    raw.copy_from_slice(&[0; ENTRY_SIZE]);
+    raw[..11].copy_from_slice(short);
+    raw[11] = attributes;
+    raw[12] = case;
+    set_le16(raw, 16, DATE);
+    set_le16(raw, 18, DATE);
+    set_le16(raw, 24, DATE);
+    set_cluster(raw, cluster);
+}
+
+fn set_cluster(raw: &mut [u8], cluster: u32) {
+    set_le16(raw, 20, (cluster >> 16) as u16);
+    set_le16(raw, 26, cluster as u16);
+}
+
+impl<D: BlockDevice> FileSystem<D> {
+    /// Mount a device without a partition table
+    pub fn mount(device: D) -> Result<Self, Error<D::Error>> {
+        Self::mount_at(device, 0)
+    }
+    pub fn mount_partition(device: D, partition: &Partition) -> Result<Self, Error<D::Error>> {
+        Self::mount_at(device, partition.first_block)
+    }
+    fn mount_at(device: D, start: u64) -> Result<Self, Error<D::Error>> {
+        let mut fs = FileSystem {
+            device,
+            start,
+            fat_type: FatType::Fat16,
+            sectors_per_cluster: 0,
+            fat_start: 0,
+            fat_size: 0,
+            fats: 0,
+            root_start: 0,
+            root_entries: 0,
+            root_cluster: 0,
+            data_start: 0,
+            clusters: 0,
+            fs_info: None,
+            fs_info_dirty: false,
+            next_free: 2,
+            cache: Cache {
+                lba: None,
+                dirty: false,
+                data: [0; BLOCK_SIZE],
+            },
+        };
+        let boot = *fs.load(0)?;
+        if boot[510] != 0x55 || boot[511] != 0xaa || le16(&boot, 11) as usize != BLOCK_SIZE {
+            return Err(Error::Unsupported);
+        }
+        let sectors_per_cluster = u32::from(boot[13]);
+        let reserved = u32::from(le16(&boot, 14));
+        let fats = u32::from(boot[16]);
+        let root_entries = u32::from(le16(&boot, 17));
+        let total = match le16(&boot, 19) {
+            0 => le32(&boot, 32),
+            total => u32::from(total),
+        };
+        let fat_size = match le16(&boot, 22) {
+            0 => le32(&boot, 36),
+            size => u32::from(size),
+        };
+        if !sectors_per_cluster.is_power_of_two() || reserved == 0 || fats == 0 || fat_size == 0 {
+            return Err(Error::Corrupt);
+        }
+        let root_start = fat_size
+            .checked_mul(fats)
+            .and_then(|size| size.checked_add(reserved))
+            .ok_or(Error::Corrupt)?;
+        let root_sectors =
+            root_entries / ENTRIES_PER_SECTOR + u32::from(root_entries % ENTRIES_PER_SECTOR != 0);
+        let data_start = root_start + root_sectors;
+        if total <= data_start {
+            return Err(Error::Corrupt);
+        }
+        let clusters = (total - data_start) / sectors_per_cluster;
+        fs.fat_type = if clusters < 4085 {
+            return Err(Error::Unsupported);
+        } else if clusters < 65525 {
+            FatType::Fat16
+        } else {
+            FatType::Fat32
+        };
+        let entry_size = match fs.fat_type {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        };
+        if u64::from(fat_size) * BLOCK_SIZE as u64 / entry_size < u64::from(clusters) + 2 {
+            return Err(Error::Corrupt);
+        }
+        fs.sectors_per_cluster = sectors_per_cluster;
+        fs.fat_start = reserved;
+        fs.fat_size = fat_size;
+        fs.fats = fats;
+        fs.root_start = root_start;
+        fs.root_entries = root_entries;
+        fs.data_start = data_start;
+        fs.clusters = clusters;
+        if fs.fat_type == FatType::Fat32 {
+            fs.root_cluster = le32(&boot, 44);
+            if !fs.is_valid(fs.root_cluster) {
+                return Err(Error::Corrupt);
+            }
+            let fs_info = u32::from(le16(&boot, 48));
+            if fs_info != 0 && fs_info < reserved {
+                fs.fs_info = Some(fs_info);
+                let sector = fs.load(u64::from(fs_info))?;
+                if le32(sector, 0) == FS_INFO_LEAD && le32(sector, 484) == FS_INFO_STRUCT {
+                    let hint = le32(sector, 492);
+                    if fs.is_valid(hint) {
+                        fs.next_free = hint;
+                    }
+                }
+            }
+        } else if root_entries == 0 {
+            return Err(Error::Corrupt);
+        }
+        Ok(fs)
+    }
+    /// Write all changes and return the device
+    pub fn unmount(mut self) -> Result<D, Error<D::Error>> {
+        self.flush()?;
+        Ok(self.device)
+    }
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+    /// Returns the size of a cluster in bytes
+    pub fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster * BLOCK_SIZE as u32
+    }
+    /// Write the cached sector and the FAT32 info sector to the device
+    pub fn flush(&mut self) -> Result<(), Error<D::Error>> {
+        if let (Some(fs_info), true) = (self.fs_info, self.fs_info_dirty) {
+            let next_free = self.next_free;
+            let sector = self.load_mut(u64::from(fs_info))?;
+            if le32(sector, 0) == FS_INFO_LEAD && le32(sector, 484) == FS_INFO_STRUCT {
+                set_le32(sector, 488, 0xffff_ffff);
+                set_le32(sector, 492, next_free);
+            }
+            self.fs_info_dirty = false;
+        }
+        self.flush_cache()
+    }
+    fn flush_cache(&mut self) -> Result<(), Error<D::Error>> {
+        if let (Some(lba), true) = (self.cache.lba, self.cache.dirty) {
+            self.device
+                .write_blocks(self.start + lba, &self.cache.data)
+                .map_err(Error::Device)?;
+            self.cache.dirty = false;
+        }
+        Ok(())
+    }
+    /// Returns a sector of the volume in the cache
+    fn load(&mut self, lba: u64) -> Result<&mut [u8; BLOCK_SIZE], Error<D::Error>> {
+        if self.cache.lba != Some(lba) {
+            self.flush_cache()?;
+            self.cache.lba = None;
+            self.device
+                .read_blocks(self.start + lba, &mut self.cache.data)
+                .map_err(Error::Device)?;
+            self.cache.lba = Some(lba);
+        }
+        Ok(&mut self.cache.data)
+    }
+    /// Returns a sector in the cache that is written back
+    fn load_mut(&mut self, lba: u64) -> Result<&mut [u8; BLOCK_SIZE], Error<D::Error>> {
+        self.load(lba)?;
+        self.cache.dirty = true;
+        Ok(&mut self.cache.data)
+    }
+    /// Returns a sector in the cache without reading it, the whole sector has
+    /// to be written
+    fn overwrite(&mut self, lba: u64) -> Result<&mut [u8; BLOCK_SIZE], Error<D::Error>> {
+        if self.cache.lba != Some(lba) {
+            self.flush_cache()?;
+            self.cache.lba = Some(lba);
+        }
+        self.cache.dirty = true;
+        Ok(&mut self.cache.data)
+    }
+    fn is_valid(&self, cluster: u32) -> bool {
+        cluster >= 2 && cluster - 2 < self.clusters
+    }
+    fn cluster_lba(&self, cluster: u32) -> u64 {
+        u64::from(self.data_start) + u64::from(cluster - 2) * u64::from(self.sectors_per_cluster)
+    }
+    /// Returns the first sector and the offset of the entry of a cluster in
+    /// the first FAT
+    fn fat_position(&self, cluster: u32) -> (u64, usize) {
+        let offset = match self.fat_type {
+            FatType::Fat16 => cluster * 2,
+            FatType::Fat32 => cluster * 4,
+        };
+        let lba = self.fat_start + offset / BLOCK_SIZE as u32;
+        (u64::from(lba), offset as usize % BLOCK_SIZE)
+    }
+    fn fat_entry(&mut self, cluster: u32) -> Result<u32, Error<D::Error>> {
+        let (lba, offset) = self.fat_position(cluster);
+        let fat_type = self.fat_type;
+        let sector = self.load(lba)?;
+        Ok(match fat_type {
+            FatType::Fat16 => u32::from(le16(sector, offset)),
+            FatType::Fat32 => le32(sector, offset) & 0x0fff_ffff,
+        })
+    }
+    /// Set the entry of a cluster in all FATs
+    fn set_fat_entry(&mut self, cluster: u32, value: u32) -> Result<(), Error<D::Error>> {
+        let (lba, offset) = self.fat_position(cluster);
+        let fat_type = self.fat_type;
+        for fat in 0..self.fats {
+            let sector = self.load_mut(lba + u64::from(fat * self.fat_size))?;
+            match fat_type {
+                FatType::Fat16 => set_le16(sector, offset, value as u16),
+                FatType::Fat32 => {
+                    // The upper 4 bits are reserved
+                    let reserved = le32(sector, offset) & 0xf000_0000;
+                    set_le32(sector, offset, reserved | value & 0x0fff_ffff);
+                }
+            }
+        }
+        self.fs_info_dirty = true;
+        Ok(())
+    }
+    /// Returns the next cluster of a chain, None at its end
+    fn next_cluster(&mut self, cluster: u32) -> Result<Option<u32>, Error<D::Error>> {
+        let next = self.fat_entry(cluster)?;
+        if self.fat_type.is_end(next) {
+            Ok(None)
+        } else if self.is_valid(next) {
+            Ok(Some(next))
+        } else {
+            Err(Error::Corrupt)
+        }
+    }
+    /// Allocate a cluster and append it to the chain of `previous`
+    fn allocate(&mut self, previous: Option<u32>) -> Result<u32, Error<D::Error>> {
+        for i in 0..self.clusters {
+            let cluster = 2 + (self.next_free - 2 + i) % self.clusters;
+            if self.fat_entry(cluster)? == 0 {
+                self.set_fat_entry(cluster, self.fat_type.end())?;
+                if let Some(previous) = previous {
+                    self.set_fat_entry(previous, cluster)?;
+                }
+                self.next_free = if self.is_valid(cluster + 1) {
+                    cluster + 1
+                } else {
+                    2
+                };
+                return Ok(cluster);
+            }
+        }
+        Err(Error::Full)
+    }
+    fn free_chain(&mut self, first: u32) -> Result<(), Error<D::Error>> {
+        let mut cluster = Some(first);
+        let mut count = 0;
+        while let Some(current) = cluster {
+            // A loop in the chain
+            if count > self.clusters {
+                return Err(Error::Corrupt);
+            }
+            cluster = self.next_cluster(current)?;
+            self.set_fat_entry(current, 0)?;
+            count += 1;
+        }
+        Ok(())
+    }
+    fn zero_cluster(&mut self, cluster: u32) -> Result<(), Error<D::Error>> {
+        let lba = self.cluster_lba(cluster);
+        for sector in 0..u64::from(self.sectors_per_cluster) {
+            *self.overwrite(lba + sector)? = [0; BLOCK_SIZE];
+        }
+        Ok(())
+    }
+    fn root(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => 0,
+            FatType::Fat32 => self.root_cluster,
+        }
+    }
+    fn entry_position(&self, cursor: Cursor) -> (u64, usize) {
+        let lba = if cursor.cluster == 0 {
+            u64::from(self.root_start)
+        } else {
+            self.cluster_lba(cursor.cluster)
+        };
+        let lba = lba + u64::from(cursor.index / ENTRIES_PER_SECTOR);
+        (
+            lba,
+            (cursor.index % ENTRIES_PER_SECTOR) as usize * ENTRY_SIZE,
+        )
+    }
+    /// Move to the next entry, returns false at the end of the directory
+    ///
+    /// The cursor stays in the last cluster at the end.
+    fn advance(&mut self, cursor: &mut Cursor) -> Result<bool, Error<D::Error>> {
+        cursor.index += 1;
+        if cursor.cluster == 0 {
+            return Ok(cursor.index < self.root_entries);
+        }
+        if cursor.index < self.sectors_per_cluster * ENTRIES_PER_SECTOR {
+            return Ok(true);
+        }
+        match self.next_cluster(cursor.cluster)? {
+            Some(next) => {
+                *cursor = Cursor {
+                    cluster: next,
+                    index: 0,
+                };
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+    /// Returns the next entry of a directory including `.` and `..`
+    fn next_entry(&mut self, dir: &mut Dir) -> Result<Option<DirEntry>, Error<D::Error>> {
+        let mut entry = DirEntry::new();
+        // Sequence number of the next long name entry and the checksum
+        let mut expected = 0;
+        let mut long_checksum = 0;
+        while !dir.end {
+            let cursor = dir.cursor;
+            let (lba, offset) = self.entry_position(cursor);
+            let mut raw = [0; ENTRY_SIZE];
+            raw.copy_from_slice(&self.load(lba)?[offset..offset + ENTRY_SIZE]);
+            dir.end = !self.advance(&mut dir.cursor)?;
+            if raw[0] == 0 {
+                dir.end = true;
+                break;
+            }
+            if raw[0] == DELETED {
+                expected = 0;
+                continue;
+            }
+            let attributes = raw[11];
+            if attributes & 0x3f == ATTR_LONG_NAME {
+                let sequence = usize::from(raw[0] & 0x1f);
+                if raw[0] & 0x40 != 0 && (1..=LONG_NAME_ENTRIES).contains(&sequence) {
+                    // The last part of the name is stored first
+                    expected = sequence;
+                    long_checksum = raw[13];
+                    entry.long_start = cursor;
+                    entry.long_entries = 0;
+                    entry.long_len = sequence * 13;
+                }
+                if expected == 0 || sequence != expected || raw[13] != long_checksum {
+                    expected = 0;
+                    entry.long_len = 0;
+                    continue;
+                }
+                for (i, &position) in LONG_NAME_OFFSETS.iter().enumerate() {
+                    let index = (sequence - 1) * 13 + i;
+                    let c = le16(&raw, position);
+                    entry.long_name[index] = c;
+                    if c == 0 && index < entry.long_len {
+                        entry.long_len = index;
+                    }
+                }
+                entry.long_entries += 1;
+                expected -= 1;
+                continue;
+            }
+            if attributes & ATTR_VOLUME_ID != 0 {
+                expected = 0;
+                entry.long_len = 0;
+                continue;
+            }
+            let has_long_name = entry.long_entries > 0
+                && expected == 0
+                && entry.long_len > 0
+                && entry.long_len <= MAX_NAME
+                && checksum(&raw[..11]) == long_checksum;
+            if !has_long_name {
+                entry.long_len = 0;
+                entry.long_entries = 0;
+            }
+            let mut len = 0;
+            for (i, &c) in raw[..11].iter().enumerate() {
+                if i == 8 && raw[8] != b' ' {
+                    entry.short_name[len] = b'.';
+                    len += 1;
+                }
+                let lower = if i < 8 {
+                    raw[12] & CASE_BASE != 0
+                } else {
+                    raw[12] & CASE_EXTENSION != 0
+                };
+                let c = match c {
+                    b' ' => continue,
+                    0x05 if i == 0 => b'_',
+                    c if !c.is_ascii() => b'_',
+                    c if lower => c.to_ascii_lowercase(),
+                    c => c,
+                };
+                entry.short_name[len] = c;
+                len += 1;
+            }
+            entry.short_len = len;
+            entry.attributes = attributes;
+            entry.cluster = u32::from(le16(&raw, 26));
+            if self.fat_type == FatType::Fat32 {
+                entry.cluster |= u32::from(le16(&raw, 20)) << 16;
+            }
+            entry.size = le32(&raw, 28);
+            entry.lba = lba;
+            entry.offset = offset;
+            return Ok(Some(entry));
+        }
+        Ok(None)
+    }
+    fn find(&mut self, dir: u32, name: &str) -> Result<Option<DirEntry>, Error<D::Error>> {
+        let mut dir = Dir::new(dir);
+        while let Some(entry) = self.next_entry(&mut dir)? {
+            if entry.matches(name) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+    /// Returns the first cluster of the directory of a path
+    fn directory(&mut self, path: &str) -> Result<u32, Error<D::Error>> {
+        let mut cluster = self.root();
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            let entry = self.find(cluster, name)?.ok_or(Error::NotFound)?;
+            if !entry.is_dir() {
+                return Err(Error::NotADirectory);
+            }
+            // `..` of a directory in the root directory
+            cluster = if entry.cluster == 0 {
+                self.root()
+            } else {
+                entry.cluster
+            };
+        }
+        Ok(cluster)
+    }
+    /// Returns a free entry of a directory, appends a cluster if there is none
+    fn free_entry(&mut self, dir: u32) -> Result<(u64, usize), Error<D::Error>> {
+        let mut cursor = Cursor {
+            cluster: dir,
+            index: 0,
+        };
+        loop {
+            let (lba, offset) = self.entry_position(cursor);
+            let first = self.load(lba)?[offset];
+            if first == 0 || first == DELETED {
+                return Ok((lba, offset));
+            }
+            if !self.advance(&mut cursor)? {
+                if dir == 0 {
+                    return Err(Error::Full);
+                }
+                let cluster = self.allocate(Some(cursor.cluster))?;
+                self.zero_cluster(cluster)?;
+                return Ok((self.cluster_lba(cluster), 0));
+            }
+        }
+    }
+    fn add_entry(
+        &mut self,
+        dir: u32,
+        name: &str,
+        attributes: u8,
+        cluster: u32,
+    ) -> Result<DirEntry, Error<D::Error>> {
+        let (short, case) = short_name(name).ok_or(Error::InvalidName)?;
+        let (lba, offset) = self.free_entry(dir)?;
+        let sector = self.load_mut(lba)?;
+        write_entry(
+            &mut sector[offset..offset + ENTRY_SIZE],
+            &short,
+            case,
+            attributes,
+            cluster,
+        );
+        let mut entry = DirEntry::new();
+        entry.attributes = attributes;
+        entry.cluster = cluster;
+        entry.lba = lba;
+        entry.offset = offset;
+        Ok(entry)
+    }
+    /// Open a directory to list its entries
+    pub fn open_dir(&mut self, path: &str) -> Result<Dir, Error<D::Error>> {
+        Ok(Dir::new(self.directory(path)?))
+    }
+    /// Returns the next entry of a directory, without `.`, `..` and the
+    /// volume label
+    pub fn read_dir(&mut self, dir: &mut Dir) -> Result<Option<DirEntry>, Error<D::Error>> {
+        while let Some(entry) = self.next_entry(dir)? {
+            if !entry.is_dot() {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+    /// Returns the entry of a file or directory
+    pub fn metadata(&mut self, path: &str) -> Result<DirEntry, Error<D::Error>> {
+        let (dir, name) = split(path);
+        if name.is_empty() {
+            return Err(Error::InvalidName);
+        }
+        let dir = self.directory(dir)?;
+        self.find(dir, name)?.ok_or(Error::NotFound)
+    }
+    /// Open a file at its start
+    pub fn open(&mut self, path: &str) -> Result<File, Error<D::Error>> {
+        let entry = self.metadata(path)?;
+        if entry.is_dir() {
+            return Err(Error::IsADirectory);
+        }
+        Ok(File::new(&entry))
+    }
+    /// Create a file or truncate an existing one
+    ///
+    /// New files need an 8.3 name.
+    pub fn create(&mut self, path: &str) -> Result<File, Error<D::Error>> {
+        let (dir, name) = split(path);
+        let dir = self.directory(dir)?;
+        match self.find(dir, name)? {
+            Some(entry) if entry.is_dir() => Err(Error::IsADirectory),
+            Some(mut entry) => {
+                if entry.cluster != 0 {
+                    self.free_chain(entry.cluster)?;
+                }
+                entry.cluster = 0;
+                entry.size = 0;
+                let mut file = File::new(&entry);
+                file.dirty = true;
+                self.flush_file(&mut file)?;
+                Ok(file)
+            }
+            None => Ok(File::new(&self.add_entry(dir, name, ATTR_ARCHIVE, 0)?)),
+        }
+    }
+    /// Open a file at its end, creates it if it does not exist
+    pub fn append(&mut self, path: &str) -> Result<File, Error<D::Error>> {
+        let mut file = match self.open(path) {
+            Err(Error::NotFound) => self.create(path)?,
+            file => file?,
+        };
+        file.position = file.size;
+        Ok(file)
+    }
+    /// Create a directory, the parent has to exist
+    pub fn create_dir(&mut self, path: &str) -> Result<(), Error<D::Error>> {
+        let (dir, name) = split(path);
+        let dir = self.directory(dir)?;
+        if short_name(name).is_none() {
+            return Err(Error::InvalidName);
+        }
+        if self.find(dir, name)?.is_some() {
+            return Err(Error::AlreadyExists);
+        }
+        let cluster = self.allocate(None)?;
+        self.zero_cluster(cluster)?;
+        // `..` of a directory in the root directory is 0, also for FAT32
+        let parent = if dir == self.root() { 0 } else { dir };
+        let lba = self.cluster_lba(cluster);
+        let sector = self.load_mut(lba)?;
+        write_entry(
+            &mut sector[..ENTRY_SIZE],
+            b".          ",
+            0,
+            ATTR_DIRECTORY,
+            cluster,
+        );
+        write_entry(
+            &mut sector[ENTRY_SIZE..2 * ENTRY_SIZE],
+            b"..         ",
+            0,
+            ATTR_DIRECTORY,
+            parent,
+        );
+        if let Err(error) = self.add_entry(dir, name, ATTR_DIRECTORY, cluster) {
+            self.free_chain(cluster)?;
+            return Err(error);
+        }
+        Ok(())
+    }
+    /// Remove a file or an empty directory
+    pub fn remove(&mut self, path: &str) -> Result<(), Error<D::Error>> {
+        let entry = self.metadata(path)?;
+        if entry.is_dot() {
+            return Err(Error::InvalidName);
+        }
+        if entry.is_dir() && self.read_dir(&mut Dir::new(entry.cluster))?.is_some() {
+            return Err(Error::DirectoryNotEmpty);
+        }
+        if entry.cluster != 0 {
+            self.free_chain(entry.cluster)?;
+        }
+        let mut cursor = entry.long_start;
+        for _ in 0..entry.long_entries {
+            let (lba, offset) = self.entry_position(cursor);
+            self.load_mut(lba)?[offset] = DELETED;
+            self.advance(&mut cursor)?;
+        }
+        self.load_mut(entry.lba)?[entry.offset] = DELETED;
+        Ok(())
+    }
+    /// Returns the cluster with an index in the chain of a file, appends
+    /// clusters if `allocate`
+    fn file_cluster(
+        &mut self,
+        file: &mut File,
+        index: u32,
+        allocate: bool,
+    ) -> Result<Option<u32>, Error<D::Error>> {
+        if file.first_cluster == 0 {
+            if !allocate {
+                return Ok(None);
+            }
+            file.first_cluster = self.allocate(None)?;
+            file.dirty = true;
+        }
+        if file.cluster == 0 || file.cluster_index > index {
+            file.cluster = file.first_cluster;
+            file.cluster_index = 0;
+        }
+        while file.cluster_index < index {
+            file.cluster = match self.next_cluster(file.cluster)? {
+                Some(next) => next,
+                None if allocate => self.allocate(Some(file.cluster))?,
+                None => return Ok(None),
+            };
+            file.cluster_index += 1;
+        }
+        Ok(Some(file.cluster))
+    }
+    /// Returns the sector and the offset in it of the position of a file
+    fn file_position(
+        &mut self,
+        file: &mut File,
+        allocate: bool,
+    ) -> Result<(u64, usize), Error<D::Error>> {
+        let cluster_size = self.cluster_size();
+        let cluster = self
+            .file_cluster(file, file.position / cluster_size, allocate)?
+            .ok_or(Error::Corrupt)?;
+        let offset = (file.position % cluster_size) as usize;
+        let lba = self.cluster_lba(cluster) + (offset / BLOCK_SIZE) as u64;
+        Ok((lba, offset % BLOCK_SIZE))
+    }
+    /// Read from the position of the file, returns the number of bytes read
+    ///
+    /// Returns 0 at the end of the file.
+    pub fn read(&mut self, file: &mut File, buffer: &mut [u8]) -> Result<usize, Error<D::Error>> {
+        let mut done = 0;
+        while done < buffer.len() && file.position < file.size {
+            let (lba, offset) = self.file_position(file, false)?;
+            let len = (BLOCK_SIZE - offset)
+                .min(buffer.len() - done)
+                .min((file.size - file.position) as usize);
+            let sector = self.load(lba)?;
+            buffer[done..done + len].copy_from_slice(&sector[offset..offset + len]);
+            done += len;
+            file.position += len as u32;
+        }
+        Ok(done)
+    }
+    /// Write at the position of the file, returns the number of bytes written
+    ///
+    /// The directory entry is only updated by `flush_file` or `close`.
+    pub fn write(&mut self, file: &mut File, data: &[u8]) -> Result<usize, Error<D::Error>> {
+        let mut done = 0;
+        while done < data.len() {
+            let (lba, offset) = self.file_position(file, true)?;
+            let len = (BLOCK_SIZE - offset)
+                .min(data.len() - done)
+                .min((u32::MAX - file.position) as usize);
+            if len == 0 {
+                return Err(Error::Full);
+            }
+            let sector = if len == BLOCK_SIZE {
+                self.overwrite(lba)?
+            } else {
+                self.load_mut(lba)?
+            };
+            sector[offset..offset + len].copy_from_slice(&data[done..done + len]);
+            done += len;
+            file.position += len as u32;
+            file.size = file.size.max(file.position);
+            file.dirty = true;
+        }
+        Ok(done)
+    }
+    /// Update the directory entry of a file and write all changes
+    pub fn flush_file(&mut self, file: &mut File) -> Result<(), Error<D::Error>> {
+        if file.dirty {
+            let sector = self.load_mut(file.lba)?;
+            let raw = &mut sector[file.offset..file.offset + ENTRY_SIZE];
+            raw[11] |= ATTR_ARCHIVE;
+            set_le16(raw, 24, DATE);
+            set_cluster(raw, file.first_cluster);
+            set_le32(raw, 28, file.size);
+            file.dirty = false;
+        }
+        self.flush()
+    }
+    pub fn close(&mut self, mut file: File) -> Result<(), Error<D::Error>> {
+        self.flush_file(&mut file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::image::{free_clusters, free_clusters_after_clear, pattern, Image};
+    use crate::fs::partition::find_fat_partition;
+    use std::io::{Read, Write};
+
+    /// A formatted partition of 24 MiB for FAT16 and 48 MiB for FAT32
+    fn image(fat32: bool, gpt: bool) -> Image {
+        let blocks = if fat32 { 48 << 11 } else { 24 << 11 };
+        let mut image = match (gpt, fat32) {
+            (true, _) => Image::gpt(blocks),
+            (false, true) => Image::mbr(blocks, 0x0c),
+            (false, false) => Image::mbr(blocks, 0x06),
+        };
+        image.format(fat32);
+        image
+    }
+
+    fn mount(image: &mut Image) -> FileSystem<&mut Image> {
+        let partition = find_fat_partition(image).unwrap().unwrap();
+        FileSystem::mount_partition(image, &partition).unwrap()
+    }
+
+    /// Read the file in pieces that are not aligned to sectors
+    fn read_all(fs: &mut FileSystem<&mut Image>, path: &str) -> Vec<u8> {
+        let mut file = fs.open(path).unwrap();
+        let mut data = Vec::new();
+        let mut buffer = [0; 777];
+        loop {
+            let len = fs.read(&mut file, &mut buffer).unwrap();
+            if len == 0 {
+                return data;
+            }
+            data.extend_from_slice(&buffer[..len]);
+        }
+    }
+
+    fn name(entry: &DirEntry) -> String {
+        match entry.long_name() {
+            Some(name) => String::from_utf16(name).unwrap(),
+            None => entry.short_name().to_string(),
+        }
+    }
+
+    #[test]
+    fn mount_fat16_and_fat32() {
+        for &(fat32, gpt) in &[(false, false), (true, false), (false, true), (true, true)] {
+            let mut image = image(fat32, gpt);
+            let fs = mount(&mut image);
+            let (fat_type, cluster_size) = if fat32 {
+                (FatType::Fat32, 512)
+            } else {
+                (FatType::Fat16, 2048)
+            };
+            assert_eq!(fs.fat_type(), fat_type);
+            assert_eq!(fs.cluster_size(), cluster_size);
+        }
+    }
+
+    #[test]
+    fn read_long_and_short_names() {
+        let mut image = image(false, false);
+        {
+            let fs = image.fatfs();
+            let root = fs.root_dir();
+            let long = "Long File Name With Spaces.txt";
+            root.create_file(long)
+                .unwrap()
+                .write_all(&pattern(10_000, 1))
+                .unwrap();
+            root.create_file("README.TXT")
+                .unwrap()
+                .write_all(b"hello")
+                .unwrap();
+            // More entries than fit into a cluster
+            root.create_dir("logs").unwrap();
+            for i in 0..40 {
+                let mut file = root
+                    .create_file(&format!("logs/entry number {}.log", i))
+                    .unwrap();
+                file.write_all(&pattern(i * 700, i as u8)).unwrap();
+            }
+        }
+        let mut fs = mount(&mut image);
+        let mut root = fs.open_dir("/").unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = fs.read_dir(&mut root).unwrap() {
+            names.push(name(&entry));
+        }
+        assert_eq!(
+            names,
+            ["Long File Name With Spaces.txt", "README.TXT", "logs"]
+        );
+        let long = read_all(&mut fs, "long file name with spaces.txt");
+        assert_eq!(long, pattern(10_000, 1));
+        let alias = fs.metadata("Long File Name With Spaces.txt").unwrap();
+        assert_eq!(alias.short_name(), "LONGFI~1.TXT");
+        assert_eq!(read_all(&mut fs, "/LONGFI~1.TXT"), long);
+        assert_eq!(read_all(&mut fs, "readme.txt"), b"hello");
+        let mut logs = fs.open_dir("logs").unwrap();
+        let mut count = 0;
+        while let Some(entry) = fs.read_dir(&mut logs).unwrap() {
+            if !entry.is_dot() {
+                count += 1;
+            }
+        }
+        assert_eq!(count, 40);
+        assert_eq!(
+            read_all(&mut fs, "logs/entry number 39.log"),
+            pattern(39 * 700, 39)
+        );
+        let mut file = fs.open("Long File Name With Spaces.txt").unwrap();
+        file.seek(4321);
+        let mut buffer = [0; 100];
+        assert_eq!(fs.read(&mut file, &mut buffer).unwrap(), 100);
+        assert_eq!(&buffer[..], &long[4321..4421]);
+        assert!(matches!(fs.open("missing.txt"), Err(Error::NotFound)));
+        assert!(matches!(fs.open("logs"), Err(Error::IsADirectory)));
+        assert!(matches!(fs.open("README.TXT/x"), Err(Error::NotADirectory)));
+    }
+
+    #[test]
+    fn write_across_clusters() {
+        let mut image = image(true, true);
+        let free = free_clusters(&mut image);
+        {
+            let fs = image.fatfs();
+            let mut config = fs.root_dir().create_file("config.toml").unwrap();
+            config.write_all(&pattern(3000, 9)).unwrap();
+        }
+        let mut expected = Vec::new();
+        {
+            let mut fs = mount(&mut image);
+            let mut log = fs.create("LOG.TXT").unwrap();
+            for i in 0..100 {
+                let data = pattern(1000 + i, i as u8);
+                assert_eq!(fs.write(&mut log, &data).unwrap(), data.len());
+                expected.extend_from_slice(&data);
+            }
+            fs.close(log).unwrap();
+            let mut log = fs.append("LOG.TXT").unwrap();
+            fs.write(&mut log, b"appended").unwrap();
+            fs.close(log).unwrap();
+            expected.extend_from_slice(b"appended");
+            // Truncating frees the clusters of the old content
+            let mut config = fs.create("config.toml").unwrap();
+            fs.write(&mut config, b"rate = 200\n").unwrap();
+            fs.close(config).unwrap();
+            assert!(matches!(
+                fs.create("has space.txt"),
+                Err(Error::InvalidName)
+            ));
+            fs.unmount().unwrap();
+        }
+        let mut fs = mount(&mut image);
+        assert_eq!(read_all(&mut fs, "log.txt"), expected);
+        assert_eq!(read_all(&mut fs, "config.toml"), b"rate = 200\n");
+        fs.unmount().unwrap();
+        {
+            let fs = image.fatfs();
+            let mut data = Vec::new();
+            let mut log = fs.root_dir().open_file("LOG.TXT").unwrap();
+            log.read_to_end(&mut data).unwrap();
+            assert_eq!(data, expected);
+        }
+        assert_eq!(free_clusters_after_clear(&mut image), free);
+    }
+
+    #[test]
+    fn create_and_remove_directories() {
+        let mut image = image(false, true);
+        let free = free_clusters(&mut image);
+        {
+            let mut fs = mount(&mut image);
+            fs.create_dir("DATA").unwrap();
+            assert!(matches!(fs.create_dir("data"), Err(Error::AlreadyExists)));
+            // Two clusters of entries
+            for i in 0..80 {
+                let mut file = fs.create(&format!("data/f{}.bin", i)).unwrap();
+                fs.write(&mut file, &pattern(5000, i)).unwrap();
+                fs.close(file).unwrap();
+            }
+            fs.create_dir("EMPTY").unwrap();
+            fs.unmount().unwrap();
+        }
+        let mut fs = mount(&mut image);
+        for i in 0..80 {
+            assert_eq!(
+                read_all(&mut fs, &format!("DATA/F{}.BIN", i)),
+                pattern(5000, i)
+            );
+        }
+        assert!(matches!(fs.remove("data"), Err(Error::DirectoryNotEmpty)));
+        fs.remove("empty").unwrap();
+        for i in 0..80 {
+            fs.remove(&format!("data/f{}.bin", i)).unwrap();
+        }
+        fs.remove("data").unwrap();
+        assert!(matches!(fs.metadata("data"), Err(Error::NotFound)));
+        fs.unmount().unwrap();
+        {
+            let fs = image.fatfs();
+            assert_eq!(fs.root_dir().iter().count(), 0);
+        }
+        assert_eq!(free_clusters(&mut image), free);
+    }
+}
@@ -0,0 +1,194 @@
+//! Disk images in memory for the tests
+//!
+//! The volumes are formatted and cross-checked with the `fatfs` crate.
+
+// Author: Moritz Doll
+// License: MIT
+
+use super::partition::{crc32, GPT_BASIC_DATA};
+use super::set_le32;
+use crate::device::block::{BlockDevice, BLOCK_SIZE};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// First block of the partition in the images
+pub const PARTITION_START: u64 = 2048;
+/// Slot of the partition in the MBR
+pub const MBR_SLOT: usize = 1;
+/// Slot of the partition in the GPT
+pub const GPT_SLOT: usize = 5;
+/// Blocks that are not used at the end, e.g. by the backup GPT
+const TAIL: u64 = 64;
+
+pub struct Image(pub Vec<u8>);
+
+impl Image {
+    pub fn new(blocks: u64) -> Self {
+        Image(vec![0; blocks as usize * BLOCK_SIZE])
+    }
+    /// A disk with a single FAT partition in the second slot of the MBR
+    pub fn mbr(blocks: u64, id: u8) -> Self {
+        let mut image = Image::new(blocks);
+        let len = image.partition_blocks() as u32;
+        let entry = 446 + MBR_SLOT * 16;
+        image.0[entry + 4] = id;
+        set_le32(&mut image.0, entry + 8, PARTITION_START as u32);
+        set_le32(&mut image.0, entry + 12, len);
+        image.0[510] = 0x55;
+        image.0[511] = 0xaa;
+        image
+    }
+    /// A disk with a protective MBR and a basic data partition in the GPT
+    pub fn gpt(blocks: u64) -> Self {
+        let mut image = Image::new(blocks);
+        let data = &mut image.0;
+        data[446 + 4] = 0xee;
+        set_le32(data, 446 + 8, 1);
+        set_le32(data, 446 + 12, (blocks - 1) as u32);
+        data[510] = 0x55;
+        data[511] = 0xaa;
+        let entry = 2 * BLOCK_SIZE + GPT_SLOT * 128;
+        data[entry..entry + 16].copy_from_slice(&GPT_BASIC_DATA);
+        let last = blocks - TAIL - 1;
+        data[entry + 32..entry + 40].copy_from_slice(&PARTITION_START.to_le_bytes());
+        data[entry + 40..entry + 48].copy_from_slice(&last.to_le_bytes());
+        let header = BLOCK_SIZE;
+        data[header..header + 8].copy_from_slice(b"EFI PART");
+        set_le32(data, header + 8, 0x0001_0000);
+        set_le32(data, header + 12, 92);
+        data[header + 72..header + 80].copy_from_slice(&2u64.to_le_bytes());
+        set_le32(data, header + 80, 128);
+        set_le32(data, header + 84, 128);
+        image.update_gpt_checksums();
+        image
+    }
+    /// Calculate the checksums of the entries and the header of the GPT
+    pub fn update_gpt_checksums(&mut self) {
+        let data = &mut self.0;
+        let header = BLOCK_SIZE;
+        let entries = !crc32(!0, &data[2 * BLOCK_SIZE..2 * BLOCK_SIZE + 128 * 128]);
+        set_le32(data, header + 88, entries);
+        set_le32(data, header + 16, 0);
+        let crc = !crc32(!0, &data[header..header + 92]);
+        set_le32(data, header + 16, crc);
+    }
+    pub fn partition_blocks(&self) -> u64 {
+        self.block_count() - PARTITION_START - TAIL
+    }
+    /// The partition as file for `fatfs`
+    pub fn volume(&mut self) -> Volume<'_> {
+        let start = PARTITION_START as usize * BLOCK_SIZE;
+        let len = self.partition_blocks() as usize * BLOCK_SIZE;
+        Volume {
+            data: &mut self.0[start..start + len],
+            position: 0,
+        }
+    }
+    /// Format the partition, FAT16 with 2 KiB clusters or FAT32 with 512 bytes
+    pub fn format(&mut self, fat32: bool) {
+        let options = if fat32 {
+            fatfs::FormatVolumeOptions::new()
+                .fat_type(fatfs::FatType::Fat32)
+                .bytes_per_cluster(512)
+        } else {
+            fatfs::FormatVolumeOptions::new()
+                .fat_type(fatfs::FatType::Fat16)
+                .bytes_per_cluster(2048)
+        };
+        fatfs::format_volume(self.volume(), options).unwrap();
+    }
+    pub fn fatfs(&mut self) -> fatfs::FileSystem<Volume<'_>> {
+        fatfs::FileSystem::new(self.volume(), fatfs::FsOptions::new()).unwrap()
+    }
+}
+
+impl BlockDevice for Image {
+    type Error = ();
+    fn block_count(&self) -> u64 {
+        (self.0.len() / BLOCK_SIZE) as u64
+    }
+    fn read_blocks(&mut self, lba: u64, buffer: &mut [u8]) -> Result<(), ()> {
+        let start = lba as usize * BLOCK_SIZE;
+        let data = self.0.get(start..start + buffer.len()).ok_or(())?;
+        buffer.copy_from_slice(data);
+        Ok(())
+    }
+    fn write_blocks(&mut self, lba: u64, buffer: &[u8]) -> Result<(), ()> {
+        let start = lba as usize * BLOCK_SIZE;
+        let data = self.0.get_mut(start..start + buffer.len()).ok_or(())?;
+        data.copy_from_slice(buffer);
+        Ok(())
+    }
+}
+
+/// The partition of an image as file
+pub struct Volume<'a> {
+    data: &'a mut [u8],
+    position: usize,
+}
+
+impl Read for Volume<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let len = buffer.len().min(self.data.len() - self.position);
+        buffer[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Write for Volume<'_> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let len = buffer.len().min(self.data.len() - self.position);
+        self.data[self.position..self.position + len].copy_from_slice(&buffer[..len]);
+        self.position += len;
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Volume<'_> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(position) => position as i64,
+            SeekFrom::End(offset) => self.data.len() as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if position < 0 || position as usize > self.data.len() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek"));
+        }
+        self.position = position as usize;
+        Ok(self.position as u64)
+    }
+}
+
+/// Data that differs in every byte of a cluster
+pub fn pattern(len: usize, seed: u8) -> Vec<u8> {
+    (0..len)
+        .map(|i| (i as u32).wrapping_mul(31).wrapping_add(u32::from(seed)) as u8 ^ (i >> 9) as u8)
+        .collect()
+}
+
+pub fn free_clusters(image: &mut Image) -> u32 {
+    image.fatfs().stats().unwrap().free_clusters()
+}
+
+/// Remove everything with `fatfs` and return the number of free clusters
+pub fn free_clusters_after_clear(image: &mut Image) -> u32 {
+    fn clear<T: fatfs::ReadWriteSeek>(dir: &fatfs::Dir<T>) {
+        for entry in dir.iter() {
+            let entry = entry.unwrap();
+            let name = entry.file_name();
+            if name == "." || name == ".." {
+                continue;
+            }
+            if entry.is_dir() {
+                clear(&entry.to_dir());
+            }
+            dir.remove(&name).unwrap();
+        }
+    }
+    let fs = image.fatfs();
+    clear(&fs.root_dir());
+    fs.stats().unwrap().free_clusters()
+}
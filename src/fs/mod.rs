@@ -0,0 +1,60 @@
+//! File systems on block devices, e.g. on the memory card of an MMC
+//!
+//! The modules only depend on the `BlockDevice` trait and not on the hardware.
+//! The tests run on the host against disk images in memory.
+
+// Author: Moritz Doll
+// License: MIT
+
+pub mod fat;
+#[cfg(test)]
+mod image;
+pub mod partition;
+
+pub use fat::{Dir, DirEntry, FatType, File, FileSystem};
+pub use partition::{Partition, PartitionKind};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error<E> {
+    /// The block device failed
+    Device(E),
+    /// Neither an MBR nor a GPT
+    NoPartitionTable,
+    /// The volume is no FAT16 or FAT32 or uses sectors other than 512 bytes
+    Unsupported,
+    /// A cluster chain, the boot sector or a checksum of the GPT is invalid
+    Corrupt,
+    NotFound,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    DirectoryNotEmpty,
+    /// The name is empty, too long or not a valid 8.3 name when creating
+    InvalidName,
+    /// No free cluster or no free entry in the root directory of FAT16
+    Full,
+}
+
+fn le16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn le32(bytes: &[u8], offset: usize) -> u32 {
+    let mut word = [0; 4];
+    word.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(word)
+}
+
+fn le64(bytes: &[u8], offset: usize) -> u64 {
+    let mut word = [0; 8];
+    word.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(word)
+}
+
+fn set_le16(bytes: &mut [u8], offset: usize, value: u16) {
+    bytes[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn set_le32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
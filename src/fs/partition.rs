@@ -0,0 +1,296 @@
+//! Partition tables, MBR and GPT
+//!
+//! Only the primary partitions of an MBR are supported, extended partitions
+//! are returned like any other partition. The checksums of the GPT are
+//! verified, the backup at the end of the device is not used.
+
+// Author: Moritz Doll
+// License: MIT
+
+use super::{le32, le64, set_le32, Error};
+use crate::device::block::{BlockDevice, BLOCK_SIZE};
+
+/// Number of primary partitions of an MBR
+pub const MBR_PARTITIONS: usize = 4;
+/// Largest number of GPT entries that are searched
+pub const GPT_PARTITIONS: usize = 128;
+
+const MBR_TABLE: usize = 446;
+const MBR_ENTRY_SIZE: usize = 16;
+const MBR_PROTECTIVE: u8 = 0xee;
+const GPT_SIGNATURE: &[u8; 8] = b"EFI PART";
+/// Revision 1.0, the only one defined
+const GPT_REVISION: u32 = 0x0001_0000;
+const GPT_HEADER_MIN: usize = 92;
+
+/// Microsoft basic data partition, EBD0A0A2-B9E5-4433-87C0-68B6B72699C7
+pub const GPT_BASIC_DATA: [u8; 16] = [
+    0xa2, 0xa0, 0xd0, 0xeb, 0xe5, 0xb9, 0x33, 0x44, 0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99, 0xc7,
+];
+/// EFI system partition, C12A7328-F81F-11D2-BA4B-00A0C93EC93B
+pub const GPT_EFI_SYSTEM: [u8; 16] = [
+    0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9, 0x3b,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PartitionKind {
+    /// System ID of an MBR entry
+    Mbr(u8),
+    /// Type GUID of a GPT entry in its on-disk byte order
+    Gpt([u8; 16]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Partition {
+    pub kind: PartitionKind,
+    pub first_block: u64,
+    pub blocks: u64,
+}
+
+impl Partition {
+    /// Returns whether the type of the partition is used for FAT
+    pub fn is_fat(&self) -> bool {
+        match self.kind {
+            PartitionKind::Mbr(id) => [0x04, 0x06, 0x0b, 0x0c, 0x0e].contains(&id),
+            PartitionKind::Gpt(guid) => guid == GPT_BASIC_DATA || guid == GPT_EFI_SYSTEM,
+        }
+    }
+}
+
+enum Table {
+    Mbr,
+    /// First block and size of the entries and their number
+    Gpt {
+        entries: u64,
+        entry_size: usize,
+        count: usize,
+    },
+}
+
+fn read_block<D: BlockDevice>(
+    device: &mut D,
+    lba: u64,
+    buffer: &mut [u8; BLOCK_SIZE],
+) -> Result<(), Error<D::Error>> {
+    device.read_blocks(lba, buffer).map_err(Error::Device)
+}
+
+/// Update the CRC-32 of the GPT, starts with `!0` and is inverted at the end
+pub(super) fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Read the table, the buffer holds the MBR afterwards
+fn table<D: BlockDevice>(
+    device: &mut D,
+    buffer: &mut [u8; BLOCK_SIZE],
+) -> Result<Table, Error<D::Error>> {
+    read_block(device, 0, buffer)?;
+    if buffer[510] != 0x55 || buffer[511] != 0xaa {
+        return Err(Error::NoPartitionTable);
+    }
+    let protective = (0..MBR_PARTITIONS)
+        .any(|index| buffer[MBR_TABLE + index * MBR_ENTRY_SIZE + 4] == MBR_PROTECTIVE);
+    if !protective {
+        return Ok(Table::Mbr);
+    }
+    let mut header = [0; BLOCK_SIZE];
+    read_block(device, 1, &mut header)?;
+    if &header[..8] != GPT_SIGNATURE {
+        return Err(Error::NoPartitionTable);
+    }
+    let header_size = le32(&header, 12) as usize;
+    if le32(&header, 8) != GPT_REVISION || !(GPT_HEADER_MIN..=BLOCK_SIZE).contains(&header_size) {
+        return Err(Error::Unsupported);
+    }
+    let header_crc = le32(&header, 16);
+    set_le32(&mut header, 16, 0);
+    if !crc32(!0, &header[..header_size]) != header_crc {
+        return Err(Error::Corrupt);
+    }
+    let entry_size = le32(&header, 84) as usize;
+    if !(128..=BLOCK_SIZE).contains(&entry_size) || BLOCK_SIZE % entry_size != 0 {
+        return Err(Error::Unsupported);
+    }
+    let entries = le64(&header, 72);
+    let count = le32(&header, 80);
+    // The checksum covers all entries, also the ones that are not searched
+    let mut crc = !0;
+    let mut remaining = u64::from(count) * entry_size as u64;
+    let mut lba = entries;
+    while remaining > 0 {
+        read_block(device, lba, buffer)?;
+        let len = remaining.min(BLOCK_SIZE as u64) as usize;
+        crc = crc32(crc, &buffer[..len]);
+        remaining -= len as u64;
+        lba += 1;
+    }
+    if !crc != le32(&header, 88) {
+        return Err(Error::Corrupt);
+    }
+    Ok(Table::Gpt {
+        entries,
+        entry_size,
+        count: (count as usize).min(GPT_PARTITIONS),
+    })
+}
+
+fn mbr_entry(mbr: &[u8], index: usize) -> Option<Partition> {
+    let entry = &mbr[MBR_TABLE + index * MBR_ENTRY_SIZE..];
+    let id = entry[4];
+    let blocks = u64::from(le32(entry, 12));
+    if id == 0 || blocks == 0 {
+        return None;
+    }
+    Some(Partition {
+        kind: PartitionKind::Mbr(id),
+        first_block: u64::from(le32(entry, 8)),
+        blocks,
+    })
+}
+
+fn gpt_entry(entry: &[u8]) -> Option<Partition> {
+    let mut guid = [0; 16];
+    guid.copy_from_slice(&entry[..16]);
+    let first = le64(entry, 32);
+    let last = le64(entry, 40);
+    if guid == [0; 16] || last < first {
+        return None;
+    }
+    Some(Partition {
+        kind: PartitionKind::Gpt(guid),
+        first_block: first,
+        blocks: last - first + 1,
+    })
+}
+
+/// Returns the partition in a slot of the partition table, None if the slot
+/// is unused or does not exist
+///
+/// A protective MBR is followed to the GPT.
+pub fn read_partition<D: BlockDevice>(
+    device: &mut D,
+    index: usize,
+) -> Result<Option<Partition>, Error<D::Error>> {
+    let mut buffer = [0; BLOCK_SIZE];
+    match table(device, &mut buffer)? {
+        Table::Mbr if index < MBR_PARTITIONS => Ok(mbr_entry(&buffer, index)),
+        Table::Gpt {
+            entries,
+            entry_size,
+            count,
+        } if index < count => {
+            let per_block = BLOCK_SIZE / entry_size;
+            read_block(device, entries + (index / per_block) as u64, &mut buffer)?;
+            Ok(gpt_entry(&buffer[(index % per_block) * entry_size..]))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Returns the first partition with a FAT type
+pub fn find_fat_partition<D: BlockDevice>(
+    device: &mut D,
+) -> Result<Option<Partition>, Error<D::Error>> {
+    let mut buffer = [0; BLOCK_SIZE];
+    match table(device, &mut buffer)? {
+        Table::Mbr => Ok((0..MBR_PARTITIONS)
+            .filter_map(|index| mbr_entry(&buffer, index))
+            .find(Partition::is_fat)),
+        Table::Gpt {
+            entries,
+            entry_size,
+            count,
+        } => {
+            let per_block = BLOCK_SIZE / entry_size;
+            for index in 0..count {
+                if index % per_block == 0 {
+                    read_block(device, entries + (index / per_block) as u64, &mut buffer)?;
+                }
+                match gpt_entry(&buffer[(index % per_block) * entry_size..]) {
+                    Some(partition) if partition.is_fat() => return Ok(Some(partition)),
+                    _ => (),
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::image::{Image, GPT_SLOT, MBR_SLOT, PARTITION_START};
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(!crc32(!0, b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn mbr() {
+        let mut image = Image::mbr(4096, 0x0c);
+        // A Linux partition in front of the FAT partition
+        image.0[MBR_TABLE + 4] = 0x83;
+        set_le32(&mut image.0, MBR_TABLE + 8, 64);
+        set_le32(&mut image.0, MBR_TABLE + 12, 64);
+        let fat = Partition {
+            kind: PartitionKind::Mbr(0x0c),
+            first_block: PARTITION_START,
+            blocks: image.partition_blocks(),
+        };
+        assert_eq!(find_fat_partition(&mut image), Ok(Some(fat)));
+        assert_eq!(read_partition(&mut image, MBR_SLOT), Ok(Some(fat)));
+        let linux = read_partition(&mut image, 0).unwrap().unwrap();
+        assert_eq!(linux.kind, PartitionKind::Mbr(0x83));
+        assert!(!linux.is_fat());
+        assert_eq!(read_partition(&mut image, 2), Ok(None));
+        assert_eq!(read_partition(&mut image, MBR_PARTITIONS), Ok(None));
+    }
+
+    #[test]
+    fn gpt() {
+        let mut image = Image::gpt(4096);
+        let fat = Partition {
+            kind: PartitionKind::Gpt(GPT_BASIC_DATA),
+            first_block: PARTITION_START,
+            blocks: image.partition_blocks(),
+        };
+        assert_eq!(find_fat_partition(&mut image), Ok(Some(fat)));
+        assert_eq!(read_partition(&mut image, GPT_SLOT), Ok(Some(fat)));
+        assert_eq!(read_partition(&mut image, 0), Ok(None));
+        assert_eq!(read_partition(&mut image, GPT_PARTITIONS), Ok(None));
+    }
+
+    #[test]
+    fn gpt_checksums() {
+        let mut image = Image::gpt(4096);
+        // The last entry is not searched but covered by the checksum
+        image.0[2 * BLOCK_SIZE + 127 * 128] = 1;
+        assert_eq!(find_fat_partition(&mut image), Err(Error::Corrupt));
+        image.update_gpt_checksums();
+        assert!(find_fat_partition(&mut image).unwrap().is_some());
+        image.0[BLOCK_SIZE + 72] = 3;
+        assert_eq!(find_fat_partition(&mut image), Err(Error::Corrupt));
+        image.0[BLOCK_SIZE + 72] = 2;
+        image.0[BLOCK_SIZE + 8] = 1;
+        image.update_gpt_checksums();
+        assert_eq!(find_fat_partition(&mut image), Err(Error::Unsupported));
+    }
+
+    #[test]
+    fn no_partition_table() {
+        let mut image = Image::new(64);
+        assert_eq!(find_fat_partition(&mut image), Err(Error::NoPartitionTable));
+    }
+}
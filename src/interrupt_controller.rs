@@ -2,8 +2,15 @@
 // Author: Moritz Doll
 // License: MIT
 
+use crate::critical_section;
+use crate::interrupt_source::InterruptSource;
 use armv7::VirtualAddress;
+use core::convert::TryFrom;
 use core::fmt;
+use core::hint;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
 use register::{mmio::*, register_bitfields};
 
 register_bitfields! {
@@ -18,17 +25,31 @@ register_bitfields! {
     SYSSTATUS [
         ResetDone OFFSET(0) NUMBITS(1) []
     ],
+    SIR [
+        ActiveLine OFFSET(0) NUMBITS(7) [],
+        SpuriousFlag OFFSET(7) NUMBITS(25) []
+    ],
     CONTROL [
         Irq OFFSET(0) NUMBITS(1) [NewIrqReset = 1],
         Fiq OFFSET(1) NUMBITS(1) [NewFiqReset = 1]
     ],
+    PROTECTION [
+        Protection OFFSET(0) NUMBITS(1) [Enable = 1, Disable = 0]
+    ],
     IDLE [
-        FuncIdle OFFSET(0) NUMBITS(1) [],
-        Turbo OFFSET(1) NUMBITS(1) []
+        FuncIdle OFFSET(0) NUMBITS(1) [AutoGated = 0, FreeRunning = 1],
+        Turbo OFFSET(1) NUMBITS(1) [FreeRunning = 0, AutoGated = 1]
     ],
     pub ILR [
         FIQnIRQ OFFSET(0) NUMBITS(1) [IntIRQ = 0, IntFIQ = 1],
         Priority OFFSET(2) NUMBITS(6) []
+    ],
+    CURRENT_PRIORITY [
+        Priority OFFSET(0) NUMBITS(7) [],
+        SpuriousFlag OFFSET(7) NUMBITS(25) []
+    ],
+    THRESHOLD [
+        PriorityThreshold OFFSET(0) NUMBITS(8) [Disabled = 0xFF]
     ]
 }
 
@@ -40,16 +61,16 @@ struct RegisterBlock {
     SYSCONFIG: ReadWrite<u32, SYSCONFIG::Register>, // 0x10
     SYSSTATUS: ReadOnly<u32, SYSSTATUS::Register>,  // 0x14
     __reserved_1: [u32; 2 + 4 * 2],
-    SIR_IRQ: ReadWrite<u32, ()>,                // 0x40
-    SIR_FIQ: ReadWrite<u32, ()>,                // 0x44
-    CONTROL: WriteOnly<u32, CONTROL::Register>, // 0x48
-    _PROTECTION: u32,                           // 0x4C
-    IDLE: ReadWrite<u32, IDLE::Register>,       // 0x50
+    SIR_IRQ: ReadOnly<u32, SIR::Register>,            // 0x40
+    SIR_FIQ: ReadOnly<u32, SIR::Register>,            // 0x44
+    CONTROL: WriteOnly<u32, CONTROL::Register>,       // 0x48
+    PROTECTION: ReadWrite<u32, PROTECTION::Register>, // 0x4C
+    IDLE: ReadWrite<u32, IDLE::Register>,             // 0x50
     __reserved_2: [u32; 3],
-    _IRQ_PRIORITY: u32, // 0x60
-    _FIQ_PRIORITY: u32, // 0x64
-    _THRESHOLD: u32,    // 0x68
-                        //__reserved_3: [u32;1+4],
+    IRQ_PRIORITY: ReadOnly<u32, CURRENT_PRIORITY::Register>, // 0x60
+    FIQ_PRIORITY: ReadOnly<u32, CURRENT_PRIORITY::Register>, // 0x64
+    THRESHOLD: ReadWrite<u32, THRESHOLD::Register>,          // 0x68
+                                                             //__reserved_3: [u32;1+4],
 }
 
 #[allow(non_snake_case)]
@@ -166,8 +187,8 @@ pub enum InterruptRegister {
 */
 
 #[repr(transparent)]
-#[derive(Debug, PartialEq)]
-pub struct InterruptNumber(u8);
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InterruptNumber(pub(crate) u8);
 
 impl InterruptNumber {
     pub fn new(number: u8) -> Option<InterruptNumber> {
@@ -182,6 +203,17 @@ impl InterruptNumber {
         let truncated = number & bitmask;
         InterruptNumber(truncated as u8)
     }
+    /// Get the interrupt number from the value of SIR_IRQ or SIR_FIQ
+    ///
+    /// Returns an error with the reported line if the spurious flag is set
+    pub fn from_sir(sir: u32) -> Result<InterruptNumber, InterruptNumber> {
+        let number = InterruptNumber::from_u32(sir);
+        if sir & !0x7f != 0 {
+            Err(number)
+        } else {
+            Ok(number)
+        }
+    }
     fn get_shift(&self) -> u32 {
         (self.0 % 32) as u32
     }
@@ -199,6 +231,37 @@ impl InterruptNumber {
     }
 }
 
+/// Priority of an interrupt line, 0 is the highest and 63 the lowest
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Priority(u8);
+
+impl Priority {
+    pub const HIGHEST: Priority = Priority(0);
+    pub const LOWEST: Priority = Priority(63);
+
+    pub fn new(priority: u8) -> Option<Priority> {
+        if priority > 63 {
+            None
+        } else {
+            Some(Priority(priority))
+        }
+    }
+    /// Returns the priority
+    ///
+    /// The priority is always <= 63
+    pub fn as_u8(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Whether an interrupt line is routed to the IRQ or the FIQ of the CPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InterruptType {
+    Irq,
+    Fiq,
+}
+
 #[derive(Debug)]
 pub struct InterruptLine {
     number: InterruptNumber,
@@ -233,6 +296,36 @@ impl InterruptLine {
         let raw_ptr = self.get_ilr_addr() as *mut ReadWrite<u32, ILR::Register>;
         unsafe { &*raw_ptr }
     }
+    /// Set the priority and route the line to IRQ or FIQ
+    pub fn configure(&self, priority: Priority, int_type: InterruptType) {
+        let fiq = match int_type {
+            InterruptType::Irq => ILR::FIQnIRQ::IntIRQ,
+            InterruptType::Fiq => ILR::FIQnIRQ::IntFIQ,
+        };
+        self.get_ilr_ptr()
+            .write(fiq + ILR::Priority.val(priority.as_u8() as u32));
+    }
+    pub fn set_priority(&self, priority: Priority) {
+        self.get_ilr_ptr()
+            .modify(ILR::Priority.val(priority.as_u8() as u32));
+    }
+    pub fn get_priority(&self) -> Priority {
+        Priority(self.get_ilr_ptr().read(ILR::Priority) as u8)
+    }
+    pub fn set_type(&self, int_type: InterruptType) {
+        let fiq = match int_type {
+            InterruptType::Irq => ILR::FIQnIRQ::IntIRQ,
+            InterruptType::Fiq => ILR::FIQnIRQ::IntFIQ,
+        };
+        self.get_ilr_ptr().modify(fiq);
+    }
+    pub fn get_type(&self) -> InterruptType {
+        if self.get_ilr_ptr().is_set(ILR::FIQnIRQ) {
+            InterruptType::Fiq
+        } else {
+            InterruptType::Irq
+        }
+    }
     pub fn reg_read(&self, reg: InterruptRegister) -> bool {
         let val = match reg {
             InterruptRegister::Status => self.get_bank_ptr().ITR.get(),
@@ -244,7 +337,7 @@ impl InterruptLine {
             InterruptRegister::PendingIRQ => self.get_bank_ptr().PENDING_IRQ.get(),
             InterruptRegister::PendingFIQ => self.get_bank_ptr().PENDING_FIQ.get(),
         };
-        (val | self.get_bitmask()) != 0
+        (val & self.get_bitmask()) != 0
     }
     pub fn reg_write(&self, reg: InterruptRegister, val: bool) {
         let bitmask = self.get_bitmask();
@@ -266,7 +359,7 @@ impl InterruptLine {
     }
     pub fn get_itr(&self) -> bool {
         let val = self.get_bank_ptr().ITR.get();
-        (val | self.get_bitmask()) != 0
+        (val & self.get_bitmask()) != 0
     }
     pub fn enable(&self) {
         self.get_bank_ptr().MIR_CLEAR.set(self.get_bitmask());
@@ -275,7 +368,7 @@ impl InterruptLine {
         self.get_bank_ptr().MIR_SET.set(self.get_bitmask());
     }
     pub fn pending(&self) -> bool {
-        self.reg_read(InterruptRegister::PendingIRQ) || self.reg_read(InterruptRegister::PendingIRQ)
+        self.reg_read(InterruptRegister::PendingIRQ) || self.reg_read(InterruptRegister::PendingFIQ)
     }
     pub fn debug_set_software_irq(&self) {
         self.get_bank_ptr().ISR_SET.set(self.get_bitmask());
@@ -285,6 +378,133 @@ impl InterruptLine {
     }
 }
 
+/// Function called for an active interrupt line
+///
+/// Closures that do not capture anything can be used as well.
+pub type Handler = fn(InterruptNumber);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_HANDLER: AtomicPtr<()> = AtomicPtr::new(ptr::null_mut());
+/// The handlers of all interrupt lines, shared by IRQ and FIQ
+static HANDLERS: [AtomicPtr<()>; 128] = [NO_HANDLER; 128];
+
+fn swap_handler(number: InterruptNumber, handler: Option<Handler>) -> Option<Handler> {
+    let new = match handler {
+        Some(handler) => handler as *mut (),
+        None => ptr::null_mut(),
+    };
+    let old = HANDLERS[number.as_u8() as usize].swap(new, Ordering::AcqRel);
+    to_handler(old)
+}
+
+fn to_handler(raw: *mut ()) -> Option<Handler> {
+    if raw.is_null() {
+        None
+    } else {
+        // Only function pointers of type Handler are stored in the table
+        Some(unsafe { mem::transmute::<*mut (), Handler>(raw) })
+    }
+}
+
+/// Outcome of dispatching an interrupt
+#[derive(Debug, PartialEq)]
+pub enum Dispatch {
+    /// The handler of the line was called
+    Handled(InterruptNumber),
+    /// No handler was registered, the line was masked
+    Unhandled(InterruptNumber),
+    /// The interrupt vanished before it was sorted, nothing was called
+    Spurious(InterruptNumber),
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+static DISPATCHED: [AtomicU32; 128] = [ZERO; 128];
+static SPURIOUS: [AtomicU32; 128] = [ZERO; 128];
+static UNHANDLED: [AtomicU32; 128] = [ZERO; 128];
+
+/// How often the dispatcher saw an interrupt line
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LineStatistics {
+    pub dispatched: u32,
+    pub spurious: u32,
+    pub unhandled: u32,
+}
+
+impl LineStatistics {
+    fn of(number: InterruptNumber) -> Self {
+        let index = number.as_u8() as usize;
+        LineStatistics {
+            dispatched: DISPATCHED[index].load(Ordering::Relaxed),
+            spurious: SPURIOUS[index].load(Ordering::Relaxed),
+            unhandled: UNHANDLED[index].load(Ordering::Relaxed),
+        }
+    }
+    fn is_empty(&self) -> bool {
+        self.dispatched == 0 && self.spurious == 0 && self.unhandled == 0
+    }
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NOT_ALLOCATED: AtomicU32 = AtomicU32::new(0);
+/// Bitmask of the lines that are allocated for software interrupts
+static SOFTWARE_LINES: [AtomicU32; 4] = [NOT_ALLOCATED; 4];
+static MAILBOXES: [AtomicU32; 128] = [ZERO; 128];
+
+fn is_software_line(number: InterruptNumber) -> bool {
+    let bit = 1 << number.get_shift();
+    SOFTWARE_LINES[number.get_offset() as usize].load(Ordering::Acquire) & bit != 0
+}
+
+/// An interrupt line without hardware source that is raised by software
+///
+/// Used to defer work from a high priority handler to a handler with lower
+/// priority. The payloads of all raises before the handler runs are combined
+/// with a bitwise or and can be taken by the handler with `take_payload`.
+#[derive(Debug)]
+pub struct SoftwareInterrupt {
+    line: InterruptLine,
+}
+
+impl SoftwareInterrupt {
+    pub fn number(&self) -> InterruptNumber {
+        self.line.number
+    }
+    /// Post the payload and raise the interrupt
+    pub fn raise(&self, payload: u32) {
+        MAILBOXES[self.line.number.as_u8() as usize].fetch_or(payload, Ordering::AcqRel);
+        self.line.reg_write(InterruptRegister::SoftwareSet, true);
+    }
+    /// Take the payload posted to a software interrupt line
+    pub fn take_payload(number: InterruptNumber) -> u32 {
+        MAILBOXES[number.as_u8() as usize].swap(0, Ordering::AcqRel)
+    }
+}
+
+/// State of the interrupt controller after `InterruptController::init`
+#[derive(Debug, Clone, Copy)]
+pub struct InitConfig {
+    /// Gate the interface clock automatically
+    pub autoidle: bool,
+    /// Gate the functional clock automatically
+    pub func_idle: bool,
+    /// Gate the input synchronizer clock when there is no interrupt activity
+    pub turbo: bool,
+    /// Restrict the access to the registers to privileged mode
+    pub protection: bool,
+}
+
+impl Default for InitConfig {
+    fn default() -> Self {
+        InitConfig {
+            autoidle: true,
+            func_idle: true,
+            turbo: true,
+            protection: false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct InterruptController {
     memory_address: u32,
@@ -300,6 +520,38 @@ impl InterruptController {
             memory_address: memory_address.as_u32(),
         }
     }
+    /// Reset the interrupt controller and bring it into a known state
+    ///
+    /// All lines are masked, the handlers are removed, software interrupts
+    /// are cleared and freed and the priority threshold is disabled.
+    ///
+    /// # Safety
+    /// The caller has to make sure that the virtual address maps to the
+    /// interrupt controller. Interrupt lines and software interrupts that
+    /// were handed out before lose their configuration.
+    pub unsafe fn init(memory_address: VirtualAddress, config: InitConfig) -> Self {
+        let controller = Self::new(memory_address);
+        controller.reset();
+        for bank in 0..4 {
+            let bank_ptr = controller.get_bank_ptr(bank);
+            bank_ptr.MIR_SET.set(0xffff_ffff);
+            bank_ptr.ISR_CLEAR.set(0xffff_ffff);
+            SOFTWARE_LINES[bank as usize].store(0, Ordering::Release);
+        }
+        for index in 0..128 {
+            HANDLERS[index].store(ptr::null_mut(), Ordering::Release);
+            MAILBOXES[index].store(0, Ordering::Release);
+        }
+        controller.set_priority_threshold(None);
+        if config.autoidle {
+            controller.autoidle();
+        }
+        controller.set_idle(config.func_idle, config.turbo);
+        controller.set_protection(config.protection);
+        controller.generate_new_irq();
+        controller.generate_new_fiq();
+        controller
+    }
     /// Print the raw status of all interrupt lines
     pub fn dump_raw_status<T: fmt::Write>(&self, writer: &mut T) -> fmt::Result {
         writeln!(writer, "Dumping raw irq controller status:")?;
@@ -316,8 +568,54 @@ impl InterruptController {
             "ITR0 {:#x}, ITR1 {:#x}, ITR2 {:#x}, ITR3 {:#x}",
             itr0, itr1, itr2, itr3
         )?;
+        for bank in 0..4 {
+            let bank_ptr = self.get_bank_ptr(bank);
+            writeln!(
+                writer,
+                "Bank {}: MIR {:#x}, PENDING_IRQ {:#x}, PENDING_FIQ {:#x}",
+                bank,
+                bank_ptr.MIR.get(),
+                bank_ptr.PENDING_IRQ.get(),
+                bank_ptr.PENDING_FIQ.get()
+            )?;
+        }
         Ok(())
     }
+    /// Print the configuration and statistics of all unmasked or seen lines
+    pub fn dump_status<T: fmt::Write>(&self, writer: &mut T) -> fmt::Result {
+        self.dump_raw_status(writer)?;
+        writeln!(
+            writer,
+            "Threshold {:?}, active IRQ priority {:?}, active FIQ priority {:?}",
+            self.get_priority_threshold(),
+            self.get_active_irq_priority(),
+            self.get_active_fiq_priority()
+        )?;
+        for number in 0..128 {
+            let line = self.new_interrupt_line(InterruptNumber(number));
+            let masked = line.reg_read(InterruptRegister::Mask);
+            let statistics = LineStatistics::of(InterruptNumber(number));
+            if masked && statistics.is_empty() {
+                continue;
+            }
+            writeln!(
+                writer,
+                "Line {} ({:?}): masked {}, pending {}, priority {}, {:?}, {:?}",
+                number,
+                InterruptSource::try_from(InterruptNumber(number)).ok(),
+                masked,
+                line.pending(),
+                line.get_priority().as_u8(),
+                line.get_type(),
+                statistics
+            )?;
+        }
+        Ok(())
+    }
+    fn get_bank_ptr(&self, bank: u32) -> &BankBlock {
+        let raw_ptr = (self.memory_address + 0x80 + bank * 0x20) as *mut BankBlock;
+        unsafe { &*raw_ptr }
+    }
     pub fn new_interrupt_line(&self, number: InterruptNumber) -> InterruptLine {
         InterruptLine::new(number, self.memory_address)
     }
@@ -327,30 +625,227 @@ impl InterruptController {
     }
     /// Get the currently active IRQ
     ///
-    /// # Safety
-    /// Should make sure that the interrupt number is valid
-    pub fn get_active_irq(&self) -> InterruptNumber {
+    /// Returns `None` if the IRQ is spurious
+    pub fn get_active_irq(&self) -> Option<InterruptNumber> {
         let active_irq = self.get_ptr().SIR_IRQ.get();
-        InterruptNumber::from_u32(active_irq)
+        InterruptNumber::from_sir(active_irq).ok()
     }
     /// Get the currently active FIQ
     ///
-    /// # Safety
-    /// Should make sure that the interrupt number is valid
-    pub fn get_active_fiq(&self) -> InterruptNumber {
+    /// Returns `None` if the FIQ is spurious
+    pub fn get_active_fiq(&self) -> Option<InterruptNumber> {
         let active_fiq = self.get_ptr().SIR_FIQ.get();
-        InterruptNumber::from_u32(active_fiq)
+        InterruptNumber::from_sir(active_fiq).ok()
     }
+    /// Reset the interrupt controller and wait until the reset is done
     pub fn reset(&self) {
         self.get_ptr()
             .SYSCONFIG
             .write(SYSCONFIG::SoftReset::SoftReset);
+        loop {
+            if self.get_ptr().SYSSTATUS.is_set(SYSSTATUS::ResetDone) {
+                break;
+            }
+            hint::spin_loop();
+        }
+    }
+    /// Select the clock gating of the functional and synchronizer clocks
+    pub fn set_idle(&self, func_idle: bool, turbo: bool) {
+        let func_idle = if func_idle {
+            IDLE::FuncIdle::AutoGated
+        } else {
+            IDLE::FuncIdle::FreeRunning
+        };
+        let turbo = if turbo {
+            IDLE::Turbo::AutoGated
+        } else {
+            IDLE::Turbo::FreeRunning
+        };
+        self.get_ptr().IDLE.write(func_idle + turbo);
+    }
+    /// Restrict the access to the registers to privileged mode
+    pub fn set_protection(&self, enable: bool) {
+        let protection = if enable {
+            PROTECTION::Protection::Enable
+        } else {
+            PROTECTION::Protection::Disable
+        };
+        self.get_ptr().PROTECTION.write(protection);
+    }
+    pub fn is_protected(&self) -> bool {
+        self.get_ptr().PROTECTION.is_set(PROTECTION::Protection)
     }
     pub fn autoidle(&self) {
         self.get_ptr()
             .SYSCONFIG
             .write(SYSCONFIG::AutoIdle::AutoIdle);
     }
+    /// Returns the priority of the currently active IRQ, None if there is none
+    ///
+    /// The INTC reports 0x40 if no IRQ is active or it was spurious.
+    pub fn get_active_irq_priority(&self) -> Option<Priority> {
+        Priority::new(self.get_ptr().IRQ_PRIORITY.read(CURRENT_PRIORITY::Priority) as u8)
+    }
+    /// Returns the priority of the currently active FIQ, None if there is none
+    ///
+    /// The INTC reports 0x40 if no FIQ is active or it was spurious.
+    pub fn get_active_fiq_priority(&self) -> Option<Priority> {
+        Priority::new(self.get_ptr().FIQ_PRIORITY.read(CURRENT_PRIORITY::Priority) as u8)
+    }
+    /// Only forward interrupts with a higher priority than the threshold
+    ///
+    /// To allow nesting, an interrupt handler sets the threshold to the
+    /// priority of the active interrupt before re-enabling interrupts in the
+    /// CPU and restores the old threshold when it is done. `None` disables
+    /// the threshold.
+    pub fn set_priority_threshold(&self, threshold: Option<Priority>) {
+        match threshold {
+            Some(priority) => self
+                .get_ptr()
+                .THRESHOLD
+                .write(THRESHOLD::PriorityThreshold.val(priority.as_u8() as u32)),
+            None => self
+                .get_ptr()
+                .THRESHOLD
+                .write(THRESHOLD::PriorityThreshold::Disabled),
+        }
+    }
+    pub fn get_priority_threshold(&self) -> Option<Priority> {
+        let threshold = self.get_ptr().THRESHOLD.read(THRESHOLD::PriorityThreshold);
+        Priority::new(threshold as u8)
+    }
+    /// Register the handler of an interrupt line, returns the previous handler
+    pub fn register_handler(&self, number: InterruptNumber, handler: Handler) -> Option<Handler> {
+        swap_handler(number, Some(handler))
+    }
+    /// Remove the handler of an interrupt line
+    pub fn unregister_handler(&self, number: InterruptNumber) -> Option<Handler> {
+        swap_handler(number, None)
+    }
+    /// Allocate a reserved interrupt line as software interrupt
+    ///
+    /// The line is routed to IRQ with the given priority and unmasked. The
+    /// dispatcher clears the line before the handler is called.
+    pub fn allocate_software_interrupt(&self, priority: Priority) -> Option<SoftwareInterrupt> {
+        for number in 0..128 {
+            let number = InterruptNumber(number);
+            if InterruptSource::try_from(number).is_ok() {
+                continue;
+            }
+            let bit = 1 << number.get_shift();
+            let previous =
+                SOFTWARE_LINES[number.get_offset() as usize].fetch_or(bit, Ordering::AcqRel);
+            if previous & bit != 0 {
+                continue;
+            }
+            MAILBOXES[number.as_u8() as usize].store(0, Ordering::Release);
+            let line = self.new_interrupt_line(number);
+            line.reg_write(InterruptRegister::SoftwareClear, true);
+            line.configure(priority, InterruptType::Irq);
+            line.enable();
+            return Some(SoftwareInterrupt { line });
+        }
+        None
+    }
+    /// Give back a software interrupt, its line is masked and cleared
+    pub fn free_software_interrupt(&self, interrupt: SoftwareInterrupt) {
+        let number = interrupt.number();
+        interrupt.line.disable();
+        interrupt
+            .line
+            .reg_write(InterruptRegister::SoftwareClear, true);
+        let bit = 1 << number.get_shift();
+        SOFTWARE_LINES[number.get_offset() as usize].fetch_and(!bit, Ordering::AcqRel);
+    }
+    fn dispatch(&self, sir: u32) -> Dispatch {
+        let number = match InterruptNumber::from_sir(sir) {
+            Ok(number) => number,
+            Err(number) => {
+                SPURIOUS[number.as_u8() as usize].fetch_add(1, Ordering::Relaxed);
+                return Dispatch::Spurious(number);
+            }
+        };
+        let index = number.as_u8() as usize;
+        if is_software_line(number) {
+            // Clear before the handler runs, so that a new raise is not lost
+            self.new_interrupt_line(number)
+                .reg_write(InterruptRegister::SoftwareClear, true);
+        }
+        match to_handler(HANDLERS[index].load(Ordering::Acquire)) {
+            Some(handler) => {
+                DISPATCHED[index].fetch_add(1, Ordering::Relaxed);
+                handler(number);
+                Dispatch::Handled(number)
+            }
+            None => {
+                UNHANDLED[index].fetch_add(1, Ordering::Relaxed);
+                // Mask the line, otherwise it fires again immediately
+                self.new_interrupt_line(number).disable();
+                Dispatch::Unhandled(number)
+            }
+        }
+    }
+    /// Returns how often the dispatcher saw the interrupt line
+    pub fn statistics(&self, number: InterruptNumber) -> LineStatistics {
+        LineStatistics::of(number)
+    }
+    /// Reset the statistics of all lines
+    pub fn reset_statistics(&self) {
+        for index in 0..128 {
+            DISPATCHED[index].store(0, Ordering::Relaxed);
+            SPURIOUS[index].store(0, Ordering::Relaxed);
+            UNHANDLED[index].store(0, Ordering::Relaxed);
+        }
+    }
+    /// Handle the active IRQ
+    ///
+    /// Calls the registered handler and afterwards allows the generation of
+    /// a new IRQ. Should be called from the IRQ exception vector. The handler
+    /// runs with IRQs masked, i.e. the dispatch does not nest.
+    pub fn dispatch_irq(&self) -> Dispatch {
+        let sir = self.get_ptr().SIR_IRQ.get();
+        let dispatch = self.dispatch(sir);
+        self.generate_new_irq();
+        dispatch
+    }
+    /// Handle the active IRQ and let IRQs with a higher priority preempt it
+    ///
+    /// The threshold is set to the priority of the active IRQ, a new IRQ is
+    /// allowed and IRQs are unmasked in the CPU while the handler runs.
+    /// Afterwards IRQs are masked again and the old threshold is restored.
+    ///
+    /// # Safety
+    /// A nested IRQ overwrites LR_irq and SPSR_irq. The IRQ exception vector
+    /// has to save both and switch to system mode before it calls this.
+    pub unsafe fn dispatch_irq_nested(&self) -> Dispatch {
+        let sir = self.get_ptr().SIR_IRQ.get();
+        let priority = match self.get_active_irq_priority() {
+            Some(priority) => priority,
+            None => {
+                let dispatch = self.dispatch(sir);
+                self.generate_new_irq();
+                return dispatch;
+            }
+        };
+        let threshold = self.get_priority_threshold();
+        self.set_priority_threshold(Some(priority));
+        self.generate_new_irq();
+        let cpsr = critical_section::enable_irq();
+        let dispatch = self.dispatch(sir);
+        critical_section::restore_interrupts(cpsr);
+        self.set_priority_threshold(threshold);
+        dispatch
+    }
+    /// Handle the active FIQ
+    ///
+    /// Calls the registered handler and afterwards allows the generation of
+    /// a new FIQ. Should be called from the FIQ exception vector.
+    pub fn dispatch_fiq(&self) -> Dispatch {
+        let sir = self.get_ptr().SIR_FIQ.get();
+        let dispatch = self.dispatch(sir);
+        self.generate_new_fiq();
+        dispatch
+    }
     pub fn generate_new_irq(&self) {
         self.get_ptr().CONTROL.write(CONTROL::Irq::NewIrqReset);
     }
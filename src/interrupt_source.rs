@@ -0,0 +1,353 @@
+//! Names of the interrupt sources of the AM335x
+//!
+//! The numbers are taken from the ARM Cortex-A8 interrupt table of the
+//! technical reference manual. Lines that are not listed are reserved.
+
+// Author: Moritz Doll
+// License: MIT
+
+use crate::interrupt_controller::InterruptNumber;
+use core::convert::TryFrom;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InterruptSource {
+    /// EMUINT, emulation interrupt
+    Emu = 0,
+    /// COMMTX, debug communication transmit
+    CommTx = 1,
+    /// COMMRX, debug communication receive
+    CommRx = 2,
+    /// BENCH, performance monitor
+    Bench = 3,
+    /// ELM_IRQ, error location process completion
+    Elm = 4,
+    /// NMI, external nNMI pin
+    Nmi = 7,
+    /// L3DEBUG, L3 interconnect debug error
+    L3Debug = 9,
+    /// L3APPINT, L3 interconnect application error
+    L3App = 10,
+    /// PRCMINT, PRCM
+    Prcm = 11,
+    /// EDMACOMPINT, EDMA3 channel controller completion
+    Edma3CcInt0 = 12,
+    /// EDMAMPERR, EDMA3 memory protection error
+    Edma3CcMpErr = 13,
+    /// EDMAERRINT, EDMA3 channel controller error
+    Edma3CcErr = 14,
+    /// ADC_TSC_GENINT, touchscreen and ADC
+    AdcTsc = 16,
+    /// USBSSINT, USB subsystem
+    UsbSs = 17,
+    /// USBINT0, USB port 0
+    Usb0 = 18,
+    /// USBINT1, USB port 1
+    Usb1 = 19,
+    /// PRU_ICSS_EVTOUT0, PRU-ICSS host event 0
+    PrussEvtOut0 = 20,
+    /// PRU_ICSS_EVTOUT1, PRU-ICSS host event 1
+    PrussEvtOut1 = 21,
+    /// PRU_ICSS_EVTOUT2, PRU-ICSS host event 2
+    PrussEvtOut2 = 22,
+    /// PRU_ICSS_EVTOUT3, PRU-ICSS host event 3
+    PrussEvtOut3 = 23,
+    /// PRU_ICSS_EVTOUT4, PRU-ICSS host event 4
+    PrussEvtOut4 = 24,
+    /// PRU_ICSS_EVTOUT5, PRU-ICSS host event 5
+    PrussEvtOut5 = 25,
+    /// PRU_ICSS_EVTOUT6, PRU-ICSS host event 6
+    PrussEvtOut6 = 26,
+    /// PRU_ICSS_EVTOUT7, PRU-ICSS host event 7
+    PrussEvtOut7 = 27,
+    /// MMCSD1INT, MMC/SD 1
+    Mmc1 = 28,
+    /// MMCSD2INT, MMC/SD 2
+    Mmc2 = 29,
+    /// I2C2INT, I2C 2
+    I2c2 = 30,
+    /// eCAP0INT, eCAP 0
+    Ecap0 = 31,
+    /// GPIOINT2A, GPIO 2 line 0
+    GpioInt2A = 32,
+    /// GPIOINT2B, GPIO 2 line 1
+    GpioInt2B = 33,
+    /// USBWAKEUP, USB wakeup
+    UsbWakeup = 34,
+    /// LCDCINT, LCD controller
+    Lcdc = 36,
+    /// GFXINT, SGX530 graphics
+    Gfx = 37,
+    /// ePWM2INT, eHRPWM 2
+    Epwm2 = 39,
+    /// 3PGSWRXTHR0, CPSW receive threshold
+    CpswRxThr0 = 40,
+    /// 3PGSWRXINT0, CPSW receive
+    CpswRx0 = 41,
+    /// 3PGSWTXINT0, CPSW transmit
+    CpswTx0 = 42,
+    /// 3PGSWMISC0, CPSW miscellaneous
+    CpswMisc0 = 43,
+    /// UART3INT, UART 3
+    Uart3 = 44,
+    /// UART4INT, UART 4
+    Uart4 = 45,
+    /// UART5INT, UART 5
+    Uart5 = 46,
+    /// eCAP1INT, eCAP 1
+    Ecap1 = 47,
+    /// DCAN0_INT0, DCAN 0 line 0
+    Dcan0Int0 = 52,
+    /// DCAN0_INT1, DCAN 0 line 1
+    Dcan0Int1 = 53,
+    /// DCAN0_PARITY, DCAN 0 parity error
+    Dcan0Parity = 54,
+    /// DCAN1_INT0, DCAN 1 line 0
+    Dcan1Int0 = 55,
+    /// DCAN1_INT1, DCAN 1 line 1
+    Dcan1Int1 = 56,
+    /// DCAN1_PARITY, DCAN 1 parity error
+    Dcan1Parity = 57,
+    /// ePWM0_TZINT, eHRPWM 0 trip zone
+    Epwm0Tz = 58,
+    /// ePWM1_TZINT, eHRPWM 1 trip zone
+    Epwm1Tz = 59,
+    /// ePWM2_TZINT, eHRPWM 2 trip zone
+    Epwm2Tz = 60,
+    /// eCAP2INT, eCAP 2
+    Ecap2 = 61,
+    /// GPIOINT3A, GPIO 3 line 0
+    GpioInt3A = 62,
+    /// GPIOINT3B, GPIO 3 line 1
+    GpioInt3B = 63,
+    /// MMCSD0INT, MMC/SD 0
+    Mmc0 = 64,
+    /// McSPI0INT, McSPI 0
+    McSpi0 = 65,
+    /// TINT0, DMTimer 0
+    Timer0 = 66,
+    /// TINT1_1MS, DMTimer 1ms
+    Timer1 = 67,
+    /// TINT2, DMTimer 2
+    Timer2 = 68,
+    /// TINT3, DMTimer 3
+    Timer3 = 69,
+    /// I2C0INT, I2C 0
+    I2c0 = 70,
+    /// I2C1INT, I2C 1
+    I2c1 = 71,
+    /// UART0INT, UART 0
+    Uart0 = 72,
+    /// UART1INT, UART 1
+    Uart1 = 73,
+    /// UART2INT, UART 2
+    Uart2 = 74,
+    /// RTCINT, RTC timer
+    Rtc = 75,
+    /// RTCALARMINT, RTC alarm
+    RtcAlarm = 76,
+    /// MBINT0, mailbox 0
+    Mailbox0 = 77,
+    /// M3_TXEV, Cortex-M3 wakeup processor event
+    M3TxEv = 78,
+    /// eQEP0INT, eQEP 0
+    Eqep0 = 79,
+    /// MCATXINT0, McASP 0 transmit
+    McAsp0Tx = 80,
+    /// MCARXINT0, McASP 0 receive
+    McAsp0Rx = 81,
+    /// MCATXINT1, McASP 1 transmit
+    McAsp1Tx = 82,
+    /// MCARXINT1, McASP 1 receive
+    McAsp1Rx = 83,
+    /// ePWM0INT, eHRPWM 0
+    Epwm0 = 86,
+    /// ePWM1INT, eHRPWM 1
+    Epwm1 = 87,
+    /// eQEP1INT, eQEP 1
+    Eqep1 = 88,
+    /// eQEP2INT, eQEP 2
+    Eqep2 = 89,
+    /// DMA_INTR_PIN2, external DMA event pin 2
+    DmaPin2 = 90,
+    /// WDT1INT, watchdog 1
+    Watchdog1 = 91,
+    /// TINT4, DMTimer 4
+    Timer4 = 92,
+    /// TINT5, DMTimer 5
+    Timer5 = 93,
+    /// TINT6, DMTimer 6
+    Timer6 = 94,
+    /// TINT7, DMTimer 7
+    Timer7 = 95,
+    /// GPIOINT0A, GPIO 0 line 0
+    GpioInt0A = 96,
+    /// GPIOINT0B, GPIO 0 line 1
+    GpioInt0B = 97,
+    /// GPIOINT1A, GPIO 1 line 0
+    GpioInt1A = 98,
+    /// GPIOINT1B, GPIO 1 line 1
+    GpioInt1B = 99,
+    /// GPMCINT, general purpose memory controller
+    Gpmc = 100,
+    /// DDRERR0, EMIF error
+    DdrErr0 = 101,
+    /// AES0_IRQ_S, AES module 0 secure side
+    Aes0Secure = 102,
+    /// AES0_IRQ_P, AES module 0 public side
+    Aes0Public = 103,
+    /// SHA_IRQ_S, SHA2/MD5 secure side
+    ShaSecure = 108,
+    /// SHA_IRQ_P, SHA2/MD5 public side
+    ShaPublic = 109,
+    /// FPKA_SINTREQUEST_S, public key accelerator
+    Pka = 110,
+    /// RNG_IRQ, random number generator
+    Rng = 111,
+    /// TCERRINT0, EDMA3 transfer controller 0 error
+    Edma3TcErr0 = 112,
+    /// TCERRINT1, EDMA3 transfer controller 1 error
+    Edma3TcErr1 = 113,
+    /// TCERRINT2, EDMA3 transfer controller 2 error
+    Edma3TcErr2 = 114,
+    /// SMRFLX_MPU, SmartReflex MPU voltage domain
+    SmartReflex0 = 120,
+    /// SMRFLX_Core, SmartReflex core voltage domain
+    SmartReflex1 = 121,
+    /// DMA_INTR_PIN0, external DMA event pin 0
+    DmaPin0 = 123,
+    /// DMA_INTR_PIN1, external DMA event pin 1
+    DmaPin1 = 124,
+    /// McSPI1INT, McSPI 1
+    McSpi1 = 125,
+}
+
+impl TryFrom<InterruptNumber> for InterruptSource {
+    type Error = InterruptNumber;
+    /// Get the source of an interrupt line, the line is returned as error if
+    /// it is reserved
+    fn try_from(number: InterruptNumber) -> Result<InterruptSource, InterruptNumber> {
+        use InterruptSource::*;
+        let source = match number.as_u8() {
+            0 => Emu,
+            1 => CommTx,
+            2 => CommRx,
+            3 => Bench,
+            4 => Elm,
+            7 => Nmi,
+            9 => L3Debug,
+            10 => L3App,
+            11 => Prcm,
+            12 => Edma3CcInt0,
+            13 => Edma3CcMpErr,
+            14 => Edma3CcErr,
+            16 => AdcTsc,
+            17 => UsbSs,
+            18 => Usb0,
+            19 => Usb1,
+            20 => PrussEvtOut0,
+            21 => PrussEvtOut1,
+            22 => PrussEvtOut2,
+            23 => PrussEvtOut3,
+            24 => PrussEvtOut4,
+            25 => PrussEvtOut5,
+            26 => PrussEvtOut6,
+            27 => PrussEvtOut7,
+            28 => Mmc1,
+            29 => Mmc2,
+            30 => I2c2,
+            31 => Ecap0,
+            32 => GpioInt2A,
+            33 => GpioInt2B,
+            34 => UsbWakeup,
+            36 => Lcdc,
+            37 => Gfx,
+            39 => Epwm2,
+            40 => CpswRxThr0,
+            41 => CpswRx0,
+            42 => CpswTx0,
+            43 => CpswMisc0,
+            44 => Uart3,
+            45 => Uart4,
+            46 => Uart5,
+            47 => Ecap1,
+            52 => Dcan0Int0,
+            53 => Dcan0Int1,
+            54 => Dcan0Parity,
+            55 => Dcan1Int0,
+            56 => Dcan1Int1,
+            57 => Dcan1Parity,
+            58 => Epwm0Tz,
+            59 => Epwm1Tz,
+            60 => Epwm2Tz,
+            61 => Ecap2,
+            62 => GpioInt3A,
+            63 => GpioInt3B,
+            64 => Mmc0,
+            65 => McSpi0,
+            66 => Timer0,
+            67 => Timer1,
+            68 => Timer2,
+            69 => Timer3,
+            70 => I2c0,
+            71 => I2c1,
+            72 => Uart0,
+            73 => Uart1,
+            74 => Uart2,
+            75 => Rtc,
+            76 => RtcAlarm,
+            77 => Mailbox0,
+            78 => M3TxEv,
+            79 => Eqep0,
+            80 => McAsp0Tx,
+            81 => McAsp0Rx,
+            82 => McAsp1Tx,
+            83 => McAsp1Rx,
+            86 => Epwm0,
+            87 => Epwm1,
+            88 => Eqep1,
+            89 => Eqep2,
+            90 => DmaPin2,
+            91 => Watchdog1,
+            92 => Timer4,
+            93 => Timer5,
+            94 => Timer6,
+            95 => Timer7,
+            96 => GpioInt0A,
+            97 => GpioInt0B,
+            98 => GpioInt1A,
+            99 => GpioInt1B,
+            100 => Gpmc,
+            101 => DdrErr0,
+            102 => Aes0Secure,
+            103 => Aes0Public,
+            108 => ShaSecure,
+            109 => ShaPublic,
+            110 => Pka,
+            111 => Rng,
+            112 => Edma3TcErr0,
+            113 => Edma3TcErr1,
+            114 => Edma3TcErr2,
+            120 => SmartReflex0,
+            121 => SmartReflex1,
+            123 => DmaPin0,
+            124 => DmaPin1,
+            125 => McSpi1,
+            _ => return Err(number),
+        };
+        Ok(source)
+    }
+}
+
+impl InterruptSource {
+    /// Returns the interrupt line of the source
+    pub fn number(self) -> InterruptNumber {
+        InterruptNumber(self as u8)
+    }
+}
+
+impl From<InterruptSource> for InterruptNumber {
+    fn from(source: InterruptSource) -> InterruptNumber {
+        source.number()
+    }
+}
@@ -5,10 +5,12 @@
 // Author: Moritz Doll
 // License: MIT
 
-#![no_std]
-#![feature(stdsimd)]
+#![cfg_attr(not(test), no_std)]
 
+pub mod critical_section;
 pub mod device;
+pub mod fs;
 pub mod interrupt_controller;
+pub mod interrupt_source;
 pub mod memory_map;
 pub mod bsp;
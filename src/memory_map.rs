@@ -10,7 +10,7 @@ pub const DRAM_START: PhysicalAddress = PhysicalAddress::new(0x8000_0000);
 pub const DRAM_END: PhysicalAddress = PhysicalAddress::new(0x9FFF_FFFF);
 
 /// List of special memory addresses (in 16MB, i.e. addresses 0xab**_****)
-pub const DEVICES: [u8; 4] = [0x44, 0x47, 0x48, 0x4A];
+pub const DEVICES: [u8; 5] = [0x44, 0x47, 0x48, 0x49, 0x4A];
 
 /// Uart
 pub const UART0: PhysicalAddress = PhysicalAddress::new(0x44E0_9000);
@@ -24,6 +24,13 @@ pub const UART5: PhysicalAddress = PhysicalAddress::new(0x481A_A000);
 pub const IRQ_CONTROLLER: PhysicalAddress = PhysicalAddress::new(0x4820_0000);
 /// Generic timer
 pub const TIMER0: PhysicalAddress = PhysicalAddress::new(0x44E0_5000);
+pub const TIMER1: PhysicalAddress = PhysicalAddress::new(0x44E3_1000);
+pub const TIMER2: PhysicalAddress = PhysicalAddress::new(0x4804_0000);
+pub const TIMER3: PhysicalAddress = PhysicalAddress::new(0x4804_2000);
+pub const TIMER4: PhysicalAddress = PhysicalAddress::new(0x4804_4000);
+pub const TIMER5: PhysicalAddress = PhysicalAddress::new(0x4804_6000);
+pub const TIMER6: PhysicalAddress = PhysicalAddress::new(0x4804_8000);
+pub const TIMER7: PhysicalAddress = PhysicalAddress::new(0x4804_A000);
 /// Watchdog
 pub const WATCHDOG: PhysicalAddress = PhysicalAddress::new(0x44E3_5000);
 /// GPIOs
@@ -31,5 +38,39 @@ pub const GPIO0: PhysicalAddress = PhysicalAddress::new(0x44E0_7000);
 pub const GPIO1: PhysicalAddress = PhysicalAddress::new(0x4804_C000);
 pub const GPIO2: PhysicalAddress = PhysicalAddress::new(0x481A_C000);
 pub const GPIO3: PhysicalAddress = PhysicalAddress::new(0x481A_E000);
+/// EDMA3 channel controller
+pub const EDMA3CC: PhysicalAddress = PhysicalAddress::new(0x4900_0000);
+/// EDMA3 transfer controllers
+pub const EDMA3TC0: PhysicalAddress = PhysicalAddress::new(0x4980_0000);
+pub const EDMA3TC1: PhysicalAddress = PhysicalAddress::new(0x4990_0000);
+pub const EDMA3TC2: PhysicalAddress = PhysicalAddress::new(0x49A0_0000);
+/// Touchscreen and ADC subsystem
+pub const ADC_TSC: PhysicalAddress = PhysicalAddress::new(0x44E0_D000);
+/// PWM subsystems
+pub const PWMSS0: PhysicalAddress = PhysicalAddress::new(0x4830_0000);
+pub const PWMSS1: PhysicalAddress = PhysicalAddress::new(0x4830_2000);
+pub const PWMSS2: PhysicalAddress = PhysicalAddress::new(0x4830_4000);
+/// Enhanced PWM modules of the PWM subsystems
+pub const EPWM0: PhysicalAddress = PhysicalAddress::new(0x4830_0200);
+pub const EPWM1: PhysicalAddress = PhysicalAddress::new(0x4830_2200);
+pub const EPWM2: PhysicalAddress = PhysicalAddress::new(0x4830_4200);
+/// Enhanced capture modules of the PWM subsystems
+pub const ECAP0: PhysicalAddress = PhysicalAddress::new(0x4830_0100);
+pub const ECAP1: PhysicalAddress = PhysicalAddress::new(0x4830_2100);
+pub const ECAP2: PhysicalAddress = PhysicalAddress::new(0x4830_4100);
+/// Enhanced quadrature encoder modules of the PWM subsystems
+pub const EQEP0: PhysicalAddress = PhysicalAddress::new(0x4830_0180);
+pub const EQEP1: PhysicalAddress = PhysicalAddress::new(0x4830_2180);
+pub const EQEP2: PhysicalAddress = PhysicalAddress::new(0x4830_4180);
+/// Ethernet switch subsystem
+pub const CPSW: PhysicalAddress = PhysicalAddress::new(0x4A10_0000);
+/// MDIO of the Ethernet switch
+pub const MDIO: PhysicalAddress = PhysicalAddress::new(0x4A10_1000);
+/// Descriptor memory of the CPDMA, 8 KiB
+pub const CPPI_RAM: PhysicalAddress = PhysicalAddress::new(0x4A10_2000);
+/// MMC/SD host controllers
+pub const MMC0: PhysicalAddress = PhysicalAddress::new(0x4806_0000);
+pub const MMC1: PhysicalAddress = PhysicalAddress::new(0x481D_8000);
+pub const MMC2: PhysicalAddress = PhysicalAddress::new(0x4781_0000);
 /// Control Module
 pub const CONTROL: PhysicalAddress = PhysicalAddress::new(0x44E1_0000);